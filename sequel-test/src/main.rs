@@ -22,7 +22,7 @@ fn main() {
     // Migrate the database if needed
     conn.register::<User>().unwrap();
     conn.register::<Post>().unwrap();
-    conn.migrate();
+    conn.migrate().unwrap();
 
     // Create a new user
     let user_id = User {
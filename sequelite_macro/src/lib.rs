@@ -1,16 +1,44 @@
 #![allow(clippy::uninlined_format_args)]
 
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
 
 /// A macro for deriving the `Model` trait.
 /// 
 /// ## Attributes
 /// * #\[table_name = "name"] - Custom table name. If not specified, the table name will be the lowercase of the struct name + 's'.
 /// * #\[default_value(value)] - Default value for the column. If not specified, the default value will be NULL.
+/// * #\[on_delete(Action)] / #\[on_update(Action)] - Action performed on a `Relation` column when the referenced row is deleted or updated.
+///   Action is one of the `ColumnRelationAction` variants, eg. `#[on_delete(Cascade)]`.
+/// * #\[auto_now_add] - The column (usually a `NaiveDateTime`) is set to `CURRENT_TIMESTAMP` when a row is inserted, its value is ignored when inserting.
+/// * #\[auto_now] - Same as `auto_now_add`, but the column is also set to `CURRENT_TIMESTAMP` by every update (unless it is set explicitly).
+/// * #\[collate = "name"] - Compare the values of the column using a collation, eg. `#[collate = "NOCASE"]` for case-insensitive
+///   sorting and uniqueness. Built-in collations are `BINARY`, `NOCASE` and `RTRIM`.
+/// * #\[check = "expr"] - Adds a `CHECK(expr)` constraint to the column, eg. `#[check = "age >= 0"]`.
+///   Changing the expression rebuilds the table when migrating.
+/// * #\[references = "column"] - A `Relation` column references the given column of the related model instead of its id.
+///   The referenced column has to be marked with `#[unique]`, and the relation is created with `Relation::by_key`.
+/// * #\[unique] - The column has a `UNIQUE` constraint.
+/// * #\[renamed_from = "old_name"] - The column was renamed, the migrator will rename the old column instead of dropping it.
+/// * #\[generated = "expr"] - The column is a STORED generated column computed from the SQL expression, eg. `first_name || ' ' || last_name`.
+///   Its value is ignored when inserting.
+/// * #\[soft_delete] - Marks an `Option<NaiveDateTime>` column (eg. `deleted_at`) used for soft deletes. `Model::delete()` sets it to the current time
+///   instead of deleting rows, and `Model::select()` hides rows where it is set (use `with_deleted()` to include them).
+/// * #\[skip] - The field is not stored in the database. It is set to `Default::default()` when the model is read, so its type must implement `Default`.
+/// * #\[readonly] - Marks the model as read-only. Read-only models cannot be inserted, updated or deleted (this is checked at compile time).
+/// 
+/// ## Types
+/// All fixed-width integer types (`i8` to `i64`, `u8` to `u64`, `isize` and `usize`) and `bool` are stored as `INTEGER`.
+/// SQLite integers are signed 64-bit, so writing a `u64`/`usize` above `i64::MAX` fails with a conversion error,
+/// and values read from the database must fit in the field type (eg. `300` can't be read into a `u8`).
+/// 
+/// ## Collections
+/// Fields of type `Vec<T>` (where `T` is a primitive type other than `u8`) are not stored as columns, but in a `{table}_{field}` side table.
+/// They are loaded when the model is selected and saved when it is inserted (use `Model::save_collections` after updating them).
 /// 
 /// ## Example
-/// ```rust
+/// ```rs
 /// use sequelite::prelude::*;
 /// 
 /// #[derive(Model)]
@@ -19,7 +47,7 @@ use quote::quote;
 ///     name: String,
 /// }
 /// ```
-#[proc_macro_derive(Model, attributes(default_value, table_name))]
+#[proc_macro_derive(Model, attributes(default_value, table_name, readonly, on_delete, on_update, renamed_from, generated, skip, soft_delete, unique, references, auto_now_add, auto_now, check, collate))]
 pub fn model_derive(input: TokenStream) -> TokenStream {
     let ast: syn::DeriveInput = syn::parse(input).unwrap();
 
@@ -40,10 +68,44 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
     let mut id_column = quote!();
     let mut id_column_const = quote!();
 
-    let fields_num = fields.len();
+    // Get table name from #[table_name = "table_name"] attribute on struct or use struct name if not present
+    let table_name = match get_table_name(&ast.attrs) {
+        Some(name) => name,
+        None => name.to_string().to_lowercase() + "s",
+    };
+
+    // Fields marked with #[skip] are not persisted and are filled with Default::default() when read
+    let (skipped_fields, persisted_fields): (Vec<_>, Vec<_>) = fields.iter()
+        .partition(|field| has_attr(&field.attrs, "skip"));
+    let skipped_setters = skipped_fields.iter().map(|field| {
+        let field_name = &field.ident;
+        quote!(#field_name: Default::default(),)
+    });
+
+    // Vec<T> fields (other than Vec<u8>) are stored in side tables instead of columns
+    let (collection_fields, column_fields): (Vec<_>, Vec<_>) = persisted_fields.into_iter()
+        .partition(|field| get_collection_type(&field.ty).is_some());
+
+    let fields_num = column_fields.len();
+
+    // Column marked with #[soft_delete], it has to be nullable as NULL means the row is not deleted
+    let soft_delete_impl = match column_fields.iter().find(|field| has_attr(&field.attrs, "soft_delete")) {
+        Some(field) => {
+            if !is_option(&field.ty) {
+                panic!("#[soft_delete] column {} must be an Option<NaiveDateTime>", field.ident.as_ref().unwrap());
+            }
+            let field_name = &field.ident;
+            quote!(
+                fn soft_delete_column() -> Option<sequelite::model::Column<'static>> {
+                    Some(Self::#field_name)
+                }
+            )
+        },
+        None => quote!(),
+    };
 
     // Generate const for each field
-    let field_consts = fields.iter().enumerate().map(|(i, field)| {
+    let field_consts = column_fields.iter().enumerate().map(|(i, field)| {
         let field_name = &field.ident;
         field_names.push(field_name.clone().unwrap());
         let field_type = &field.ty;
@@ -70,6 +132,10 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
             }
         }
 
+        if has_attr(&field.attrs, "unique") {
+            flags.push(quote!(sequelite::sql_types::SqliteFlag::Unique));
+        }
+
         // Get type of field ensuring that if it is an Option<T>, we get the inner type
         let field_type = if field_option {
             match field_type {
@@ -121,132 +187,88 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
         // Generate setter for column value
         let setter = if field_option {
             quote!(
-                #field_name: row.get(#i + offset).ok(),
+                #field_name: sequelite::model::parse_optional_column(row, #i + offset, stringify!(#field_name))?,
             )
         } else {
             quote!(
-                #field_name: row.get(#i + offset).unwrap(),
+                #field_name: row.get(#i + offset)?,
             )
         };
         column_value_setters.push(setter);
 
         let mut relation = quote!(None);
+        let mut referenced_column_const = quote!();
 
-        // Get sqlitetype from field type
+        // Get sqlitetype from field type. Relations have the type of the column they reference,
+        // every other type decides its column type through SqliteTyped (which reports unsupported types, pointing at the field)
         let field_type = match field_type {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segments = &path.segments;
-                if segments.len() == 1 {
-                    let segment = &segments[0];
-                    let ident = &segment.ident;
-                    // Check Vec<u8>
-                    if ident == "Vec" {
-                        // Get inner type
-                        let inner_type = &segment.arguments;
-
-                        match inner_type {
-                            syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) => {
-                                if args.len() == 1 {
-                                    let arg = &args[0];
-                                    match arg {
-                                        syn::GenericArgument::Type(ty) => {
-                                            let ty = match ty {
-                                                syn::Type::Path(syn::TypePath { path, .. }) => {
-                                                    let segments = &path.segments;
-                                                    if segments.len() == 1 {
-                                                        let segment = &segments[0];
-                                                        let ident = &segment.ident;
-                                                        ident.to_string()
-                                                    } else {
-                                                        panic!("Only one type is supported");
-                                                    }
-                                                }
-                                                _ => panic!("Only types are supported"),
-                                            };
-                                            if ty == "u8" {
-                                                quote!(sequelite::sql_types::SqliteType::Blob)
+            syn::Type::Path(syn::TypePath { path, .. }) if path.segments.len() == 1 && path.segments[0].ident == "Relation" => {
+                let segment = &path.segments[0];
+                // Get inner type and save identifier
+                let inner_type = &segment.arguments;
+
+                let relation_type = match inner_type {
+                    syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) => {
+                        if args.len() == 1 {
+                            let arg = &args[0];
+                            match arg {
+                                syn::GenericArgument::Type(ty) => {
+                                    let ty = match ty {
+                                        syn::Type::Path(syn::TypePath { path, .. }) => {
+                                            let segments = &path.segments;
+                                            if segments.len() == 1 {
+                                                let segment = &segments[0];
+                                                &segment.ident
                                             } else {
-                                                panic!("Unsupported type: {:?}", segments);
+                                                panic!("Only one type is supported");
                                             }
                                         }
                                         _ => panic!("Only types are supported"),
-                                    }
-                                } else {
-                                    panic!("Only one type is supported");
+                                    };
+                                    ty
                                 }
+                                _ => panic!("Only types are supported"),
                             }
-                            _ => panic!("Only types are supported"),
+                        } else {
+                            panic!("Only one type is supported");
                         }
-                    } else if ident == "Relation" {
-                        // Get inner type and save identifier
-                        let inner_type = &segment.arguments;
-
-                        let relation_type = match inner_type {
-                            syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) => {
-                                if args.len() == 1 {
-                                    let arg = &args[0];
-                                    match arg {
-                                        syn::GenericArgument::Type(ty) => {
-                                            let ty = match ty {
-                                                syn::Type::Path(syn::TypePath { path, .. }) => {
-                                                    let segments = &path.segments;
-                                                    if segments.len() == 1 {
-                                                        let segment = &segments[0];
-                                                        &segment.ident
-                                                    } else {
-                                                        panic!("Only one type is supported");
-                                                    }
-                                                }
-                                                _ => panic!("Only types are supported"),
-                                            };
-                                            ty
-                                        }
-                                        _ => panic!("Only types are supported"),
-                                    }
-                                } else {
-                                    panic!("Only one type is supported");
-                                }
-                            }
-                            _ => panic!("Only types are supported"),
-                        };
-
-                        // Set relation
-                        relation = quote!(Some(sequelite::model::relation::ColumnRelation::new(#relation_type::TABLE_NAME_CONST, Self::TABLE_NAME_CONST, "id", &#relation_type::ID_COLUMN_CONST, stringify!(#field_name))));
+                    }
+                    _ => panic!("Only types are supported"),
+                };
 
-                        // And setter
-                        column_value_setters[i] = quote!(
-                            #field_name: Relation::<#relation_type>::parse_from_row(&row, offset, #i, &mut offset_counter, joins.contains(&stringify!(#field_name).to_string())),
+                // Set relation
+                let on_delete = get_relation_action(&field.attrs, "on_delete");
+                let on_update = get_relation_action(&field.attrs, "on_update");
+                // The relation references the id, unless #[references = "column"] is specified
+                let (key_name, key_column, key_type) = match get_string_attr(&field.attrs, "references") {
+                    Some(column) => {
+                        // References can't be taken inside of a const call, so the column gets its own const
+                        let column_ident = syn::Ident::new(&column, proc_macro2::Span::call_site());
+                        let const_ident = syn::Ident::new(&format!("{}_REFERENCED_COLUMN", field_name.as_ref().unwrap()).to_uppercase(), proc_macro2::Span::call_site());
+                        referenced_column_const = quote!(
+                            pub const #const_ident: &'static sequelite::model::Column<'static> = &#relation_type::#column_ident;
                         );
+                        // The foreign key has the type of the referenced column (eg. TEXT for an email)
+                        (column, quote!(Self::#const_ident), quote!(Self::#const_ident.ty))
+                    },
+                    None => ("id".to_string(), quote!(#relation_type::ID_COLUMN_CONST), quote!(sequelite::sql_types::SqliteType::Integer)),
+                };
+                relation = quote!(Some(sequelite::model::relation::ColumnRelation::new(#relation_type::TABLE_NAME_CONST, Self::TABLE_NAME_CONST, #key_name, #key_column, stringify!(#field_name))#on_delete #on_update));
 
-
-                        // Get relation type
-                        quote!(sequelite::sql_types::SqliteType::Integer)                        
-                    } else {
-                        // Other types
-                        match ident.to_string().as_str() {
-                            "String" => quote!(sequelite::sql_types::SqliteType::Text),
-                            "i32" => quote!(sequelite::sql_types::SqliteType::Integer),
-                            "i64" => quote!(sequelite::sql_types::SqliteType::Integer),
-                            "f32" => quote!(sequelite::sql_types::SqliteType::Real),
-                            "f64" => quote!(sequelite::sql_types::SqliteType::Real),
-                            "bool" => quote!(sequelite::sql_types::SqliteType::Integer),
-                            _ => panic!("Unsupported type: {:?}", segments),
-                        }
-                    }
-                } else if segments.len() == 2 {
-                    // Expect that 3rd segment is NaiveDateTime
-                    let segment = &segments[1];
-                    let ident = &segment.ident;
-                    if ident == "NaiveDateTime" {
-                        quote!(sequelite::sql_types::SqliteType::DateTime)
-                    } else {
-                        panic!("Type {} not supported", ident);
-                    }
+                // And setter, Option<Relation<T>> is a nullable foreign key
+                let parse_fn = if field_option {
+                    quote!(parse_optional_from_row)
                 } else {
-                    panic!("Type {:?} not supported", segments);
-                }
+                    quote!(parse_from_row)
+                };
+                column_value_setters[i] = quote!(
+                    #field_name: Relation::<#relation_type>::#parse_fn(row, offset, #i, &mut offset_counter, joins.contains(&stringify!(#field_name).to_string()), #key_column)?,
+                );
+
+
+                key_type
             }
-            _ => panic!("Only types are supported"),
+            other => quote_spanned!(other.span()=> <#other as sequelite::sql_types::SqliteTyped>::SQLITE_TYPE),
         };
 
         // If field has #[default(...)] attribute, set default value
@@ -264,16 +286,125 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
             }
         }
 
+        // If field has #[auto_now_add] or #[auto_now] attribute, it is set by the database
+        let auto_now = if has_attr(&field.attrs, "auto_now") {
+            quote!(.auto_now())
+        } else if has_attr(&field.attrs, "auto_now_add") {
+            quote!(.auto_now_add())
+        } else {
+            quote!()
+        };
+        if !auto_now.is_empty() && default_value.to_string() == "None" {
+            default_value = quote!(Some(&sequelite::sql_types::NowTime));
+        }
+
+        // If field has #[renamed_from = "old_name"] attribute, keep track of the old name for migrations
+        let renamed_from = match get_string_attr(&field.attrs, "renamed_from") {
+            Some(old_name) => quote!(.renamed_from(#old_name)),
+            None => quote!(),
+        };
+
+        // If field has #[collate = "name"] attribute, compare its values using the collation
+        let collate = match get_string_attr(&field.attrs, "collate") {
+            Some(collation) => quote!(.collation(#collation)),
+            None => quote!(),
+        };
+
+        // If field has #[check = "expr"] attribute, add a CHECK constraint
+        let check = match get_string_attr(&field.attrs, "check") {
+            Some(expr) => quote!(.check(#expr)),
+            None => quote!(),
+        };
+
+        // If field has #[generated = "expr"] attribute, the column is computed by the database
+        let generated = match get_string_attr(&field.attrs, "generated") {
+            Some(expr) => quote!(.generated(#expr)),
+            None => quote!(),
+        };
+
         quote!(
+            #referenced_column_const
             pub const #field_name: sequelite::model::Column<'static> = 
-                sequelite::model::Column::new_const(stringify!(#field_name), Self::TABLE_NAME_CONST, #field_type, &[#(#flags),*], #default_value, #relation);
+                sequelite::model::Column::new_const(stringify!(#field_name), Self::TABLE_NAME_CONST, #field_type, &[#(#flags),*], #default_value, #relation)#renamed_from #generated #collate #check #auto_now;
         )
     });
 
-    // Get table name from #[table_name = "table_name"] attribute on struct or use struct name if not present
-    let table_name = match get_table_name(&ast.attrs) {
-        Some(name) => name,
-        None => name.to_string().to_lowercase() + "s",
+    // Generate side tables and their loading/saving for Vec<T> fields
+    let mut collections = Vec::new();
+    let mut collection_consts = Vec::new();
+    let mut collection_setters = Vec::new();
+    let mut collection_loaders = Vec::new();
+    let mut collection_values = Vec::new();
+    for (i, field) in collection_fields.iter().enumerate() {
+        let field_name = &field.ident;
+        let value_type = get_collection_type(&field.ty).unwrap();
+        let value_type = quote_spanned!(value_type.span()=> <#value_type as sequelite::sql_types::SqliteTyped>::SQLITE_TYPE);
+        let collection_table = format!("{}_{}", table_name, field_name.as_ref().unwrap());
+
+        // Columns are kept in a separate const so that the slice is 'static
+        let columns_const = syn::Ident::new(&format!("{}_COLLECTION_COLUMNS", field_name.as_ref().unwrap()).to_uppercase(), proc_macro2::Span::call_site());
+        collection_consts.push(quote!(
+            pub const #columns_const: &'static [sequelite::model::Column<'static>] = &[
+                sequelite::model::Column::new_const("id", #collection_table, sequelite::sql_types::SqliteType::Integer, &[
+                    sequelite::sql_types::SqliteFlag::PrimaryKey,
+                    sequelite::sql_types::SqliteFlag::AutoIncrement,
+                    sequelite::sql_types::SqliteFlag::NotNull
+                ], None, None),
+                sequelite::model::Column::new_const("parent_id", #collection_table, sequelite::sql_types::SqliteType::Integer, &[sequelite::sql_types::SqliteFlag::NotNull], None,
+                    Some(sequelite::model::relation::ColumnRelation::new(Self::TABLE_NAME_CONST, #collection_table, "id", Self::ID_COLUMN_CONST, "parent_id")
+                        .on_delete(sequelite::model::relation::ColumnRelationAction::Cascade))),
+                sequelite::model::Column::new_const("value", #collection_table, #value_type, &[sequelite::sql_types::SqliteFlag::NotNull], None, None),
+            ];
+        ));
+        collections.push(quote!(
+            sequelite::model::collection::ModelCollection::new(#collection_table, Self::#columns_const)
+        ));
+        collection_setters.push(quote!(#field_name: Vec::new(),));
+
+        // One query per side table for all of the models
+        collection_loaders.push(quote!(
+            let mut values = #name::COLLECTIONS_SLICE[#i].load_values(&ids, conn)?;
+            for model in models.iter_mut() {
+                model.#field_name = values.remove(&model.get_id()).unwrap_or_default();
+            }
+        ));
+
+        collection_values.push(quote!(
+            sequelite::model::collection::CollectionValues {
+                collection: &#name::COLLECTIONS_SLICE[#i],
+                values: self.#field_name.iter().map(|value| Box::new(value.clone()) as Box<dyn sequelite::model::SqliteToSql>).collect(),
+            }
+        ));
+    }
+
+    let collection_impl = if collections.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            fn collections() -> &'static [sequelite::model::collection::ModelCollection] {
+                #name::COLLECTIONS_SLICE
+            }
+
+            fn collection_values(&self) -> Vec<sequelite::model::collection::CollectionValues> {
+                vec![#(#collection_values),*]
+            }
+
+            fn load_all_collections(models: &mut [Self], conn: &sequelite::connection::Connection) -> Result<(), sequelite::rusqlite::Error> {
+                if models.is_empty() {
+                    return Ok(());
+                }
+                let ids = models.iter().map(|model| model.get_id()).collect::<Vec<_>>();
+                #(#collection_loaders)*
+                Ok(())
+            }
+        )
+    };
+
+    // Models marked with #[readonly] cannot be written to
+    let writable_impl = if has_attr(&ast.attrs, "readonly") {
+        quote!()
+    } else {
+        quote!(impl sequelite::model::WritableModel for #name {})
     };
 
     quote!(
@@ -288,6 +419,12 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
             pub const TABLE_NAME_CONST: &'static str = #table_name;
             pub const ID_COLUMN_CONST: &'static sequelite::model::Column<'static> = &#id_column_const;
             pub const FIELDS_NUM_CONST: usize = #fields_num;
+
+            #(#collection_consts)*
+
+            pub const COLLECTIONS_SLICE: &'static [sequelite::model::collection::ModelCollection] = &[
+                #(#collections),*
+            ];
         }
 
         impl sequelite::model::Model for #name {
@@ -319,31 +456,185 @@ pub fn model_derive(input: TokenStream) -> TokenStream {
                 panic!("Unknown column: {}", column.name_const());
             }
 
-            fn parse_row(row: &sequelite::model::SqliteRow, offset: usize, joins: &Vec<String>) -> Self {
+            fn parse_row(row: &sequelite::model::SqliteRow, offset: usize, joins: &Vec<String>) -> Result<Self, sequelite::rusqlite::Error> {
                 let mut offset_counter = Self::FIELDS_NUM_CONST;
-                Self {
+                Ok(Self {
                     #(#column_value_setters)*
-                }
+                    #(#collection_setters)*
+                    #(#skipped_setters)*
+                })
             }
 
-            fn parse_rows(mut rows: sequelite::model::SqliteRows, offset: usize, joins: &Vec<String>) -> Vec<Self> {
+            fn parse_rows(mut rows: sequelite::model::SqliteRows, offset: usize, joins: &Vec<String>) -> Result<Vec<Self>, sequelite::rusqlite::Error> {
                 let mut temp = Vec::new();
-                let mut row_counter = 0;
+                while let Some(row) = rows.next()? {
+                    temp.push(Self::parse_row(row, offset, joins)?);
+                }
+                Ok(temp)
+            }
+
+            #collection_impl
+            #soft_delete_impl
+        }
+
+        #writable_impl
+    ).into()
+}
+
+/// A macro for deriving the `SqliteEnum` trait on fieldless enums, which allows them to be used as model columns.
+/// 
+/// ## Attributes
+/// * #\[store_as_text] - Store variants by their name instead of their discriminant.
+/// 
+/// ## Example
+/// ```rs
+/// use sequelite::prelude::*;
+/// 
+/// #[derive(Debug, Clone, PartialEq, SqliteEnum)]
+/// enum Status {
+///     Active,
+///     Banned = 10,
+/// }
+/// 
+/// #[derive(Model)]
+/// struct User {
+///     id: Option<i32>,
+///     status: Status,
+/// }
+/// ```
+#[proc_macro_derive(SqliteEnum, attributes(store_as_text))]
+pub fn sqlite_enum_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = ast.ident;
 
-                while let Some(row) = rows.next().unwrap() {
-                    temp.push(Self::parse_row(&row, offset, joins));
+    let variants = match ast.data {
+        syn::Data::Enum(ref data) => &data.variants,
+        _ => panic!("SqliteEnum can only be derived for enums"),
+    };
 
-                    row_counter += 1;
+    let variant_names = variants.iter().map(|variant| {
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            panic!("SqliteEnum variants cannot have fields");
+        }
+        &variant.ident
+    }).collect::<Vec<_>>();
+
+    let (sqlite_type, to_sql, from_sql) = if has_attr(&ast.attrs, "store_as_text") {
+        (
+            quote!(sequelite::sql_types::SqliteType::Text),
+            quote!(
+                match self {
+                    #(#name::#variant_names => stringify!(#variant_names),)*
+                }
+            ),
+            quote!(
+                match value.as_str()? {
+                    #(stringify!(#variant_names) => Ok(#name::#variant_names),)*
+                    other => Err(sequelite::rusqlite::types::FromSqlError::Other(
+                        format!("Invalid {} value: {}", stringify!(#name), other).into()
+                    )),
+                }
+            )
+        )
+    } else {
+        (
+            quote!(sequelite::sql_types::SqliteType::Integer),
+            quote!(
+                match self {
+                    #(#name::#variant_names => #name::#variant_names as i64,)*
                 }
-                temp
+            ),
+            quote!(
+                match value.as_i64()? {
+                    #(v if v == #name::#variant_names as i64 => Ok(#name::#variant_names),)*
+                    other => Err(sequelite::rusqlite::types::FromSqlError::OutOfRange(other)),
+                }
+            )
+        )
+    };
+
+    quote!(
+        impl sequelite::sql_types::SqliteEnum for #name {
+            const SQLITE_TYPE: sequelite::sql_types::SqliteType = #sqlite_type;
+        }
+
+        impl sequelite::sql_types::SqliteTyped for #name {
+            const SQLITE_TYPE: sequelite::sql_types::SqliteType = #sqlite_type;
+        }
+
+        impl sequelite::rusqlite::ToSql for #name {
+            fn to_sql(&self) -> sequelite::rusqlite::Result<sequelite::rusqlite::types::ToSqlOutput<'_>> {
+                Ok(sequelite::rusqlite::types::ToSqlOutput::from(#to_sql))
+            }
+        }
+
+        impl sequelite::rusqlite::types::FromSql for #name {
+            fn column_result(value: sequelite::rusqlite::types::ValueRef<'_>) -> sequelite::rusqlite::types::FromSqlResult<Self> {
+                #from_sql
+            }
+        }
+    ).into()
+}
+
+/// A macro for deriving the `FromRow` trait, which allows parsing query results into structs which are not models.
+/// 
+/// Fields of structs with named fields are read from the columns with the same name,
+/// fields of tuple structs are read by their position.
+/// 
+/// ## Example
+/// ```rs
+/// use sequelite::prelude::*;
+/// 
+/// #[derive(FromRow)]
+/// struct UserName {
+///     id: i32,
+///     name: String,
+/// }
+/// 
+/// let names = User::select()
+///     .columns(&[User::id, User::name])
+///     .select_as::<UserName>()
+///     .exec(&conn).unwrap();
+/// ```
+#[proc_macro_derive(FromRow)]
+pub fn from_row_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let name = ast.ident;
+
+    let fields = match ast.data {
+        syn::Data::Struct(ref data) => &data.fields,
+        _ => panic!("FromRow can only be derived for structs"),
+    };
+
+    let construct = match fields {
+        syn::Fields::Named(fields) => {
+            let field_names = fields.named.iter().map(|field| field.ident.as_ref().unwrap());
+            quote!(#name { #(#field_names: row.get(stringify!(#field_names))?,)* })
+        }
+        syn::Fields::Unnamed(fields) => {
+            let indices = (0..fields.unnamed.len()).map(proc_macro2::Literal::usize_suffixed);
+            quote!(#name ( #(row.get(#indices)?,)* ))
+        }
+        syn::Fields::Unit => quote!(#name),
+    };
+
+    quote!(
+        impl sequelite::model::FromRow for #name {
+            fn from_row(row: &sequelite::rusqlite::Row) -> Result<Self, sequelite::rusqlite::Error> {
+                Ok(#construct)
             }
         }
     ).into()
 }
 
 fn get_table_name(attrs: &[syn::Attribute]) -> Option<String> {
+    get_string_attr(attrs, "table_name")
+}
+
+// Get value of #[name = "value"] attribute
+fn get_string_attr(attrs: &[syn::Attribute], name: &str) -> Option<String> {
     for attr in attrs {
-        if attr.path.get_ident().unwrap() == "table_name" {
+        if attr.path.is_ident(name) {
             // Expect = symbol and string literal
             let mut tokens = attr.tokens.clone().into_iter();
 
@@ -370,6 +661,54 @@ fn get_table_name(attrs: &[syn::Attribute]) -> Option<String> {
     None
 }
 
+// Get #[on_delete(Action)] or #[on_update(Action)] as a call on ColumnRelation
+fn get_relation_action(attrs: &[syn::Attribute], name: &str) -> proc_macro2::TokenStream {
+    for attr in attrs {
+        if attr.path.is_ident(name) {
+            let action: syn::Ident = match attr.parse_args() {
+                Ok(action) => action,
+                Err(_) => panic!("Expected #[{}(Action)]", name),
+            };
+            let method = syn::Ident::new(name, proc_macro2::Span::call_site());
+            return quote!(.#method(sequelite::model::relation::ColumnRelationAction::#action));
+        }
+    }
+
+    quote!()
+}
+
+// Get the value type of a Vec<T> field which should be stored in a side table (Vec<u8> is a blob)
+fn get_collection_type(field_type: &syn::Type) -> Option<&syn::Ident> {
+    let segment = match field_type {
+        syn::Type::Path(syn::TypePath { path, .. }) if path.segments.len() == 1 => &path.segments[0],
+        _ => return None,
+    };
+    if segment.ident != "Vec" {
+        return None;
+    }
+
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) if args.len() == 1 => {
+            match &args[0] {
+                syn::GenericArgument::Type(syn::Type::Path(syn::TypePath { path, .. })) if path.segments.len() == 1 => {
+                    let ident = &path.segments[0].ident;
+                    if ident == "u8" {
+                        None
+                    } else {
+                        Some(ident)
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
+
 fn is_option(field_type: &syn::Type) -> bool {
     match field_type {
         syn::Type::Path(syn::TypePath { path, .. }) => {
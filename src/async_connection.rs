@@ -0,0 +1,188 @@
+use std::{sync::{atomic::{AtomicUsize, Ordering}, mpsc, Arc, Condvar, Mutex}, thread, time::Duration};
+
+use futures::{channel::{mpsc as async_mpsc, oneshot}, Stream, StreamExt};
+
+use crate::connection::Connection;
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// Number of rows sent from the connection thread at once when streaming.
+const ASYNC_STREAM_BATCH: usize = 64;
+
+/// Number of batches a stream reads ahead of its consumer.
+const STREAM_BATCHES_AHEAD: usize = 2;
+
+// Jobs waiting for the connection thread, streams stop waiting for their consumer while there are any
+#[derive(Default)]
+struct JobQueue {
+    waiting: Mutex<usize>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    fn update(&self, f: impl FnOnce(&mut usize)) {
+        f(&mut self.waiting.lock().unwrap());
+        self.changed.notify_all();
+    }
+}
+
+/// A connection to a SQLite database which can be used from async code.
+/// 
+/// SQLite is blocking, so the [Connection](Connection) lives on a dedicated thread and every call is sent to it.
+/// 
+/// ## Example
+/// ```rs
+/// let conn = AsyncConnection::new("my_database.db").await.unwrap();
+/// conn.call(|conn| {
+///     conn.register::<User>()?;
+///     conn.migrate().unwrap();
+///     Ok(())
+/// }).await.unwrap();
+/// 
+/// let users = conn.call(|conn| User::select().exec(conn)).await.unwrap();
+/// ```
+/// 
+/// ## Note
+/// This requires the `async` feature to be enabled.
+pub struct AsyncConnection {
+    sender: mpsc::Sender<Job>,
+    queue: Arc<JobQueue>,
+}
+
+impl AsyncConnection {
+    /// Creates a new connection to a SQLite database on a dedicated thread.
+    /// 
+    /// ## Arguments
+    /// * `path` - The path to the database file.
+    pub async fn new(path: &str) -> Result<Self, rusqlite::Error> {
+        let path = path.to_string();
+        Self::open(move || Connection::new(&path)).await
+    }
+
+    /// Creates a new connection to a transient SQLite database in memory on a dedicated thread.
+    pub async fn new_memory() -> Result<Self, rusqlite::Error> {
+        Self::open(Connection::new_memory).await
+    }
+
+    async fn open<F>(open: F) -> Result<Self, rusqlite::Error>
+    where
+        F: FnOnce() -> Result<Connection, rusqlite::Error> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (opened_sender, opened) = oneshot::channel();
+        let queue = Arc::new(JobQueue::default());
+        let thread_queue = queue.clone();
+
+        thread::spawn(move || {
+            let mut conn = match open() {
+                Ok(conn) => {
+                    let _ = opened_sender.send(Ok(()));
+                    conn
+                },
+                Err(error) => {
+                    let _ = opened_sender.send(Err(error));
+                    return;
+                }
+            };
+
+            // Run jobs until the AsyncConnection is dropped
+            while let Ok(job) = receiver.recv() {
+                thread_queue.update(|waiting| *waiting -= 1);
+                job(&mut conn);
+            }
+        });
+
+        opened.await.expect("Connection thread stopped")?;
+        Ok(AsyncConnection { sender, queue })
+    }
+
+    fn submit(sender: &mpsc::Sender<Job>, queue: &JobQueue, job: Job) {
+        queue.update(|waiting| *waiting += 1);
+        sender.send(job).expect("Connection thread stopped");
+    }
+
+    /// Run a function with the underlying connection on the connection thread.
+    /// 
+    /// ## Arguments
+    /// * `f` - The function to run, its result is returned.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let count = conn.call(|conn| User::count().exec(conn)).await.unwrap();
+    /// ```
+    pub async fn call<R, F>(&self, f: F) -> R
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Connection) -> R + Send + 'static,
+    {
+        let (result_sender, result) = oneshot::channel();
+        Self::submit(&self.sender, &self.queue, Box::new(move |conn| {
+            let _ = result_sender.send(f(conn));
+        }));
+
+        result.await.expect("Connection thread stopped")
+    }
+
+    /// Run a function on the connection thread which produces values one by one, and send them back in batches.
+    /// The `send` callback passed to the function returns false once the stream has been dropped.
+    /// 
+    /// The function is started when the stream is first polled. It reads at most a few batches ahead of the consumer,
+    /// unless other calls are waiting for the connection, then the remaining values are read without waiting so they are not blocked.
+    pub(crate) fn stream<T, F>(&self, f: F) -> impl Stream<Item = Result<T, rusqlite::Error>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection, &mut dyn FnMut(T) -> bool) -> Result<(), rusqlite::Error> + Send + 'static,
+    {
+        let (sender, receiver) = async_mpsc::unbounded::<Result<Vec<T>, rusqlite::Error>>();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+
+        let job_queue = self.queue.clone();
+        let job_in_flight = in_flight.clone();
+        let job: Job = Box::new(move |conn| {
+            let send_batch = |batch| {
+                job_in_flight.fetch_add(1, Ordering::SeqCst);
+                sender.unbounded_send(batch).is_ok()
+            };
+
+            let mut batch = Vec::with_capacity(ASYNC_STREAM_BATCH);
+            let result = f(conn, &mut |value| {
+                batch.push(value);
+                if batch.len() < ASYNC_STREAM_BATCH {
+                    return true;
+                }
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(ASYNC_STREAM_BATCH));
+                if !send_batch(Ok(full)) {
+                    return false;
+                }
+
+                // Wait for the consumer, the timeout catches streams dropped while waiting
+                let mut waiting = job_queue.waiting.lock().unwrap();
+                while job_in_flight.load(Ordering::SeqCst) >= STREAM_BATCHES_AHEAD && *waiting == 0 && !sender.is_closed() {
+                    waiting = job_queue.changed.wait_timeout(waiting, Duration::from_millis(50)).unwrap().0;
+                }
+                !sender.is_closed()
+            });
+
+            send_batch(result.map(|()| batch));
+        });
+
+        // Nothing runs on the connection thread until the stream is polled
+        let jobs = self.sender.clone();
+        let queue = self.queue.clone();
+        let receiver = futures::stream::once(futures::future::lazy(move |_| {
+            Self::submit(&jobs, &queue, job);
+            receiver
+        })).flatten();
+
+        let queue = self.queue.clone();
+        receiver.flat_map(move |batch| {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            queue.update(|_| ());
+            let items = match batch {
+                Ok(values) => values.into_iter().map(Ok).collect(),
+                Err(error) => vec![Err(error)],
+            };
+            futures::stream::iter(items)
+        })
+    }
+}
@@ -1,7 +1,142 @@
 use log::{info, debug, warn};
-use rusqlite::{ToSql, types::{ToSqlOutput, ValueRef}};
+use rusqlite::{ToSql, OptionalExtension, types::{FromSql, ToSqlOutput, ValueRef}};
 
-use crate::{model::{Model, Column, migrator::{DbSchema, Migrator}}, IntoSqliteTy, sql_types::{SqliteFlag, SqliteType}};
+use crate::{model::{Model, WritableModel, Column, FromRow, DefaultExpression, collection::CollectionValues, migrator::{DbSchema, Migrator, create_table_if_not_exists_statement, MigrationPolicy, MigrationError, MigrationReport, MigrationStep, MIGRATIONS_TABLE}}, IntoSqlite, IntoSqliteTy, sql_types::{SqliteFlag, SqliteType}};
+
+/// SQLite journal mode, set using `PRAGMA journal_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off
+}
+
+impl JournalMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF"
+        }
+    }
+}
+
+/// SQLite synchronous level, set using `PRAGMA synchronous`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra
+}
+
+impl Synchronous {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA"
+        }
+    }
+}
+
+/// Options applied to a connection right after it is opened.
+/// Options that are not set keep the SQLite defaults.
+/// 
+/// ## Example
+/// ```rs
+/// let options = ConnectionOptions::new()
+///     .journal_mode(JournalMode::Wal)
+///     .synchronous(Synchronous::Normal)
+///     .busy_timeout_ms(5000)
+///     .foreign_keys(true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    journal_mode: Option<JournalMode>,
+    synchronous: Option<Synchronous>,
+    busy_timeout_ms: Option<u64>,
+    foreign_keys: Option<bool>
+}
+
+impl ConnectionOptions {
+    /// Creates empty connection options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the journal mode of the database.
+    pub fn journal_mode(self, mode: JournalMode) -> Self {
+        Self { journal_mode: Some(mode), ..self }
+    }
+
+    /// Set the synchronous level of the database.
+    pub fn synchronous(self, level: Synchronous) -> Self {
+        Self { synchronous: Some(level), ..self }
+    }
+
+    /// Set how long (in milliseconds) to wait for a locked database before returning `SQLITE_BUSY`.
+    pub fn busy_timeout_ms(self, timeout: u64) -> Self {
+        Self { busy_timeout_ms: Some(timeout), ..self }
+    }
+
+    /// Enable or disable foreign key enforcement.
+    /// Connections enforce foreign keys by default, so this is mostly useful to turn them off.
+    pub fn foreign_keys(self, enabled: bool) -> Self {
+        Self { foreign_keys: Some(enabled), ..self }
+    }
+
+    fn apply(&self, conn: &Connection) -> Result<(), rusqlite::Error> {
+        let connection = &conn.connection;
+        if let Some(timeout) = self.busy_timeout_ms {
+            conn.set_busy_timeout(std::time::Duration::from_millis(timeout))?;
+        }
+        if let Some(mode) = self.journal_mode {
+            conn.set_journal_mode(mode)?;
+        }
+        if let Some(level) = self.synchronous {
+            connection.pragma_update(None, "synchronous", level.as_str())?;
+        }
+        if let Some(enabled) = self.foreign_keys {
+            connection.pragma_update(None, "foreign_keys", enabled)?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    // Schema alias set by Connection::with_schema
+    static SCHEMA: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Quote an identifier (eg. a table name), so that it can be safely used in SQL.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Prefix the table name with the schema set by [Connection::with_schema](Connection::with_schema), if any.
+pub(crate) fn qualified_table_name(table: &str) -> String {
+    SCHEMA.with(|schema| match schema.borrow().as_ref() {
+        Some(schema) => format!("{}.{}", schema, table),
+        None => table.to_string(),
+    })
+}
+
+// Restores the previous schema, even if the closure panics
+struct SchemaGuard(Option<String>);
+
+impl Drop for SchemaGuard {
+    fn drop(&mut self) {
+        SCHEMA.with(|schema| *schema.borrow_mut() = self.0.take());
+    }
+}
 
 /// A connection to a SQLite database. This is the main entry point for interacting with the database.
 /// 
@@ -11,11 +146,13 @@ use crate::{model::{Model, Column, migrator::{DbSchema, Migrator}}, IntoSqliteTy
 /// ```
 pub struct Connection {
     pub connection: rusqlite::Connection,
-    latest_schema: DbSchema<'static>
+    latest_schema: DbSchema<'static>,
+    migration_policy: MigrationPolicy
 }
 
 impl Connection {
     /// Creates a new connection to a SQLite database.
+    /// Foreign key enforcement is enabled on the new connection.
     /// 
     /// ## Arguments
     /// * `path` - The path to the database file.
@@ -26,11 +163,27 @@ impl Connection {
     /// ```
     pub fn new(path: &str) -> Result<Self, rusqlite::Error> {
         let connection = rusqlite::Connection::open(path)?;
-        let _ = env_logger::try_init();
-        Ok(Connection {
-            connection,
-            latest_schema: DbSchema::new()
-        })
+        Self::init(connection)
+    }
+
+    /// Creates a new connection to a SQLite database and configures it using the given options.
+    /// 
+    /// ## Arguments
+    /// * `path` - The path to the database file.
+    /// * `options` - The options to apply after opening the database.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let options = ConnectionOptions::new()
+    ///     .journal_mode(JournalMode::Wal)
+    ///     .busy_timeout_ms(5000)
+    ///     .foreign_keys(true);
+    /// let mut conn = Connection::new_with_options("my_database.db", options).unwrap();
+    /// ```
+    pub fn new_with_options(path: &str, options: ConnectionOptions) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::new(path)?;
+        options.apply(&conn)?;
+        Ok(conn)
     }
 
     /// Creates a new connection to a transient SQLite database in memory.
@@ -41,10 +194,197 @@ impl Connection {
     /// ```
     pub fn new_memory() -> Result<Self, rusqlite::Error> {
         let connection = rusqlite::Connection::open_in_memory()?;
+        Self::init(connection)
+    }
+
+    /// Opens a connection to a SQLite database using the given flags.
+    /// 
+    /// ## Arguments
+    /// * `path` - The path to the database file.
+    /// * `flags` - The flags to open the database with, eg. to open it read-only or fail if it doesn't exist.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let conn = Connection::open_with_flags("my_database.db", OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+    /// ```
+    pub fn open_with_flags(path: &str, flags: rusqlite::OpenFlags) -> Result<Self, rusqlite::Error> {
+        let connection = rusqlite::Connection::open_with_flags(path, flags)?;
+        Self::init(connection)
+    }
+
+    /// Opens an existing SQLite database in read-only mode.
+    /// Any attempt to write to the database will return an error.
+    /// 
+    /// ## Arguments
+    /// * `path` - The path to the database file.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let conn = Connection::open_readonly("my_database.db").unwrap();
+    /// let users = User::select().exec(&conn).unwrap();
+    /// ```
+    pub fn open_readonly(path: &str) -> Result<Self, rusqlite::Error> {
+        Self::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX | rusqlite::OpenFlags::SQLITE_OPEN_URI)
+    }
+
+    /// Wraps an already opened rusqlite connection, eg. one with custom SQL functions registered.
+    /// The connection starts with an empty schema, so models still have to be registered.
+    /// 
+    /// ## Note
+    /// Like every other constructor this runs `PRAGMA foreign_keys = ON` on the wrapped connection,
+    /// so relations are enforced even if the caller turned them off. Use `set_foreign_keys(false)` to undo it.
+    /// With the `decimal` feature the decimal SQL functions are registered as well.
+    /// 
+    /// ## Arguments
+    /// * `connection` - The rusqlite connection to use.
+    /// 
+    /// ## Errors
+    /// If the pragma can't be set or the functions can't be registered.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let raw = rusqlite::Connection::open("database.db").unwrap();
+    /// let mut conn = Connection::from_rusqlite(raw).unwrap();
+    /// ```
+    pub fn from_rusqlite(connection: rusqlite::Connection) -> Result<Self, rusqlite::Error> {
+        Self::init(connection)
+    }
+
+    /// Run a closure with all model queries targeting tables in an attached database.
+    /// 
+    /// ## Arguments
+    /// * `schema` - The alias of the attached database (or `main`/`temp`).
+    /// * `f` - The closure to run, queries have to be created and executed inside of it.
+    /// 
+    /// ## Panics
+    /// If the schema name is not a valid identifier
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.exec_raw("ATTACH DATABASE 'tenant1.db' AS tenant1", &[]).unwrap();
+    /// let users = conn.with_schema("tenant1", |conn| {
+    ///     User::select().exec(conn).unwrap()
+    /// });
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM tenant1.users;
+    /// ```
+    /// 
+    /// ## Notes
+    /// The schema is kept per thread. Migrations and collection side tables ignore it.
+    pub fn with_schema<R>(&self, schema: &str, f: impl FnOnce(&Self) -> R) -> R {
+        if schema.is_empty() || !schema.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            panic!("Invalid schema name: {}", schema);
+        }
+        let previous = SCHEMA.with(|current| current.borrow_mut().replace(schema.to_string()));
+        let _guard = SchemaGuard(previous);
+        f(self)
+    }
+
+    /// Set the journal mode of the database (`PRAGMA journal_mode`).
+    /// 
+    /// ## Arguments
+    /// * `mode` - The journal mode to use.
+    /// 
+    /// ## Returns
+    /// The journal mode that is in effect afterwards. SQLite may refuse some modes,
+    /// eg. in-memory databases can only use `Memory` or `Off`.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let conn = Connection::new("my_database.db").unwrap();
+    /// conn.set_journal_mode(JournalMode::Wal).unwrap();
+    /// ```
+    pub fn set_journal_mode(&self, mode: JournalMode) -> Result<String, rusqlite::Error> {
+        debug!(target: "query_internal", "Setting journal mode to {}", mode.as_str());
+        // journal_mode returns the new mode as a row
+        self.connection.pragma_update_and_check(None, "journal_mode", mode.as_str(), |row| row.get(0))
+    }
+
+    /// Set how long to wait for a locked database before returning `SQLITE_BUSY`.
+    /// 
+    /// ## Arguments
+    /// * `timeout` - The maximum time to wait, zero disables waiting.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let conn = Connection::new("my_database.db").unwrap();
+    /// conn.set_busy_timeout(Duration::from_secs(5)).unwrap();
+    /// ```
+    pub fn set_busy_timeout(&self, timeout: std::time::Duration) -> Result<(), rusqlite::Error> {
+        self.connection.busy_timeout(timeout)
+    }
+
+    /// Enable or disable foreign key enforcement (`PRAGMA foreign_keys`).
+    /// 
+    /// Foreign keys are enabled by default. Disabling them can speed up bulk imports,
+    /// but rows violating the constraints are not rejected and won't be checked when they are enabled again.
+    /// 
+    /// ## Note
+    /// This must be called outside of a transaction, SQLite silently ignores it otherwise.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.set_foreign_keys(false).unwrap();
+    /// conn.insert(posts.as_slice()).unwrap();
+    /// conn.set_foreign_keys(true).unwrap();
+    /// ```
+    pub fn set_foreign_keys(&self, enabled: bool) -> Result<(), rusqlite::Error> {
+        debug!(target: "query_internal", "Setting foreign keys to {}", enabled);
+        self.connection.pragma_update(None, "foreign_keys", enabled)
+    }
+
+    /// Check if foreign key enforcement is enabled.
+    pub fn foreign_keys(&self) -> Result<bool, rusqlite::Error> {
+        self.connection.pragma_query_value(None, "foreign_keys", |row| row.get(0))
+    }
+
+    /// Set an arbitrary PRAGMA (eg. `cache_size`, `mmap_size` or `temp_store`).
+    /// 
+    /// ## Arguments
+    /// * `name` - The name of the pragma
+    /// * `value` - The value to set it to
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.pragma("cache_size", -64000).unwrap();
+    /// conn.pragma("temp_store", "MEMORY").unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Prefer dedicated methods like `set_journal_mode` or `set_foreign_keys` when they exist.
+    pub fn pragma(&self, name: &str, value: impl ToSql) -> Result<(), rusqlite::Error> {
+        debug!(target: "query_internal", "Setting pragma {}", name);
+        self.connection.pragma_update(None, name, value)
+    }
+
+    /// Read the current value of a PRAGMA.
+    /// 
+    /// ## Arguments
+    /// * `name` - The name of the pragma
+    /// 
+    /// ## Returns
+    /// The first column of the first row returned by the pragma
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let cache_size: i64 = conn.pragma_query("cache_size").unwrap();
+    /// ```
+    pub fn pragma_query<T: FromSql>(&self, name: &str) -> Result<T, rusqlite::Error> {
+        self.connection.pragma_query_value(None, name, |row| row.get(0))
+    }
+
+    fn init(connection: rusqlite::Connection) -> Result<Self, rusqlite::Error> {
+        // SQLite does not enforce foreign keys unless asked to
+        connection.pragma_update(None, "foreign_keys", true)?;
+        #[cfg(feature = "decimal")]
+        crate::model::decimal::register_functions(&connection)?;
         let _ = env_logger::try_init();
         Ok(Connection {
             connection,
-            latest_schema: DbSchema::new()
+            latest_schema: DbSchema::new(),
+            migration_policy: MigrationPolicy::default()
         })
     }
 
@@ -63,13 +403,37 @@ impl Connection {
     /// 
     /// let mut conn = Connection::new_memory().unwrap();
     /// conn.register::<User>().unwrap();
-    /// conn.migrate();
+    /// conn.migrate().unwrap();
     /// ```
     pub fn register<M: Model>(&mut self) -> Result<(), rusqlite::Error> {
         self.latest_schema.add_table::<M>();
         Ok(())
     }
 
+    /// Register a model and create its table right away (if it doesn't exist yet).
+    /// 
+    /// This is useful for simple apps, which don't need [migrate](Connection::migrate) to keep the schema up to date.
+    /// Tables of `Vec<T>` fields are created as well.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut conn = Connection::new_memory().unwrap();
+    /// conn.register_and_create::<User>().unwrap();
+    /// User { id: None, name: "John".to_string() }.insert(&conn).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// An existing table is left as it is, even if the model has changed. Use `migrate` to update it.
+    pub fn register_and_create<M: Model>(&mut self) -> Result<(), rusqlite::Error> {
+        self.register::<M>()?;
+
+        self.execute_no_params(&create_table_if_not_exists_statement(M::table_name(), M::columns()))?;
+        for collection in M::collections() {
+            self.execute_no_params(&create_table_if_not_exists_statement(collection.table, collection.columns))?;
+        }
+        Ok(())
+    }
+
     /// Execute query which creates a table if it doesn't exist.
     pub fn add_table<M: Model + IntoSqliteTy>(&self) -> Result<(), rusqlite::Error> {
         let sql = M::into_sqlite();
@@ -77,6 +441,52 @@ impl Connection {
         Ok(())
     }
 
+    /// Delete all rows of a model and reset its id counter, so the next inserted row gets id 1.
+    /// 
+    /// Rows of its `Vec<T>` fields are deleted as well. Soft deleted models are removed permanently.
+    /// 
+    /// ## Returns
+    /// The number of deleted rows of the model.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.truncate::<User>().unwrap();
+    /// assert_eq!(User::count().exec(&conn).unwrap(), 0);
+    /// ```
+    pub fn truncate<M: Model + WritableModel>(&self) -> Result<usize, rusqlite::Error> {
+        let transaction = if self.connection.is_autocommit() {
+            Some(self.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+
+        // Collections first, as they reference the model
+        for collection in M::collections() {
+            self.connection.execute(&format!("DELETE FROM {}", qualified_table_name(collection.table)), [])?;
+        }
+        let sql = format!("DELETE FROM {}", qualified_table_name(M::table_name()));
+        debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+        let deleted = self.connection.execute(&sql, [])?;
+
+        // sqlite_sequence only exists once a table with AUTOINCREMENT was created
+        let has_sequence: bool = self.connection.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE name = 'sqlite_sequence')", qualified_table_name("sqlite_master")),
+            [], |row| row.get(0)
+        )?;
+        if has_sequence {
+            let sql = format!("DELETE FROM {} WHERE name = ?", qualified_table_name("sqlite_sequence"));
+            self.connection.execute(&sql, [M::table_name()])?;
+            for collection in M::collections() {
+                self.connection.execute(&sql, [collection.table])?;
+            }
+        }
+
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(deleted)
+    }
+
     /// Execute query which drops a table if it exists.
     pub fn drop_table<M: Model>(&self) -> Result<(), rusqlite::Error> {
         let sql = format!("DROP TABLE IF EXISTS {}", M::table_name());
@@ -107,7 +517,7 @@ impl Connection {
     /// conn.exec_raw("CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL)", &[]).unwrap();
     /// ```
     pub fn exec_raw(&self, sql: &str, params: &[&dyn ToSql]) -> Result<usize, rusqlite::Error> {
-        debug!(target: "query", "Executing raw query: \"{}\"", sql);
+        debug!(target: "query", "Executing raw query {:016x}: \"{}\"", query_id(sql), sql);
         let n = self.connection.execute(sql, params)?;
         Ok(n)
     }
@@ -119,7 +529,7 @@ impl Connection {
     /// ## Arguments
     /// * `sql` - The SQL query to execute.
     /// * `params` - The parameters to pass to the query.
-    /// * `callback` - A callback function which will be called with the result of the query as a `rusqlite::Rows` object.
+    /// * `callback` - A callback function which will be called with the result of the query as a mutable `rusqlite::Rows` object.
     /// 
     /// ## Returns
     /// The result of the callback function.
@@ -127,24 +537,168 @@ impl Connection {
     /// ## Example
     /// ```rs
     /// let mut conn = Connection::new_memory().unwrap();
-    /// let users = conn.query_raw("SELECT * FROM users", &[], |rows| {
-    ///    let mut users = Vec::new();
-    ///    for row in rows {
-    ///        users.push(
-    ///            User {
-    ///                id: row.get(0).unwrap(),
-    ///                name: row.get(1).unwrap()
-    ///            }
-    ///        );
-    ///    }
-    ///    users
+    /// let users = conn.query_raw("SELECT id, name FROM users", &[], |rows| {
+    ///     let mut users = Vec::new();
+    ///     while let Some(row) = rows.next().unwrap() {
+    ///         users.push(
+    ///             User {
+    ///                 id: row.get(0).unwrap(),
+    ///                 name: row.get(1).unwrap()
+    ///             }
+    ///         );
+    ///     }
+    ///     users
     /// }).unwrap();
     /// ```
-    pub fn query_raw<F, T>(&self, sql: &str, params: &[&dyn ToSql], callback: F) -> Result<T, rusqlite::Error> where F: Fn(&rusqlite::Rows) -> T {
-        debug!(target: "query", "Executing raw query: \"{}\"", sql);
+    pub fn query_raw<F, T>(&self, sql: &str, params: &[&dyn ToSql], mut callback: F) -> Result<T, rusqlite::Error> where F: FnMut(&mut rusqlite::Rows) -> T {
+        debug!(target: "query", "Executing raw query {:016x}: \"{}\"", query_id(sql), sql);
         let mut stmt = self.connection.prepare(sql)?; 
-        let rows = stmt.query(params)?;
-        Ok(callback(&rows))
+        let mut rows = stmt.query(params)?;
+        Ok(callback(&mut rows))
+    }
+
+    /// Execute a raw query on the database and parse the returned rows.
+    /// 
+    /// This is useful for statements with a `RETURNING` clause, like hand-written upserts or updates.
+    /// 
+    /// ## Arguments
+    /// * `sql` - The SQL query to execute.
+    /// * `params` - The parameters to pass to the query.
+    /// 
+    /// ## Returns
+    /// The returned rows parsed as `T`.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut conn = Connection::new_memory().unwrap();
+    /// let users: Vec<User> = conn.query_raw_returning(
+    ///     "UPDATE users SET name = ? WHERE id = ? RETURNING *",
+    ///     &[&"John", &1]
+    /// ).unwrap();
+    /// ```
+    pub fn query_raw_returning<T: FromRow>(&self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<T>, rusqlite::Error> {
+        debug!(target: "query", "Executing raw query {:016x}: \"{}\"", query_id(sql), sql);
+        let mut stmt = self.connection.prepare(sql)?;
+        let mut rows = stmt.query(params)?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            result.push(T::from_row(row)?);
+        }
+        Ok(result)
+    }
+
+    /// Execute a raw query on the database and read a single value from it.
+    /// 
+    /// ## Arguments
+    /// * `sql` - The SQL query to execute.
+    /// * `params` - The parameters to pass to the query.
+    /// 
+    /// ## Returns
+    /// The first column of the first row, or `None` if the query returned no rows or the value is NULL.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut conn = Connection::new_memory().unwrap();
+    /// let max_id: Option<i64> = conn.query_scalar("SELECT MAX(id) FROM users", &[]).unwrap();
+    /// ```
+    pub fn query_scalar<T: FromSql>(&self, sql: &str, params: &[&dyn ToSql]) -> Result<Option<T>, rusqlite::Error> {
+        debug!(target: "query", "Executing raw query {:016x}: \"{}\"", query_id(sql), sql);
+        let mut stmt = self.connection.prepare(sql)?;
+        let mut rows = stmt.query(params)?;
+        match rows.next()? {
+            Some(row) => row.get(0),
+            None => Ok(None),
+        }
+    }
+
+    /// Execute multiple SQL statements separated by semicolons, eg. a setup script.
+    /// 
+    /// ## Arguments
+    /// * `sql` - The statements to execute, they can't have parameters.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.execute_batch("
+    ///     CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT);
+    ///     INSERT INTO settings VALUES ('theme', 'dark');
+    /// ").unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// The statements are not wrapped in a transaction, include `BEGIN` and `COMMIT` in the script if needed.
+    pub fn execute_batch(&self, sql: &str) -> Result<(), rusqlite::Error> {
+        debug!(target: "query", "Executing batch: \"{}\"", sql);
+        self.connection.execute_batch(sql)
+    }
+
+    /// Execute a list of queries all or nothing, in a single transaction.
+    /// 
+    /// ## Arguments
+    /// * `queries` - The queries to execute, eg. built with `to_raw` or [RawQuery::new].
+    /// 
+    /// ## Returns
+    /// The total number of rows affected by the queries.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.run_all(&[
+    ///     User::update().set(User::credits, 0).to_raw(),
+    ///     RawQuery::new("DELETE FROM sessions".to_string(), Vec::new()),
+    /// ]).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Inside of an existing transaction, no new transaction is started and the caller is responsible for rolling back.
+    pub fn run_all(&self, queries: &[RawQuery]) -> Result<usize, rusqlite::Error> {
+        let transaction = if self.connection.is_autocommit() {
+            Some(self.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+        let mut affected = 0;
+        for raw_query in queries.iter() {
+            debug!(target: "query", "Executing query {:016x}: {:?}", raw_query.query_id(), raw_query.sql);
+            let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+            affected += self.connection.prepare_cached(&raw_query.sql)?.execute(params.as_slice())?;
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(affected)
+    }
+
+    /// Check if a table exists in the database.
+    /// 
+    /// ## Arguments
+    /// * `name` - The name of the table, it is bound as a parameter.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// if !conn.table_exists("users").unwrap() {
+    ///     conn.migrate().unwrap();
+    /// }
+    /// ```
+    pub fn table_exists(&self, name: &str) -> Result<bool, rusqlite::Error> {
+        let sql = format!("SELECT EXISTS(SELECT 1 FROM {} WHERE type='table' AND name=?)", qualified_table_name("sqlite_master"));
+        self.connection.query_row(&sql, [name], |row| row.get(0))
+    }
+
+    /// Count the rows of a table.
+    /// 
+    /// ## Arguments
+    /// * `table` - The name of the table, it is quoted so any name is safe to pass.
+    /// 
+    /// ## Returns
+    /// The number of rows, or an error if the table doesn't exist.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let users = conn.row_count("users").unwrap();
+    /// ```
+    pub fn row_count(&self, table: &str) -> Result<i64, rusqlite::Error> {
+        let sql = format!("SELECT COUNT(*) FROM {}", qualified_table_name(&quote_identifier(table)));
+        debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+        self.connection.query_row(&sql, [], |row| row.get(0))
     }
 
     /// Get the names of all tables in the database.
@@ -156,7 +710,7 @@ impl Connection {
         let mut tables = Vec::new();
         while let Some(row) = rows.next()? {
             let rn = row.get(0)?;
-            if rn != "sqlite_sequence" {
+            if rn != "sqlite_sequence" && rn != MIGRATIONS_TABLE {
                 tables.push(rn);
             }
         }
@@ -167,7 +721,8 @@ impl Connection {
     /// 
     /// **WARNING:** This should not be used outside of the migrator. It is not guaranteed to work in the future.
     pub fn get_all_columns<'a>(&self, table: &str) -> Result<Vec<Column<'a>>, rusqlite::Error> {
-        let mut stmt = self.connection.prepare(&format!("PRAGMA table_info({})", table))?;
+        // table_xinfo also lists generated columns, which table_info hides
+        let mut stmt = self.connection.prepare(&format!("PRAGMA table_xinfo({})", table))?;
         let mut rows = stmt.query([])?;
         let mut columns = Vec::new();
         while let Some(row) = rows.next()? {
@@ -175,8 +730,10 @@ impl Connection {
             let ty: String = row.get(2)?;
             let not_null: bool = row.get(3)?;
             let pk: bool = row.get(5)?;
-            // TODO: Default value
-            let _default_value: Option<ValueRef> = row.get_ref(4).ok();
+            // 2 for VIRTUAL and 3 for STORED generated columns
+            let hidden: i32 = row.get(6)?;
+            // The default is stored as the SQL expression from the table definition
+            let default_value: Option<String> = row.get(4)?;
 
             let mut flags = Vec::new();
             if not_null {
@@ -197,8 +754,9 @@ impl Connection {
             }
 
             let ty = SqliteType::from_str(&ty);
-            let column = Column::new(name, "", ty.unwrap(), flags, None, None);
-            columns.push(column);
+            let default_value = default_value.map(|sql| Box::new(DefaultExpression(sql)) as Box<dyn IntoSqlite>);
+            let column = Column::new(name, "", ty.unwrap(), flags, default_value, None);
+            columns.push(if hidden >= 2 { column.mark_generated() } else { column });
         }
         Ok(columns)
     }
@@ -207,6 +765,12 @@ impl Connection {
     /// 
     /// This will create new tables, add new columns, remove old columns, modify tables, etc.
     /// 
+    /// The migration runs in a transaction, so the schema is left unchanged if any statement fails.
+    /// 
+    /// ## Returns
+    /// A [MigrationReport] listing the applied operations.
+    /// An error if a query failed, or if the [MigrationPolicy] is `Safe` and the migration would drop tables or columns.
+    /// 
     /// ## Example:
     /// ```rs
     /// use sequelite::prelude::*;
@@ -219,16 +783,85 @@ impl Connection {
     /// 
     /// let mut conn = Connection::new_memory().unwrap();
     /// conn.register::<User>();
-    /// conn.migrate();
+    /// conn.migrate().unwrap();
     /// ```
     /// 
     /// ## Notes:
     /// You can enable `RUST_LOG=debug` to see the migration queries.
-    pub fn migrate(&self) {
+    pub fn migrate(&self) -> Result<MigrationReport, MigrationError> {
         info!(target: "migration", "Ensuring database is up to date...");
         Migrator::migrate(&self.latest_schema, self)
     }
 
+    /// Get the SQL statements `migrate` would execute, without changing the database.
+    /// 
+    /// ## Returns
+    /// The statements in the order they would be executed, empty if the database is up to date.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// for statement in conn.migration_plan().unwrap() {
+    ///     println!("{}", statement);
+    /// }
+    /// conn.migrate().unwrap();
+    /// ```
+    pub fn migration_plan(&self) -> Result<Vec<String>, rusqlite::Error> {
+        Migrator::plan(&self.latest_schema, self)
+    }
+
+    /// Apply imperative migration steps up to the given version.
+    /// Every step is executed only once, applied versions are recorded in the `_sequelite_migrations` table.
+    /// This complements `migrate`, which only brings the schema up to date.
+    /// 
+    /// ## Arguments
+    /// * `version` - The version to migrate to, steps with a greater version are not applied yet.
+    /// * `steps` - All migration steps, already applied ones are skipped.
+    /// 
+    /// ## Returns
+    /// A [MigrationReport] listing the applied steps, or the error of the first failing step.
+    /// Steps applied before the failing one stay applied.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// conn.migrate().unwrap();
+    /// let steps = [
+    ///     MigrationStep::new(1, |conn| conn.exec_raw("UPDATE users SET name = trim(name)", &[]).map(|_| ())),
+    ///     MigrationStep::new(2, |conn| conn.exec_raw("DELETE FROM users WHERE name = ''", &[]).map(|_| ())),
+    /// ];
+    /// conn.migrate_to(2, &steps).unwrap();
+    /// assert_eq!(conn.schema_version().unwrap(), 2);
+    /// ```
+    pub fn migrate_to(&self, version: u32, steps: &[MigrationStep]) -> Result<MigrationReport, MigrationError> {
+        info!(target: "migration", "Applying migrations up to version {}...", version);
+        Migrator::migrate_to(version, steps, self)
+    }
+
+    /// Get the version of the last migration step applied with `migrate_to`, or 0 if there were none.
+    pub fn schema_version(&self) -> Result<u32, rusqlite::Error> {
+        Migrator::current_version(self)
+    }
+
+    /// Set which operations `migrate` is allowed to perform.
+    /// 
+    /// ## Arguments
+    /// * `policy` - [MigrationPolicy::Safe] refuses to drop tables and columns, [MigrationPolicy::Destructive] (the default) drops them.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut conn = Connection::new("my_database.db").unwrap();
+    /// conn.set_migration_policy(MigrationPolicy::Safe);
+    /// conn.register::<User>().unwrap();
+    /// conn.migrate().unwrap();
+    /// ```
+    pub fn set_migration_policy(&mut self, policy: MigrationPolicy) {
+        self.migration_policy = policy;
+    }
+
+    /// Get the current migration policy.
+    pub fn migration_policy(&self) -> MigrationPolicy {
+        self.migration_policy
+    }
+
     /// Execute a query on the database.
     /// 
     /// ## Arguments
@@ -246,10 +879,10 @@ impl Connection {
         }
 
         let raw_query = query.get_query();
-        debug!(target: "query", "Executing query: {:?}", raw_query.sql);
+        debug!(target: "query", "Executing query {:016x}: {:?}", raw_query.query_id(), raw_query.sql);
         let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
         let params = params.as_slice();
-        self.connection.execute(&raw_query.sql, params)
+        self.connection.prepare_cached(&raw_query.sql)?.execute(params)
     }
 
     /// Execute a query on the database.
@@ -272,7 +905,7 @@ impl Connection {
     /// 
     /// let mut conn = Connection::new_memory().unwrap();
     /// conn.register::<User>();
-    /// conn.migrate();
+    /// conn.migrate().unwrap();
     /// 
     /// let user_id = User {
     ///     id: None,
@@ -295,15 +928,48 @@ impl Connection {
         }
 
         let raw_query = query.get_query();
-        debug!(target: "query", "Executing query: {:?}", raw_query.sql);
+        debug!(target: "query", "Executing query {:016x}: {:?}", raw_query.query_id(), raw_query.sql);
         let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
         let params = params.as_slice();
-        let mut stmt = self.connection.prepare(&raw_query.sql)?;
+        let mut stmt = self.connection.prepare_cached(&raw_query.sql)?;
         let rows = stmt.query(params)?;
-        let result = query.parse_result(rows);
+        let mut result = query.parse_result(rows)?;
+        query.finish(&mut result, self)?;
         Ok(result)
     }
 
+    /// Prepare a query for repeated execution with different parameters.
+    /// 
+    /// The SQL of the query is compiled only once, and parameters can be rebound on every execution.
+    /// 
+    /// ## Arguments
+    /// * `query` - The query to prepare. Values used in its filters are just placeholders and will be replaced on execution.
+    /// 
+    /// ## Returns
+    /// A [PreparedQuery](PreparedQuery) which can be executed multiple times.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut query = conn.prepare(User::select().filter(User::age.gt(0))).unwrap();
+    /// 
+    /// let adults = query.exec(&[&18]).unwrap();
+    /// let seniors = query.exec(&[&65]).unwrap();
+    /// ```
+    pub fn prepare<T, Q0: Queryable<T>, Q: IntoQueryable<T, Queryable = Q0>>(&self, query: Q) -> Result<PreparedQuery<'_, T, Q0>, rusqlite::Error> {
+        let mut query = query.into_queryable();
+        let raw_query = query.get_query();
+        debug!(target: "query", "Preparing query {:016x}: {:?}", raw_query.query_id(), raw_query.sql);
+        let statement = self.connection.prepare(&raw_query.sql)?;
+        Ok(PreparedQuery {
+            conn: self,
+            statement,
+            query_id: raw_query.query_id(),
+            sql: raw_query.sql,
+            query,
+            marker: std::marker::PhantomData
+        })
+    }
+
     /// Insert data into the database.
     /// 
     /// ## Arguments
@@ -312,6 +978,10 @@ impl Connection {
     /// ## Returns
     /// The id of the inserted row. (If there are multiple rows, the id of the last row is returned.)
     /// 
+    /// Batches binding more than [MAX_INSERT_PARAMS](MAX_INSERT_PARAMS) parameters are split into multiple statements,
+    /// executed in a transaction (unless one is already open).
+    /// Models with `Vec<T>` fields are inserted one row at a time, together with the values in their side tables, also in a transaction.
+    /// 
     /// ## Example
     /// ```rs
     /// use sequelite::prelude::*;
@@ -324,7 +994,7 @@ impl Connection {
     /// 
     /// let mut conn = Connection::new_memory().unwrap();
     /// conn.register::<User>();
-    /// conn.migrate();
+    /// conn.migrate().unwrap();
     /// 
     /// conn.insert(User {
     ///     id: None, // Id will be auto generated by the database
@@ -343,17 +1013,184 @@ impl Connection {
     /// ```
     // Yes I know that this could be more readable and that these generics are shit
     pub fn insert<I0: Insertable, I: IntoInsertable<Insertable = I0>>(&self, insertable: I) -> Result<i64, rusqlite::Error> {
-        let mut insertable = insertable.into_insertable();
-        let raw_query = insertable.get_query();
-        debug!(target: "query", "Executing query: {:?}", raw_query.sql);
-        let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
-        let params = params.as_slice();
-        self.connection.execute(&raw_query.sql, params)?;
+        self.insert_queries(&mut insertable.into_insertable())
+    }
+
+    // Execute an already built insert, used by `insert` and insert queries with extra options
+    pub(crate) fn insert_queries<I: Insertable>(&self, insertable: &mut I) -> Result<i64, rusqlite::Error> {
+        let collections = insertable.take_collections();
+        if !collections.is_empty() {
+            // Saving the collections changes the last insert rowid
+            let ids = self.insert_with_collections(insertable, collections, "rowid", |row| row.get::<_, i64>(1))?;
+            return Ok(ids.last().copied().unwrap_or_else(|| self.connection.last_insert_rowid()));
+        }
+
+        let raw_queries = insertable.get_queries(MAX_INSERT_PARAMS);
+
+        // Large batches are split into multiple statements, which should be inserted all or nothing.
+        // Inside of an existing transaction, the caller is responsible for that.
+        let transaction = if raw_queries.len() > 1 && self.connection.is_autocommit() {
+            Some(self.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+        for raw_query in raw_queries.iter() {
+            debug!(target: "query", "Executing query {:016x}: {:?}", raw_query.query_id(), raw_query.sql);
+            let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+            let params = params.as_slice();
+            self.connection.prepare_cached(&raw_query.sql)?.execute(params)?;
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
         
         // Get last row id
         let last_row_id = self.connection.last_insert_rowid();
         Ok(last_row_id)
     }
+
+    // Execute an already built insert, returning the id of every inserted row (rows skipped by ON CONFLICT are left out)
+    pub(crate) fn insert_returning_ids<I: Insertable>(&self, insertable: &mut I) -> Result<Vec<i64>, rusqlite::Error> {
+        let collections = insertable.take_collections();
+        if !collections.is_empty() {
+            return self.insert_with_collections(insertable, collections, "rowid", |row| row.get::<_, i64>(1));
+        }
+
+        let raw_queries = insertable.get_queries(MAX_INSERT_PARAMS);
+        let transaction = if raw_queries.len() > 1 && self.connection.is_autocommit() {
+            Some(self.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+        let mut ids = Vec::new();
+        for raw_query in raw_queries.iter() {
+            let sql = format!("{} RETURNING rowid", raw_query.sql);
+            debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+            let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+            let mut statement = self.connection.prepare_cached(&sql)?;
+            let mut rows = statement.query(params.as_slice())?;
+            while let Some(row) = rows.next()? {
+                ids.push(row.get(0)?);
+            }
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(ids)
+    }
+
+    /// Insert a model (or models) into the database and return the value of a single column of the inserted row.
+    /// 
+    /// This uses a `RETURNING` clause, so it also works for values generated by the database which are not the row id (eg. default timestamps).
+    /// If multiple rows are inserted, the value of the first one is returned.
+    /// 
+    /// ## Arguments
+    /// * `insertable` - The model(s) to insert.
+    /// * `column` - The column to return.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let created_at: NaiveDateTime = conn.insert_returning_value(
+    ///     Post { id: None, title: "Hello".to_string(), created_at: None },
+    ///     &Post::created_at
+    /// ).unwrap();
+    /// ```
+    pub fn insert_returning_value<T: FromSql, I0: Insertable, I: IntoInsertable<Insertable = I0>>(&self, insertable: I, column: &Column) -> Result<T, rusqlite::Error> {
+        let mut insertable = insertable.into_insertable();
+        let collections = insertable.take_collections();
+        if !collections.is_empty() {
+            return self.insert_with_collections(&mut insertable, collections, &column.name(), |row| row.get(1))?
+                .into_iter().next()
+                .ok_or(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        let raw_query = insertable.get_query();
+        let sql = format!("{} RETURNING {}", raw_query.sql, column.name());
+        debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+        let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+        self.connection.prepare_cached(&sql)?.query_row(params.as_slice(), |row| row.get(0))
+    }
+
+    // Insert the rows one by one, so that the id of every row is known, and save their `Vec<T>` fields.
+    // Everything is inserted in a transaction (unless one is already open), so a failure doesn't leave partial rows.
+    // Returns the `returning` expression of every inserted row, rows skipped by ON CONFLICT are left out.
+    fn insert_with_collections<T, I: Insertable>(&self, insertable: &mut I, collections: Vec<Vec<CollectionValues>>, returning: &str, map: impl Fn(&rusqlite::Row) -> Result<T, rusqlite::Error>) -> Result<Vec<T>, rusqlite::Error> {
+        let transaction = if self.connection.is_autocommit() {
+            Some(self.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+
+        let mut results = Vec::new();
+        // At most one row fits in a query binding no parameters
+        for (raw_query, row_collections) in insertable.get_queries(0).into_iter().zip(collections) {
+            let sql = format!("{} RETURNING rowid, {}", raw_query.sql, returning);
+            debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+            let params = raw_query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+            let inserted = self.connection.prepare_cached(&sql)?
+                .query_row(params.as_slice(), |row| Ok((row.get::<_, i64>(0)?, map(row)?)))
+                .optional()?;
+            if let Some((id, value)) = inserted {
+                for values in row_collections {
+                    values.save(id, self)?;
+                }
+                results.push(value);
+            }
+        }
+
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(results)
+    }
+
+    /// Set how many compiled statements are kept for reuse by `query`, `exec` and `insert`.
+    /// Statements are cached by their SQL text, the default capacity is 16.
+    /// 
+    /// ## Arguments
+    /// * `capacity` - The maximum number of cached statements, 0 disables caching.
+    pub fn set_statement_cache_capacity(&self, capacity: usize) {
+        self.connection.set_prepared_statement_cache_capacity(capacity);
+    }
+
+    /// Remove all compiled statements from the statement cache.
+    pub fn clear_statement_cache(&self) {
+        self.connection.flush_prepared_statement_cache();
+    }
+}
+
+/// A query that has been compiled once and can be executed multiple times with different parameters.
+/// 
+/// This is created using [Connection::prepare](Connection::prepare) or `ModelQuery::prepare`.
+pub struct PreparedQuery<'c, T, Q: Queryable<T>> {
+    conn: &'c Connection,
+    statement: rusqlite::Statement<'c>,
+    query_id: u64,
+    sql: String,
+    query: Q,
+    marker: std::marker::PhantomData<T>
+}
+
+impl<'c, T, Q: Queryable<T>> PreparedQuery<'c, T, Q> {
+    /// Execute the prepared query with the given parameters.
+    /// 
+    /// ## Arguments
+    /// * `params` - The parameters to bind. They replace all parameters of the original query in the same order.
+    /// 
+    /// ## Returns
+    /// The result of the query.
+    pub fn exec(&mut self, params: &[&dyn ToSql]) -> Result<T, rusqlite::Error> {
+        debug!(target: "query", "Executing prepared query {:016x}: {:?}", self.query_id, self.sql);
+        let rows = self.statement.query(params)?;
+        let mut result = self.query.parse_result(rows)?;
+        self.query.finish(&mut result, self.conn)?;
+        Ok(result)
+    }
+
+    /// Get the number of parameters that need to be passed to `exec`.
+    pub fn parameter_count(&self) -> usize {
+        self.statement.parameter_count()
+    }
 }
 
 /// It is implemented for everything that has `.exec(&conn)` method.
@@ -364,7 +1201,11 @@ pub trait Executable<T> {
 /// Trait that represents everything that can be used as a query in `connection.query(...)`
 pub trait Queryable<T> {
     fn get_query(&mut self) -> RawQuery;
-    fn parse_result(&mut self, rows: rusqlite::Rows) -> T;
+    fn parse_result(&mut self, rows: rusqlite::Rows) -> Result<T, rusqlite::Error>;
+    /// Called with the parsed result once the rows are read, eg. to load data from other tables.
+    fn finish(&mut self, _result: &mut T, _conn: &Connection) -> Result<(), rusqlite::Error> {
+        Ok(())
+    }
     fn should_execute(&self) -> bool {
         false
     }
@@ -377,9 +1218,23 @@ pub trait IntoQueryable<T> {
     fn into_queryable(self) -> Self::Queryable;
 }
 
+/// The maximum number of parameters in a single insert statement.
+/// SQLite versions before 3.32.0 refuse statements with more than 999 variables.
+pub const MAX_INSERT_PARAMS: usize = 999;
+
 /// Trait that represents everything that can be inserted in `connection.insert(...)`
 pub trait Insertable {
     fn get_query(&mut self) -> RawQuery;
+
+    /// Split the insert into multiple queries, each binding at most `max_params` parameters.
+    fn get_queries(&mut self, _max_params: usize) -> Vec<RawQuery> {
+        vec![self.get_query()]
+    }
+
+    /// Take the values of `Vec<T>` fields of every inserted row, which are saved to side tables after the rows are inserted.
+    fn take_collections(&mut self) -> Vec<Vec<CollectionValues>> {
+        Vec::new()
+    }
 }
 
 /// Trait that should be implemented for everything that can be made into an insertable (including insertables themselves).
@@ -421,28 +1276,83 @@ impl RawQuery {
         }
     }
 
+    /// Convert the parameters to owned SQLite values, so they can be cloned or sent to another thread.
+    pub fn owned_params(&self) -> Result<Vec<rusqlite::types::Value>, rusqlite::Error> {
+        self.params.iter()
+            .map(|param| match param.to_sql()? {
+                ToSqlOutput::Borrowed(value) => Ok(value.into()),
+                ToSqlOutput::Owned(value) => Ok(value),
+                _ => Err(rusqlite::Error::ToSqlConversionFailure("Unsupported parameter type".into())),
+            })
+            .collect()
+    }
+
+    /// Get a stable identifier of the query, see [query_id](query_id).
+    pub fn query_id(&self) -> u64 {
+        query_id(&self.sql)
+    }
+
     /// Useful for debugging (and only for debugging)
     pub fn substitute_params(&self) -> String {
-        let mut sql = self.sql.clone();
-        for param in &self.params {
-            let param = param.to_sql().unwrap();
-            sql = sql.replace('?', &value_to_string(&param));
-        }
-        sql
+        let params = self.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+        substitute_params(&self.sql, &params)
     }
 }
 
-fn value_to_string(value: &dyn ToSql) -> String {
-    let value = value.to_sql().unwrap();
-    if let ToSqlOutput::Owned(ref value) = value {
-        match value {
-            rusqlite::types::Value::Integer(i) => i.to_string(),
-            rusqlite::types::Value::Real(f) => f.to_string(),
-            rusqlite::types::Value::Text(s) => s.to_string(),
-            rusqlite::types::Value::Blob(b) => String::from_utf8(b.to_vec()).unwrap(),
-            rusqlite::types::Value::Null => String::new()
+/// Get a stable identifier of a query, eg. to group executions of the same query in metrics.
+/// 
+/// This is a hash (64-bit FNV-1a) of the SQL with `?` placeholders, so it doesn't depend on the bound parameters,
+/// and is the same across runs and versions of Rust. It is included in the `query` log messages.
+/// 
+/// ## Example
+/// ```rs
+/// let first = User::select().filter(User::id.eq(1)).to_raw().query_id();
+/// let second = User::select().filter(User::id.eq(2)).to_raw().query_id();
+/// assert_eq!(first, second);
+/// ```
+pub fn query_id(sql: &str) -> u64 {
+    sql.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Replace every `?` placeholder with its parameter as an SQL literal, eg. to log a query as a single statement.
+/// Placeholders inside of string literals and quoted identifiers are left untouched.
+pub(crate) fn substitute_params(sql: &str, params: &[&dyn ToSql]) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut params = params.iter();
+    let mut quote = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '?') => {
+                if let Some(param) = params.next() {
+                    result.push_str(&value_to_sql_literal(*param));
+                    continue;
+                }
+            }
+            _ => {}
         }
-    } else {
-        panic!("Cannot convert value to string");
+        result.push(c);
+    }
+    result
+}
+
+fn value_to_sql_literal(value: &dyn ToSql) -> String {
+    let value = match value.to_sql() {
+        Ok(value) => value,
+        Err(_) => return "?".to_string(),
+    };
+    let value = match value {
+        ToSqlOutput::Borrowed(value) => value,
+        ToSqlOutput::Owned(ref value) => value.into(),
+        _ => return "?".to_string(),
+    };
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        // Debug formatting keeps the decimal point, so the value stays a REAL
+        ValueRef::Real(f) => format!("{:?}", f),
+        ValueRef::Text(s) => format!("'{}'", String::from_utf8_lossy(s).replace('\'', "''")),
+        ValueRef::Blob(b) => format!("X'{}'", b.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
     }
 }
\ No newline at end of file
@@ -25,7 +25,7 @@
 //! let mut conn = Connection::new_memory().unwrap();
 //! conn.register::<User>().unwrap();
 //! conn.register::<Post>().unwrap();
-//! conn.migrate();
+//! conn.migrate().unwrap();
 //! 
 //! let user_id = User {
 //!     id: None,
@@ -56,22 +56,36 @@
 pub mod model;
 pub mod sql_types;
 pub mod connection;
+#[cfg(feature = "async")]
+pub mod async_connection;
 
 /// A prelude for users of the library.
 pub mod prelude {
-    pub use crate::model::{Model, Column, ModelExt, SqliteRows, SqliteToSql,
-        query::{ColumnQueryFilterImpl, ModelQueryFilterExt},
-        relation::Relation
+    pub use crate::model::{Model, WritableModel, Column, ModelExt, FromRow, SqliteRows, SqliteToSql,
+        query::{ModelQuery, Page, ColumnQueryOrder, QueryOrder, ModelQueryFilter, ColumnQueryFilterImpl, ModelQueryFilterExt, ColumnInQuery,
+            SharedFilter, Aggregate, ColumnExpression, exists, not_exists, not, count_all},
+        relation::Relation,
+        migrator::{MigrationPolicy, MigrationError, MigrationReport, MigrationStep}
     };
 
-    pub use crate::connection::Connection;
-    pub use crate::connection::Executable;
+    pub use crate::connection::{Connection, ConnectionOptions, JournalMode, Synchronous};
+    pub use crate::connection::{Executable, IntoInsertable};
 
-    pub use sequelite_macro::Model;
+    pub use sequelite_macro::{Model, SqliteEnum, FromRow};
 
     pub use rusqlite::Error as SqliteError;
+    pub use rusqlite::OpenFlags;
 
-    pub use crate::sql_types::NowTime;
+    pub use crate::sql_types::{NowTime, SqliteEnum};
+
+    #[cfg(feature = "serde")]
+    pub use crate::model::json::Json;
+
+    #[cfg(feature = "decimal")]
+    pub use crate::model::decimal::Decimal;
+
+    #[cfg(feature = "async")]
+    pub use crate::async_connection::AsyncConnection;
 }
 
 pub extern crate rusqlite;
@@ -92,9 +106,12 @@ pub trait IntoSqliteTy {
 }
 
 #[cfg(test)]
+// The ages of the sample rows are computed with (i*3)>>2/2%35, as they were originally written
+#[allow(clippy::precedence, clippy::identity_op)]
 mod tests {
     use crate as sequelite;
     use sequelite::prelude::*;
+    use chrono::NaiveDateTime;
 
     #[derive(Debug, Model)]
     #[table_name = "test"]
@@ -111,7 +128,7 @@ mod tests {
     fn migrate_create_table() {
         let mut conn = Connection::new_memory().unwrap();
         conn.register::<TestModel>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
         let exists = conn.get_all_tables().unwrap().iter().any(|table| table == "test");
         assert!(exists);
@@ -121,7 +138,7 @@ mod tests {
     fn crud() {
         let mut conn = Connection::new_memory().unwrap();
         conn.register::<TestModel>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
 
         // Create 10 users with random names (C)
@@ -232,7 +249,7 @@ mod tests {
         // Create table with TestModel
         let mut conn = Connection::new_memory().unwrap();
         conn.register::<TestModel>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
         // Insert 10 users
         for i in 0..10 {
@@ -249,7 +266,7 @@ mod tests {
 
         // Migrate to MigrationTest0
         conn.register::<MigrationTest0>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
         // Check if all users are still there
         assert_eq!(TestModel::count().exec(&conn).unwrap(), 10);
@@ -262,7 +279,7 @@ mod tests {
         // Create table with TestModel
         let mut conn = Connection::new_memory().unwrap();
         conn.register::<TestModel>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
         // Insert 10 users
         for i in 0..10 {
@@ -279,10 +296,3357 @@ mod tests {
 
         // Migrate to MigrationTest1
         conn.register::<MigrationTest1>().unwrap();
-        conn.migrate();
+        conn.migrate().unwrap();
 
         // Check if all users are still there
         assert_eq!(TestModel::count().exec(&conn).unwrap(), 10);
     }
 
+    #[derive(Debug, Model)]
+    #[table_name = "cities"]
+    struct CityModel {
+        id: Option<i32>,
+        city: Option<String>,
+    }
+
+    #[test]
+    fn distinct() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<CityModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for city in ["Warsaw", "Krakow", "Warsaw", "Gdansk", "Krakow"] {
+            CityModel {
+                id: None,
+                city: Some(city.to_string()),
+            }.insert(&conn).unwrap();
+        }
+
+        // Select distinct cities
+        let cities = CityModel::select()
+            .columns(&[CityModel::city])
+            .distinct()
+            .exec(&conn).unwrap();
+        assert_eq!(cities.len(), 3);
+
+        // Count distinct cities
+        let count = CityModel::count()
+            .columns(&[CityModel::city])
+            .distinct()
+            .exec(&conn).unwrap();
+        assert_eq!(count, 3);
+
+        // Distinct on COUNT(*) should not change anything
+        assert_eq!(CityModel::count().distinct().exec(&conn).unwrap(), 5);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "cities"]
+    #[readonly]
+    struct ReadonlyCityModel {
+        id: Option<i32>,
+        city: Option<String>,
+    }
+
+    #[test]
+    fn readonly_model() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ReadonlyCityModel>().unwrap();
+        conn.migrate().unwrap();
+
+        // Read-only models can only be written to using raw queries
+        conn.exec_raw("INSERT INTO cities (city) VALUES (?), (?)", &[&"Warsaw", &"Krakow"]).unwrap();
+
+        let cities = ReadonlyCityModel::select()
+            .filter(ReadonlyCityModel::city.eq("Krakow"))
+            .exec(&conn).unwrap();
+        assert_eq!(cities.len(), 1);
+        assert_eq!(ReadonlyCityModel::count().exec(&conn).unwrap(), 2);
+    }
+
+    #[test]
+    fn update_increment() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..4 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i * 10,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        // Increment age of even users twice
+        for _ in 0..2 {
+            TestModel::update()
+                .increment(TestModel::age, 3)
+                .filter(TestModel::even.eq(true))
+                .exec(&conn).unwrap();
+        }
+
+        // Decrement age of all users
+        TestModel::update()
+            .decrement(TestModel::age, 1)
+            .exec(&conn).unwrap();
+
+        let ages = TestModel::select()
+            .order_by(TestModel::id.asc())
+            .exec(&conn).unwrap()
+            .into_iter()
+            .map(|user| user.age)
+            .collect::<Vec<_>>();
+        assert_eq!(ages, vec![5, 9, 25, 29]);
+    }
+
+    #[test]
+    fn update_set_each() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..4 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let query = TestModel::update().set_each(TestModel::name, &[(1, "John"), (2, "Jane")]);
+        assert_eq!(query.to_sql(), "UPDATE test SET name=CASE id WHEN ? THEN ? WHEN ? THEN ? ELSE name END WHERE test.id IN (?, ?)");
+
+        // Update three rows to three different names at once
+        let updated = TestModel::update()
+            .set_each(TestModel::name, &[(1, "John"), (2, "Jane"), (4, "Bob")])
+            .exec(&conn).unwrap();
+        assert_eq!(updated, 3);
+
+        // Empty slice should not do anything
+        let updated = TestModel::update()
+            .set_each::<&str>(TestModel::name, &[])
+            .exec(&conn).unwrap();
+        assert_eq!(updated, 0);
+
+        let names = TestModel::select()
+            .order_by(TestModel::id.asc())
+            .exec(&conn).unwrap()
+            .into_iter()
+            .map(|user| user.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["John", "Jane", "User 2", "Bob"]);
+    }
+
+    #[test]
+    fn column_comparison() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        // Ids are 1..=6, ages are 0, 2, 4, 6, 8, 10
+        for i in 0..6 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i * 2,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let older = TestModel::count()
+            .filter(TestModel::age.gt_col(TestModel::id))
+            .exec(&conn).unwrap();
+        assert_eq!(older, 4);
+
+        let same = TestModel::select()
+            .filter(TestModel::age.eq_col(TestModel::id))
+            .exec(&conn).unwrap();
+        assert_eq!(same.len(), 1);
+        assert_eq!(same[0].age, 2);
+
+        let combined = TestModel::count()
+            .filter(TestModel::age.le_col(TestModel::id) | TestModel::even.eq(false))
+            .exec(&conn).unwrap();
+        assert_eq!(combined, 4);
+    }
+
+    #[test]
+    fn prepared_query() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let mut query = TestModel::select()
+            .filter(TestModel::age.ge(0) & TestModel::even.eq(true))
+            .prepare(&conn).unwrap();
+        assert_eq!(query.parameter_count(), 2);
+        assert_eq!(query.exec(&[&5, &true]).unwrap().len(), 2);
+        assert_eq!(query.exec(&[&5, &false]).unwrap().len(), 3);
+        assert_eq!(query.exec(&[&0, &true]).unwrap().len(), 5);
+
+        let mut count = TestModel::count()
+            .filter(TestModel::name.eq(""))
+            .prepare(&conn).unwrap();
+        assert_eq!(count.exec(&[&"User 3"]).unwrap(), 1);
+        assert_eq!(count.exec(&[&"Nobody"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn filter_raw() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i * 5,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let users = TestModel::select()
+            .filter(TestModel::even.eq(true))
+            .filter_raw("abs(age - ?) <= ?", vec![Box::new(20), Box::new(10)])
+            .order_by(TestModel::id.asc())
+            .exec(&conn).unwrap();
+
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![10, 20, 30]);
+
+        // Raw filter first, then typed one
+        let count = TestModel::count()
+            .filter_raw("age % ? = 0", vec![Box::new(10)])
+            .filter(TestModel::age.gt(10))
+            .exec(&conn).unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[derive(Debug, Model)]
+    struct Author {
+        id: Option<i32>,
+        name: String,
+    }
+
+    #[derive(Debug, Model)]
+    struct Book {
+        id: Option<i32>,
+        title: String,
+        author: Relation<Author>,
+    }
+
+    fn setup_authors() -> Connection {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Author>().unwrap();
+        conn.register::<Book>().unwrap();
+        conn.migrate().unwrap();
+
+        for name in ["John", "Jane", "Bob"] {
+            Author {
+                id: None,
+                name: name.to_string(),
+            }.insert(&conn).unwrap();
+        }
+
+        // John has two books, Jane has one and Bob has none
+        conn.insert(&[
+            Book { id: None, title: "First".to_string(), author: Relation::id(1) },
+            Book { id: None, title: "Second".to_string(), author: Relation::id(1) },
+            Book { id: None, title: "Third".to_string(), author: Relation::id(2) },
+        ]).unwrap();
+
+        conn
+    }
+
+    #[test]
+    fn exists_subquery() {
+        let conn = setup_authors();
+
+        let authors = Author::select()
+            .filter(Author::id.has_related::<Book>(Book::author))
+            .exec(&conn).unwrap();
+        let names = authors.iter().map(|author| author.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["John", "Jane"]);
+
+        let authors = Author::select()
+            .filter(not_exists(Book::select().filter(Book::author.eq_col(Author::id))))
+            .exec(&conn).unwrap();
+        assert_eq!(authors.len(), 1);
+        assert_eq!(authors[0].name, "Bob");
+
+        let count = Author::count()
+            .filter(exists(Book::select().filter(Book::author.eq_col(Author::id) & Book::title.eq("Third"))) | Author::name.eq("Bob"))
+            .exec(&conn).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn multiple_filters() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        // Select
+        let users = TestModel::select()
+            .filter(TestModel::age.ge(3) | TestModel::age.eq(0))
+            .filter(TestModel::even.eq(true))
+            .exec(&conn).unwrap();
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![0, 4, 6, 8]);
+
+        // Update
+        let updated = TestModel::update()
+            .set(TestModel::name, "John Doe")
+            .filter(TestModel::age.lt(5))
+            .filter(TestModel::even.eq(false))
+            .exec(&conn).unwrap();
+        assert_eq!(updated, 2);
+
+        let johns = TestModel::count()
+            .filter(TestModel::name.eq("John Doe"))
+            .filter(TestModel::age.lt(5))
+            .exec(&conn).unwrap();
+        assert_eq!(johns, 2);
+
+        // Delete
+        let deleted = TestModel::delete()
+            .filter(TestModel::age.gt(2))
+            .filter(TestModel::even.eq(true))
+            .exec(&conn).unwrap();
+        assert_eq!(deleted, 3);
+        assert_eq!(TestModel::count().exec(&conn).unwrap(), 7);
+    }
+
+    #[test]
+    fn count_where() {
+        let conn = setup_authors();
+
+        assert_eq!(Book::count_where(Book::author.eq(1), &conn).unwrap(), 2);
+        assert_eq!(Book::count_where(Book::author.eq(3), &conn).unwrap(), 0);
+        assert_eq!(Author::count_where(Author::name.like("J%"), &conn).unwrap(), 2);
+    }
+
+    #[test]
+    fn not_filter() {
+        use rusqlite::types::ToSqlOutput;
+
+        let mut filter = !(TestModel::name.eq("John") | TestModel::age.lt(18));
+        let query = filter.get_query();
+        assert_eq!(query.sql, "NOT ((test.name = ?) OR (test.age < ?))");
+        assert_eq!(query.params[0].to_sql().unwrap(), ToSqlOutput::from("John"));
+        assert_eq!(query.params[1].to_sql().unwrap(), ToSqlOutput::from(18));
+
+        let mut filter = TestModel::even.is_null().not() & TestModel::age.ge(2);
+        assert_eq!(filter.get_query().sql, "(NOT (test.even IS NULL)) AND (test.age >= ?)");
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i * 5,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let count = TestModel::count()
+            .filter(!(TestModel::name.eq("User 9") | TestModel::age.lt(18)))
+            .exec(&conn).unwrap();
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn filter_grouping() {
+
+        let mut filter = (TestModel::age.lt(10) | TestModel::age.gt(30)) & TestModel::even.eq(true);
+        assert_eq!(filter.get_query().sql, "((test.age < ?) OR (test.age > ?)) AND (test.even = ?)");
+
+        let mut filter = TestModel::age.lt(10) | TestModel::age.gt(30) & TestModel::even.eq(true);
+        assert_eq!(filter.get_query().sql, "(test.age < ?) OR ((test.age > ?) AND (test.even = ?))");
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        // Ages are 0, 5, 10, ..., 45
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i * 5,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        let users = TestModel::select()
+            .filter((TestModel::age.lt(10) | TestModel::age.gt(30)) & TestModel::even.eq(true))
+            .exec(&conn).unwrap();
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![0, 40]);
+
+        let users = TestModel::select()
+            .filter(TestModel::age.lt(10) | TestModel::age.gt(30) & TestModel::even.eq(true))
+            .exec(&conn).unwrap();
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![0, 5, 40]);
+    }
+
+    #[test]
+    fn not_function() {
+
+        let mut filter = not(TestModel::name.eq("John") & TestModel::age.lt(18)) | TestModel::even.is_null();
+        assert_eq!(filter.get_query().sql, "(NOT ((test.name = ?) AND (test.age < ?))) OR (test.even IS NULL)");
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for (name, age) in [("John", 10), ("John", 20), ("Jane", 10)] {
+            TestModel {
+                id: None,
+                name: name.to_string(),
+                age,
+                even: None
+            }.insert(&conn).unwrap();
+        }
+
+        let users = TestModel::select()
+            .filter(not(TestModel::name.eq("John") & TestModel::age.lt(18)))
+            .order_by(TestModel::id.asc())
+            .exec(&conn).unwrap();
+        let users = users.iter().map(|user| (user.name.as_str(), user.age)).collect::<Vec<_>>();
+        assert_eq!(users, vec![("John", 20), ("Jane", 10)]);
+    }
+
+    #[test]
+    fn filter_grouping_methods() {
+
+        let mut filter = TestModel::name.eq("John").or(TestModel::name.eq("Jane")) & TestModel::age.ge(18);
+        assert_eq!(filter.get_query().sql, "((test.name = ?) OR (test.name = ?)) AND (test.age >= ?)");
+
+        let mut filter = TestModel::name.eq("John").or(TestModel::name.eq("Jane").and(TestModel::age.ge(18)));
+        assert_eq!(filter.get_query().sql, "(test.name = ?) OR ((test.name = ?) AND (test.age >= ?))");
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for (name, age) in [("John", 10), ("John", 20), ("Jane", 10), ("Jane", 30), ("Bob", 40)] {
+            TestModel {
+                id: None,
+                name: name.to_string(),
+                age,
+                even: None
+            }.insert(&conn).unwrap();
+        }
+
+        let adults = TestModel::count()
+            .filter(TestModel::name.eq("John").or(TestModel::name.eq("Jane")) & TestModel::age.ge(18))
+            .exec(&conn).unwrap();
+        assert_eq!(adults, 2);
+
+        let matching = TestModel::count()
+            .filter(TestModel::name.eq("John").or(TestModel::name.eq("Jane").and(TestModel::age.ge(18))))
+            .exec(&conn).unwrap();
+        assert_eq!(matching, 3);
+    }
+
+    #[test]
+    fn limit_offset_order() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for i in 0..10 {
+            TestModel {
+                id: None,
+                name: format!("User {}", i),
+                age: i,
+                even: Some(i % 2 == 0)
+            }.insert(&conn).unwrap();
+        }
+
+        // Offset before limit and order
+        let users = TestModel::select()
+            .offset(2)
+            .limit(3)
+            .order_by(TestModel::age.desc())
+            .exec(&conn).unwrap();
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![7, 6, 5]);
+
+        // Limit before offset
+        let users = TestModel::select()
+            .filter(TestModel::even.eq(true))
+            .limit(2)
+            .offset(1)
+            .exec(&conn).unwrap();
+        let ages = users.iter().map(|user| user.age).collect::<Vec<_>>();
+        assert_eq!(ages, vec![2, 4]);
+    }
+
+    #[test]
+    fn query_raw_returning() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        let inserted: Vec<TestModel> = conn.query_raw_returning(
+            "INSERT INTO test (name, age) VALUES (?, ?), (?, ?) RETURNING *",
+            &[&"John", &20, &"Jane", &30]
+        ).unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(inserted[0].id, Some(1));
+        assert_eq!(inserted[1].name, "Jane");
+        assert_eq!(inserted[1].even, None);
+
+        let updated: Vec<TestModel> = conn.query_raw_returning(
+            "UPDATE test SET age = age + 1 WHERE name = ? RETURNING *",
+            &[&"Jane"]
+        ).unwrap();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].age, 31);
+
+        // Partial or mistyped rows are errors, not panics
+        let partial = conn.query_raw_returning::<TestModel>("SELECT id FROM test", &[]);
+        assert!(matches!(partial, Err(rusqlite::Error::InvalidColumnIndex(_))));
+        let mistyped = conn.query_raw_returning::<TestModel>("SELECT id, name, 'old' AS age, even FROM test", &[]);
+        assert!(matches!(mistyped, Err(rusqlite::Error::InvalidColumnType(..))));
+    }
+
+    #[test]
+    fn like_escape_nocase() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for name in ["john_doe", "johnXdoe", "John Doe", "JOHN"] {
+            TestModel {
+                id: None,
+                name: name.to_string(),
+                age: 0,
+                even: None
+            }.insert(&conn).unwrap();
+        }
+
+        // Without escaping, _ matches any character
+        assert_eq!(TestModel::count_where(TestModel::name.like("%n_d%"), &conn).unwrap(), 3);
+
+        let users = TestModel::select()
+            .filter(TestModel::name.like_escape("%n\\_d%", '\\'))
+            .exec(&conn).unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "john_doe");
+
+        conn.exec_raw("PRAGMA case_sensitive_like = ON", &[]).unwrap();
+        assert_eq!(TestModel::count_where(TestModel::name.like("john%"), &conn).unwrap(), 2);
+        assert_eq!(TestModel::count_where(TestModel::name.ilike("john%"), &conn).unwrap(), 4);
+        assert_eq!(TestModel::count_where(TestModel::name.eq_nocase("john"), &conn).unwrap(), 1);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "unsigned"]
+    struct UnsignedModel {
+        id: Option<i32>,
+        small: u8,
+        #[default_value(&7u16)]
+        medium: u16,
+        large: u32,
+        huge: Option<u64>,
+        size: usize,
+    }
+
+    #[test]
+    fn unsigned_columns() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<UnsignedModel>().unwrap();
+        conn.migrate().unwrap();
+
+        UnsignedModel {
+            id: None,
+            small: u8::MAX,
+            medium: u16::MAX,
+            large: u32::MAX,
+            huge: Some(i64::MAX as u64),
+            size: 42,
+        }.insert(&conn).unwrap();
+
+        let model = UnsignedModel::select().exec(&conn).unwrap().pop().unwrap();
+        assert_eq!(model.small, u8::MAX);
+        assert_eq!(model.medium, u16::MAX);
+        assert_eq!(model.large, u32::MAX);
+        assert_eq!(model.huge, Some(i64::MAX as u64));
+        assert_eq!(model.size, 42);
+
+        // u64 values above i64::MAX cannot be stored in SQLite
+        let result = UnsignedModel {
+            id: None,
+            small: 0,
+            medium: 0,
+            large: 0,
+            huge: Some(u64::MAX),
+            size: 0,
+        }.insert(&conn);
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Settings {
+        theme: String,
+        notifications: Notifications,
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Notifications {
+        email: bool,
+        channels: Vec<String>,
+    }
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, Model)]
+    struct Profile {
+        id: Option<i32>,
+        settings: Json<Settings>,
+        extra: Option<Json<Vec<i32>>>,
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_columns() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Profile>().unwrap();
+        conn.migrate().unwrap();
+
+        let settings = Settings {
+            theme: "dark".to_string(),
+            notifications: Notifications {
+                email: true,
+                channels: vec!["push".to_string(), "sms".to_string()],
+            },
+        };
+
+        Profile {
+            id: None,
+            settings: Json(settings.clone()),
+            extra: None,
+        }.insert(&conn).unwrap();
+
+        Profile {
+            id: None,
+            settings: Json(settings.clone()),
+            extra: Some(Json(vec![1, 2, 3])),
+        }.insert(&conn).unwrap();
+
+        let profiles = Profile::select().exec(&conn).unwrap();
+        assert_eq!(*profiles[0].settings, settings);
+        assert_eq!(profiles[0].extra, None);
+        assert_eq!(profiles[1].extra.as_ref().unwrap().0, vec![1, 2, 3]);
+
+        // Stored as JSON text
+        let count = Profile::count_where(Profile::settings.like("%\"theme\":\"dark\"%"), &conn).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn connection_options() {
+        let path = std::env::temp_dir().join(format!("sequelite_options_{}.db", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let options = ConnectionOptions::new()
+            .journal_mode(JournalMode::Wal)
+            .synchronous(Synchronous::Normal)
+            .busy_timeout_ms(5000)
+            .foreign_keys(true);
+        let conn = Connection::new_with_options(&path, options).unwrap();
+
+        let journal_mode: String = conn.connection.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+        let synchronous: i32 = conn.connection.query_row("PRAGMA synchronous", [], |row| row.get(0)).unwrap();
+        assert_eq!(synchronous, 1);
+        let foreign_keys: bool = conn.connection.query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap();
+        assert!(foreign_keys);
+
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    #[test]
+    fn order_by_relation() {
+        let conn = setup_authors();
+
+        let books = Book::select()
+            .join_relation(Book::author)
+            .order_by_relation(Author::name.asc())
+            .exec(&conn).unwrap();
+        let titles = books.iter().map(|b| b.title.as_str()).collect::<Vec<_>>();
+        assert_eq!(titles[0], "Third");
+        assert_eq!(books[0].author.try_get().unwrap().name, "Jane");
+        assert_eq!(books.len(), 3);
+
+        let books = Book::select()
+            .join_relation(Book::author)
+            .order_by_relation(Author::name.desc())
+            .exec(&conn).unwrap();
+        assert_eq!(books[2].title, "Third");
+    }
+
+    #[test]
+    #[should_panic]
+    fn order_by_relation_not_joined() {
+        let _ = Book::select().order_by_relation(Author::name.asc());
+    }
+
+    #[derive(Debug, Model)]
+    struct Chapter {
+        id: Option<i32>,
+        title: String,
+        #[on_delete(Cascade)]
+        book: Relation<Book>,
+    }
+
+    #[test]
+    fn foreign_key_cascade() {
+        let mut conn = setup_authors();
+        conn.register::<Chapter>().unwrap();
+        conn.migrate().unwrap();
+
+        conn.insert(&[
+            Chapter { id: None, title: "One".to_string(), book: Relation::id(1) },
+            Chapter { id: None, title: "Two".to_string(), book: Relation::id(1) },
+            Chapter { id: None, title: "Three".to_string(), book: Relation::id(2) },
+        ]).unwrap();
+
+        // Referencing a missing row is rejected
+        assert!(Chapter { id: None, title: "Missing".to_string(), book: Relation::id(42) }.insert(&conn).is_err());
+
+        Book::delete().filter(Book::id.eq(1)).exec(&conn).unwrap();
+        assert_eq!(Chapter::count().exec(&conn).unwrap(), 1);
+        assert_eq!(Chapter::select().exec(&conn).unwrap()[0].title, "Three");
+    }
+
+    #[test]
+    fn shared_filter() {
+        let conn = setup_authors();
+
+        let filter = SharedFilter::new(Book::author.eq(1) | Book::title.eq("Third")).unwrap();
+        let first = SharedFilter::new(Book::title.eq("First")).unwrap();
+
+        assert_eq!(Book::count().apply_filter(&filter).exec(&conn).unwrap(), 3);
+        assert_eq!(Book::select().apply_filter(&filter).apply_filter(&first).exec(&conn).unwrap().len(), 1);
+        assert_eq!(Book::count().filter(filter.clone() & !first.clone()).exec(&conn).unwrap(), 2);
+
+        Book::update()
+            .set(Book::title, "Renamed")
+            .apply_filter(&first)
+            .exec(&conn).unwrap();
+        assert_eq!(Book::count().apply_filter(&first).exec(&conn).unwrap(), 0);
+
+        let second = SharedFilter::new(Book::title.eq("Second")).unwrap();
+        Book::delete().apply_filter(&second).exec(&conn).unwrap();
+        assert_eq!(Book::count().apply_filter(&filter).exec(&conn).unwrap(), 2);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, SqliteEnum)]
+    enum TaskState {
+        Todo,
+        Doing,
+        Done = 10,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, SqliteEnum)]
+    #[store_as_text]
+    enum TaskPriority {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, Model)]
+    struct Task {
+        id: Option<i32>,
+        state: TaskState,
+        priority: Option<TaskPriority>,
+    }
+
+    #[test]
+    fn enum_columns() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Task>().unwrap();
+        conn.migrate().unwrap();
+
+        conn.insert(&[
+            Task { id: None, state: TaskState::Todo, priority: Some(TaskPriority::High) },
+            Task { id: None, state: TaskState::Done, priority: Some(TaskPriority::Low) },
+        ]).unwrap();
+        Task { id: None, state: TaskState::Done, priority: None }.insert(&conn).unwrap();
+
+        let done = Task::select().filter(Task::state.eq(TaskState::Done)).exec(&conn).unwrap();
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].priority, Some(TaskPriority::Low));
+        assert_eq!(done[1].priority, None);
+
+        let high = Task::select().filter(Task::priority.eq(TaskPriority::High)).exec(&conn).unwrap();
+        assert_eq!(high[0].state, TaskState::Todo);
+
+        // Stored by discriminant and by name
+        let raw: (i64, String) = conn.connection.query_row("SELECT state, priority FROM tasks WHERE id = 2", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!(raw, (10, "Low".to_string()));
+
+        // Invalid values are an error instead of a panic
+        conn.exec_raw("UPDATE tasks SET state = 5, priority = 'Medium' WHERE id = 1", &[]).unwrap();
+        let state = conn.connection.query_row("SELECT state FROM tasks WHERE id = 1", [], |row| row.get::<_, TaskState>(0));
+        assert!(state.is_err());
+        let priority = conn.connection.query_row("SELECT priority FROM tasks WHERE id = 1", [], |row| row.get::<_, TaskPriority>(0));
+        assert!(priority.is_err());
+
+        // Also when selecting models, including nullable enum columns
+        let result = Task::select().filter(Task::id.eq(1)).exec(&conn);
+        assert!(matches!(result, Err(rusqlite::Error::IntegralValueOutOfRange(1, 5))));
+        conn.exec_raw("UPDATE tasks SET state = 0 WHERE id = 1", &[]).unwrap();
+        let result = Task::select().filter(Task::id.eq(1)).exec(&conn);
+        assert!(matches!(result, Err(rusqlite::Error::FromSqlConversionFailure(2, ..))));
+        conn.exec_raw("UPDATE tasks SET state = 'Done', priority = NULL WHERE id = 1", &[]).unwrap();
+        let result = Task::select().filter(Task::id.eq(1)).exec(&conn);
+        assert!(matches!(result, Err(rusqlite::Error::InvalidColumnType(1, ..))));
+    }
+
+    #[test]
+    fn related_models() {
+        let conn = setup_authors();
+        let authors = Author::select().exec(&conn).unwrap();
+
+        let books = authors[0].related::<Book>(Book::author).exec(&conn).unwrap();
+        assert_eq!(books.iter().map(|b| b.title.as_str()).collect::<Vec<_>>(), vec!["First", "Second"]);
+
+        let books = authors[1].related::<Book>(Book::author)
+            .filter(Book::title.ne("Third"))
+            .exec(&conn).unwrap();
+        assert!(books.is_empty());
+
+        assert!(authors[2].related::<Book>(Book::author).exec(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn related_models_not_relation() {
+        let author = Author { id: Some(1), name: "John".to_string() };
+        let _ = author.related::<Book>(Book::title);
+    }
+
+    #[derive(Debug, Model)]
+    struct Article {
+        id: Option<i32>,
+        title: String,
+        tags: Vec<String>,
+        scores: Vec<i64>,
+    }
+
+    #[test]
+    fn collection_fields() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Article>().unwrap();
+        conn.migrate().unwrap();
+
+        let tables = conn.get_all_tables().unwrap();
+        assert!(tables.contains(&"articles_tags".to_string()));
+        assert!(tables.contains(&"articles_scores".to_string()));
+
+        let first = Article {
+            id: None,
+            title: "First".to_string(),
+            tags: vec!["rust".to_string(), "sqlite".to_string()],
+            scores: vec![3, 1, 2],
+        }.insert(&conn).unwrap();
+        Article {
+            id: None,
+            title: "Second".to_string(),
+            tags: Vec::new(),
+            scores: vec![5],
+        }.insert(&conn).unwrap();
+
+        let mut articles = Article::select().exec(&conn).unwrap();
+        assert_eq!(articles[0].tags, vec!["rust", "sqlite"]);
+        assert_eq!(articles[0].scores, vec![3, 1, 2]);
+        assert!(articles[1].tags.is_empty());
+        assert_eq!(articles[1].scores, vec![5]);
+
+        // Replace the values of an existing row
+        articles[0].tags = vec!["orm".to_string()];
+        articles[0].save_collections(first, &conn).unwrap();
+        let articles = Article::select().with_id(first).exec(&conn).unwrap();
+        assert_eq!(articles[0].tags, vec!["orm"]);
+
+        // Values are removed together with the parent row
+        Article::delete().filter(Article::id.eq(first)).exec(&conn).unwrap();
+        let count: i64 = conn.connection.query_row("SELECT COUNT(*) FROM articles_scores", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[derive(Debug, Model)]
+    struct Recipe {
+        id: Option<i32>,
+        #[unique]
+        name: String,
+        steps: Vec<String>,
+    }
+
+    #[test]
+    fn collection_fields_query_and_prepare() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Recipe>().unwrap();
+        conn.migrate().unwrap();
+        conn.insert(Recipe { id: None, name: "Tea".to_string(), steps: vec!["boil".to_string(), "steep".to_string()] }).unwrap();
+        conn.insert(Recipe { id: None, name: "Toast".to_string(), steps: vec!["toast".to_string()] }).unwrap();
+
+        // Every way of running a select loads the side tables
+        let recipes = conn.query(Recipe::select().order_by(Recipe::id.asc())).unwrap();
+        assert_eq!(recipes[0].steps, vec!["boil", "steep"]);
+        assert_eq!(recipes[1].steps, vec!["toast"]);
+
+        let mut prepared = Recipe::select().filter(Recipe::name.eq("")).prepare::<Vec<Recipe>>(&conn).unwrap();
+        assert_eq!(prepared.exec(&[&"Tea"]).unwrap()[0].steps, vec!["boil", "steep"]);
+        assert_eq!(prepared.exec(&[&"Toast"]).unwrap()[0].steps, vec!["toast"]);
+    }
+
+    #[test]
+    fn collection_fields_batch_insert() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Recipe>().unwrap();
+        conn.migrate().unwrap();
+
+        let recipe = |name: &str, steps: &[&str]| Recipe {
+            id: None,
+            name: name.to_string(),
+            steps: steps.iter().map(|step| step.to_string()).collect(),
+        };
+
+        // Slices, arrays and single models all save the side tables
+        let last = conn.insert(&[recipe("Tea", &["boil", "steep"]), recipe("Toast", &["toast"])]).unwrap();
+        assert_eq!(last, 2);
+        let batch = vec![recipe("Salad", &[]), recipe("Soup", &["chop", "boil", "season"])];
+        conn.insert(batch.as_slice()).unwrap();
+        conn.insert(recipe("Egg", &["boil"])).unwrap();
+        let name: String = conn.insert_returning_value(recipe("Rice", &["rinse", "boil"]), &Recipe::name).unwrap();
+        assert_eq!(name, "Rice");
+
+        let steps = Recipe::select().order_by(Recipe::id.asc()).exec(&conn).unwrap()
+            .into_iter()
+            .map(|recipe| (recipe.name, recipe.steps.join(",")))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![
+            ("Tea".to_string(), "boil,steep".to_string()),
+            ("Toast".to_string(), "toast".to_string()),
+            ("Salad".to_string(), "".to_string()),
+            ("Soup".to_string(), "chop,boil,season".to_string()),
+            ("Egg".to_string(), "boil".to_string()),
+            ("Rice".to_string(), "rinse,boil".to_string()),
+        ]);
+
+        // Skipped rows don't save their values to another row
+        let id = (&[recipe("Tea", &["microwave"]), recipe("Pasta", &["boil", "drain"])])
+            .into_insertable()
+            .on_conflict_do_nothing(Recipe::name)
+            .exec(&conn).unwrap();
+        assert_eq!(Recipe::find(&conn, 1).unwrap().unwrap().steps, vec!["boil", "steep"]);
+        let pasta = Recipe::select().filter(Recipe::name.eq("Pasta")).exec(&conn).unwrap().remove(0);
+        assert_eq!(pasta.steps, vec!["boil", "drain"]);
+        assert_eq!(id, pasta.id.map(i64::from));
+
+        // A failing row rolls back the rows and values inserted before it
+        let values = |conn: &Connection| conn.row_count("recipes_steps").unwrap();
+        let before = (Recipe::count().exec(&conn).unwrap(), values(&conn));
+        assert!(conn.insert(&[recipe("Stew", &["simmer"]), recipe("Tea", &["again"])]).is_err());
+        assert_eq!((Recipe::count().exec(&conn).unwrap(), values(&conn)), before);
+    }
+
+    #[test]
+    fn query_raw_rows() {
+        let conn = setup_authors();
+
+        let mut visited = 0;
+        let names = conn.query_raw("SELECT id, name FROM authors WHERE id > ? ORDER BY id", &[&1], |rows| {
+            let mut names = Vec::new();
+            while let Some(row) = rows.next().unwrap() {
+                visited += 1;
+                names.push((row.get::<_, i64>(0).unwrap(), row.get::<_, String>(1).unwrap()));
+            }
+            names
+        }).unwrap();
+
+        assert_eq!(names, vec![(2, "Jane".to_string()), (3, "Bob".to_string())]);
+        assert_eq!(visited, 2);
+    }
+
+    #[test]
+    fn open_readonly() {
+        let path = std::env::temp_dir().join(format!("sequelite_readonly_{}.db", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        // Opening a missing database read-only fails instead of creating it
+        assert!(Connection::open_readonly(&path).is_err());
+
+        {
+            let mut conn = Connection::new(&path).unwrap();
+            conn.register::<Author>().unwrap();
+            conn.migrate().unwrap();
+            Author { id: None, name: "John".to_string() }.insert(&conn).unwrap();
+        }
+
+        let conn = Connection::open_readonly(&path).unwrap();
+        assert_eq!(Author::select().exec(&conn).unwrap()[0].name, "John");
+
+        let result = Author { id: None, name: "Jane".to_string() }.insert(&conn);
+        match result {
+            Err(SqliteError::SqliteFailure(error, _)) => assert_eq!(error.code, rusqlite::ErrorCode::ReadOnly),
+            other => panic!("Expected a read-only error, got {:?}", other),
+        }
+
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn migration_policy() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        TestModel { id: None, name: "John".to_string(), age: 30, even: None }.insert(&conn).unwrap();
+
+        // MigrationTest0 uses the same table, but has no age column
+        conn.register::<MigrationTest0>().unwrap();
+        conn.set_migration_policy(MigrationPolicy::Safe);
+        match conn.migrate() {
+            Err(MigrationError::Destructive(operations)) => assert_eq!(operations, vec!["drop column age from table test"]),
+            other => panic!("Expected a destructive migration error, got {:?}", other),
+        }
+        let columns = conn.get_all_columns("test").unwrap();
+        assert!(columns.iter().any(|c| c.name() == "age"));
+
+        // Adding columns and tables is still allowed
+        conn.register::<TestModel>().unwrap();
+        conn.register::<CityModel>().unwrap();
+        conn.migrate().unwrap();
+        assert!(conn.get_all_tables().unwrap().contains(&"cities".to_string()));
+
+        conn.register::<MigrationTest0>().unwrap();
+        conn.set_migration_policy(MigrationPolicy::Destructive);
+        conn.migrate().unwrap();
+        let columns = conn.get_all_columns("test").unwrap();
+        assert!(!columns.iter().any(|c| c.name() == "age"));
+        assert_eq!(MigrationTest0::select().exec(&conn).unwrap()[0].name, "John");
+    }
+
+    #[derive(Debug, Model)]
+    struct Event {
+        id: Option<i32>,
+        name: String,
+        #[default_value(&NowTime)]
+        created_at: Option<chrono::NaiveDateTime>,
+    }
+
+    #[test]
+    fn insert_returning_value() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Event>().unwrap();
+        conn.migrate().unwrap();
+
+        let created_at: chrono::NaiveDateTime = conn.insert_returning_value(
+            Event { id: None, name: "Launch".to_string(), created_at: None },
+            &Event::created_at
+        ).unwrap();
+        let event = Event::select().exec(&conn).unwrap().pop().unwrap();
+        assert_eq!(event.created_at, Some(created_at));
+
+        let name: String = conn.insert_returning_value(
+            Event { id: None, name: "Landing".to_string(), created_at: None },
+            &Event::name
+        ).unwrap();
+        assert_eq!(name, "Landing");
+    }
+
+    #[derive(Model)]
+    #[table_name = "test"]
+    struct MigrationTestRequired {
+        id: Option<i32>,
+        name: String,
+        required: String,
+    }
+
+    #[test]
+    fn migration_report_rollback() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["create table test"]);
+        assert!(conn.migrate().unwrap().is_empty());
+        TestModel { id: None, name: "John".to_string(), age: 30, even: None }.insert(&conn).unwrap();
+
+        // Dropping age and even succeeds, but a NOT NULL column without a default can't be added to a table with rows
+        conn.register::<MigrationTestRequired>().unwrap();
+        assert!(matches!(conn.migrate(), Err(MigrationError::Sqlite(_))));
+
+        let columns = conn.get_all_columns("test").unwrap();
+        let names = columns.iter().map(|c| c.name()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "name", "age", "even"]);
+        assert_eq!(TestModel::select().exec(&conn).unwrap()[0].age, 30);
+
+        // Foreign keys are enabled again after the failed migration
+        let foreign_keys: bool = conn.connection.query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap();
+        assert!(foreign_keys);
+
+        conn.register::<TestModel>().unwrap();
+        conn.register::<CityModel>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["create table cities"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Filter uses a column of table `books`, but the query is on table `authors`")]
+    fn filter_wrong_model() {
+        let _ = Author::select().filter(Author::name.eq("John") & Book::title.eq("First"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Filter uses a column of table `books`, but the query is on table `authors`")]
+    fn filter_wrong_model_delete() {
+        let _ = Author::delete().filter(Book::title.is_null());
+    }
+
+    #[test]
+    fn filter_joined_model() {
+        let conn = setup_authors();
+
+        // Columns of joined tables and correlated subqueries are allowed
+        let books = Book::select()
+            .join_relation(Book::author)
+            .filter(Author::name.eq("Jane"))
+            .exec(&conn).unwrap();
+        assert_eq!(books.len(), 1);
+
+        let authors = Author::select()
+            .filter(exists(Book::select().filter(Book::author.eq_col(Author::id))))
+            .exec(&conn).unwrap();
+        assert_eq!(authors.len(), 2);
+    }
+
+    #[test]
+    fn journal_mode_and_busy_timeout() {
+        let path = std::env::temp_dir().join(format!("sequelite_journal_{}.db", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let conn = Connection::new(&path).unwrap();
+        assert_eq!(conn.set_journal_mode(JournalMode::Wal).unwrap(), "wal");
+        let journal_mode: String = conn.connection.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        conn.set_busy_timeout(std::time::Duration::from_millis(1500)).unwrap();
+        let timeout: i64 = conn.connection.query_row("PRAGMA busy_timeout", [], |row| row.get(0)).unwrap();
+        assert_eq!(timeout, 1500);
+
+        // In-memory databases can't use WAL
+        let memory = Connection::new_memory().unwrap();
+        assert_eq!(memory.set_journal_mode(JournalMode::Wal).unwrap(), "memory");
+
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_stream() {
+        use futures::StreamExt;
+
+        futures::executor::block_on(async {
+            let conn = AsyncConnection::new_memory().await.unwrap();
+            conn.call(|conn| {
+                conn.register::<Author>().unwrap();
+                conn.migrate().unwrap();
+                for i in 0..150 {
+                    Author { id: None, name: format!("Author {}", i) }.insert(conn)?;
+                }
+                Ok::<_, SqliteError>(())
+            }).await.unwrap();
+
+            // More rows than a single batch
+            let authors = Author::select()
+                .filter(Author::id.gt(10))
+                .stream(&conn)
+                .collect::<Vec<_>>().await;
+            assert_eq!(authors.len(), 140);
+            assert_eq!(authors[0].as_ref().unwrap().name, "Author 10");
+
+            // Dropping the stream early does not block the connection
+            let first = Author::select().stream(&conn).take(3).collect::<Vec<_>>().await;
+            assert_eq!(first.len(), 3);
+
+            let count = conn.call(|conn| Author::count().exec(conn)).await.unwrap();
+            assert_eq!(count, 150);
+
+            // Errors are yielded by the stream
+            let errors = Author::select()
+                .filter_raw("missing_column = ?", vec![Box::new(1)])
+                .stream(&conn)
+                .collect::<Vec<_>>().await;
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].is_err());
+        });
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_stream_does_not_block_calls() {
+        use futures::StreamExt;
+
+        futures::executor::block_on(async {
+            let conn = AsyncConnection::new_memory().await.unwrap();
+            conn.call(|conn| {
+                conn.register::<Author>().unwrap();
+                conn.migrate().unwrap();
+                let authors = (0..1000).map(|i| Author { id: None, name: format!("Author {}", i) }).collect::<Vec<_>>();
+                conn.insert(authors.as_slice())
+            }).await.unwrap();
+
+            // A stream which is never polled doesn't run
+            let idle = Author::select().stream(&conn);
+            assert_eq!(conn.call(|conn| Author::count().exec(conn)).await.unwrap(), 1000);
+            drop(idle);
+
+            // Partially read streams don't block other calls, which see the rest of the rows afterwards
+            let mut authors = Author::select().stream(&conn);
+            assert_eq!(authors.next().await.unwrap().unwrap().name, "Author 0");
+            let count = conn.call(|conn| Author::count().exec(conn)).await.unwrap();
+            assert_eq!(count, 1000);
+            let rest = authors.collect::<Vec<_>>().await;
+            assert_eq!(rest.len(), 999);
+            assert!(rest.iter().all(|author| author.is_ok()));
+
+            // Rows which can't be parsed are errors, the rest of the stream is still read
+            conn.call(|conn| conn.exec_raw("UPDATE authors SET name = X'00' WHERE id = 2", &[])).await.unwrap();
+            let authors = Author::select().stream(&conn).collect::<Vec<_>>().await;
+            assert_eq!(authors.len(), 1000);
+            assert!(matches!(authors[1], Err(SqliteError::InvalidColumnType(..))));
+            assert_eq!(authors.iter().filter(|author| author.is_ok()).count(), 999);
+        });
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "shapes"]
+    struct ShapeV1 {
+        id: Option<i32>,
+        name: String,
+        size: i32,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "shapes"]
+    struct ShapeV2 {
+        id: Option<i32>,
+        label: Option<String>,
+        name: String,
+        size: String,
+    }
+
+    #[test]
+    fn migrate_type_change_with_new_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeV1>().unwrap();
+        conn.migrate().unwrap();
+        conn.insert(&[
+            ShapeV1 { id: None, name: "square".to_string(), size: 4 },
+            ShapeV1 { id: None, name: "circle".to_string(), size: 7 },
+        ]).unwrap();
+
+        // label is added (at the end of the old table) and size changes its type, which rebuilds the table
+        conn.register::<ShapeV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["add column label to table shapes", "rebuild table shapes"]);
+
+        let shapes = ShapeV2::select().exec(&conn).unwrap();
+        assert_eq!(shapes.len(), 2);
+        assert_eq!(shapes[0].id, Some(1));
+        assert_eq!(shapes[0].label, None);
+        assert_eq!(shapes[0].name, "square");
+        assert_eq!(shapes[0].size, "4");
+        assert_eq!(shapes[1].name, "circle");
+        assert_eq!(shapes[1].size, "7");
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "people"]
+    struct PersonV1 {
+        id: Option<i32>,
+        full_name: String,
+        age: i32,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "people"]
+    struct PersonV2 {
+        id: Option<i32>,
+        #[renamed_from = "full_name"]
+        name: String,
+        age: i32,
+    }
+
+    #[test]
+    fn migrate_renamed_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<PersonV1>().unwrap();
+        conn.migrate().unwrap();
+        conn.insert(&[
+            PersonV1 { id: None, full_name: "John Doe".to_string(), age: 30 },
+            PersonV1 { id: None, full_name: "Jane Doe".to_string(), age: 25 },
+        ]).unwrap();
+
+        // Renaming is not destructive, so it is allowed by the safe policy
+        conn.set_migration_policy(MigrationPolicy::Safe);
+        conn.register::<PersonV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["rename column full_name to name in table people"]);
+
+        let people = PersonV2::select().exec(&conn).unwrap();
+        assert_eq!(people[0].name, "John Doe");
+        assert_eq!(people[1].name, "Jane Doe");
+        assert_eq!(people[1].age, 25);
+
+        // Already renamed
+        assert!(conn.migrate().unwrap().is_empty());
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "people"]
+    struct PersonV3 {
+        id: Option<i32>,
+        #[renamed_from = "full_name"]
+        name: String,
+        age: i32,
+        #[renamed_from = "nickname"]
+        alias: Option<String>,
+    }
+
+    #[test]
+    fn migrate_renamed_column_missing() {
+        // Creating the table from scratch ignores rename hints
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<PersonV2>().unwrap();
+        assert_eq!(conn.migrate().unwrap().operations, vec!["create table people"]);
+        PersonV2 { id: None, name: "John Doe".to_string(), age: 30 }.insert(&conn).unwrap();
+
+        // nickname never existed, so alias is added like any other new column
+        conn.register::<PersonV3>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["add column alias to table people"]);
+
+        let people = PersonV3::select().exec(&conn).unwrap();
+        assert_eq!(people[0].name, "John Doe");
+        assert_eq!(people[0].alias, None);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "tickets"]
+    struct TicketV1 {
+        id: Option<i32>,
+        title: String,
+        points: i32,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "tickets"]
+    struct TicketV2 {
+        id: Option<i32>,
+        points: f64,
+        #[default_value(&5)]
+        priority: i32,
+        title: String,
+    }
+
+    #[test]
+    fn migrate_add_column_with_default_and_type_change() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TicketV1>().unwrap();
+        conn.migrate().unwrap();
+        conn.insert(&[
+            TicketV1 { id: None, title: "Login page".to_string(), points: 3 },
+            TicketV1 { id: None, title: "Dark mode".to_string(), points: 8 },
+        ]).unwrap();
+
+        conn.register::<TicketV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["add column priority to table tickets", "rebuild table tickets"]);
+
+        let tickets = TicketV2::select().exec(&conn).unwrap();
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].title, "Login page");
+        assert_eq!(tickets[0].points, 3.0);
+        assert_eq!(tickets[0].priority, 5);
+        assert_eq!(tickets[1].title, "Dark mode");
+        assert_eq!(tickets[1].points, 8.0);
+        assert_eq!(tickets[1].priority, 5);
+    }
+
+    #[test]
+    fn order_by_collate() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Author>().unwrap();
+        conn.migrate().unwrap();
+        for name in ["bob", "Alice", "charlie", "Bob"] {
+            Author { id: None, name: name.to_string() }.insert(&conn).unwrap();
+        }
+
+        let names = |authors: Vec<Author>| authors.into_iter().map(|a| a.name).collect::<Vec<_>>();
+
+        let binary = Author::select().order_by(Author::name.asc()).exec(&conn).unwrap();
+        assert_eq!(names(binary), vec!["Alice", "Bob", "bob", "charlie"]);
+
+        let nocase = Author::select().order_by(Author::name.asc().collate("NOCASE")).exec(&conn).unwrap();
+        assert_eq!(names(nocase)[3], "charlie");
+
+        let nocase = Author::select().order_by(Author::name.desc().collate("NOCASE")).exec(&conn).unwrap();
+        assert_eq!(names(nocase)[0], "charlie");
+        assert_eq!(format!("{:?}", Author::select().order_by(Author::name.asc().collate("NOCASE"))), "ModelQuery { query: \"SELECT * FROM authors ORDER BY name COLLATE NOCASE ASC\" }");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid collation name")]
+    fn order_by_collate_invalid() {
+        let _ = Author::name.asc().collate("NOCASE; DROP TABLE authors");
+    }
+
+    #[test]
+    fn from_rusqlite() {
+        let raw = rusqlite::Connection::open_in_memory().unwrap();
+        raw.create_scalar_function("initial", 1, rusqlite::functions::FunctionFlags::SQLITE_DETERMINISTIC, |ctx| {
+            let name = ctx.get::<String>(0)?;
+            Ok(name.chars().next().map(|c| c.to_string()))
+        }).unwrap();
+        raw.pragma_update(None, "foreign_keys", false).unwrap();
+
+        let mut conn = Connection::from_rusqlite(raw).unwrap();
+        assert!(conn.foreign_keys().unwrap());
+        conn.register::<Author>().unwrap();
+        conn.migrate().unwrap();
+        Author { id: None, name: "John".to_string() }.insert(&conn).unwrap();
+        Author { id: None, name: "Jane".to_string() }.insert(&conn).unwrap();
+        Author { id: None, name: "Bob".to_string() }.insert(&conn).unwrap();
+
+        let authors = Author::select()
+            .filter_raw("initial(name) = ?", vec![Box::new("J")])
+            .exec(&conn).unwrap();
+        assert_eq!(authors.len(), 2);
+        assert!(authors.iter().all(|a| a.name.starts_with('J')));
+    }
+
+    #[test]
+    fn sqlite_type_for_rust_type() {
+        use crate::sql_types::SqliteType;
+        assert_eq!(SqliteType::for_rust_type("i64"), Some(SqliteType::Integer));
+        assert_eq!(SqliteType::for_rust_type("bool"), Some(SqliteType::Integer));
+        assert_eq!(SqliteType::for_rust_type("i16"), Some(SqliteType::Integer));
+        assert_eq!(SqliteType::for_rust_type("f32"), Some(SqliteType::Real));
+        assert_eq!(SqliteType::for_rust_type("Option<String>"), Some(SqliteType::Text));
+        assert_eq!(SqliteType::for_rust_type("Vec<u8>"), Some(SqliteType::Blob));
+        assert_eq!(SqliteType::for_rust_type("Option<chrono::NaiveDateTime>"), Some(SqliteType::DateTime));
+        assert_eq!(SqliteType::for_rust_type("Relation<Author>"), Some(SqliteType::Integer));
+        #[cfg(feature = "serde")]
+        assert_eq!(SqliteType::for_rust_type("Json<Vec<String>>"), Some(SqliteType::Text));
+        assert_eq!(SqliteType::for_rust_type("Vec<String>"), None);
+        assert_eq!(SqliteType::for_rust_type("TaskState"), None);
+
+        // Matches what the derive generated
+        assert_eq!(Book::title.ty, SqliteType::for_rust_type("String").unwrap());
+        assert_eq!(Book::author.ty, SqliteType::for_rust_type("Relation<Author>").unwrap());
+        assert_eq!(Book::title.ty, <String as crate::sql_types::SqliteTyped>::SQLITE_TYPE);
+    }
+
+    #[test]
+    fn migrate_to_versions() {
+        use std::{rc::Rc, cell::Cell};
+
+        let conn = setup_authors();
+        assert_eq!(conn.schema_version().unwrap(), 0);
+
+        let runs = Rc::new(Cell::new(0));
+        let steps = {
+            let (first, second) = (runs.clone(), runs.clone());
+            vec![
+                MigrationStep::new(2, move |conn| {
+                    second.set(second.get() + 1);
+                    conn.exec_raw("UPDATE authors SET name = name || '!'", &[]).map(|_| ())
+                }),
+                MigrationStep::new(1, move |conn| {
+                    first.set(first.get() + 1);
+                    conn.exec_raw("UPDATE authors SET name = upper(name)", &[]).map(|_| ())
+                }),
+            ]
+        };
+
+        let report = conn.migrate_to(1, &steps).unwrap();
+        assert_eq!(report.operations, vec!["apply migration 1"]);
+        assert_eq!(conn.schema_version().unwrap(), 1);
+
+        // Steps run in version order and only once
+        let report = conn.migrate_to(2, &steps).unwrap();
+        assert_eq!(report.operations, vec!["apply migration 2"]);
+        assert!(conn.migrate_to(2, &steps).unwrap().is_empty());
+        assert_eq!(runs.get(), 2);
+        assert_eq!(conn.schema_version().unwrap(), 2);
+        assert_eq!(Author::select().with_id(1).exec(&conn).unwrap()[0].name, "JOHN!");
+
+        // The history table survives schema migrations
+        assert!(conn.migrate().unwrap().is_empty());
+        assert_eq!(conn.schema_version().unwrap(), 2);
+    }
+
+    #[test]
+    fn migrate_to_failing_step() {
+        let conn = setup_authors();
+        let steps = [
+            MigrationStep::new(1, |conn| conn.exec_raw("UPDATE authors SET name = 'x'", &[]).map(|_| ())),
+            MigrationStep::new(2, |conn| conn.exec_raw("UPDATE missing_table SET name = 'y'", &[]).map(|_| ())),
+        ];
+        assert!(conn.migrate_to(2, &steps).is_err());
+
+        // The first step stays applied, the failing one is rolled back
+        assert_eq!(conn.schema_version().unwrap(), 1);
+        assert_eq!(Author::select().filter(Author::name.eq("x")).exec(&conn).unwrap().len(), 3);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "shapes"]
+    struct ShapeLabeled {
+        id: Option<i32>,
+        name: String,
+        size: i32,
+        label: Option<String>,
+    }
+
+    #[test]
+    fn migration_plan() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeV1>().unwrap();
+        assert_eq!(conn.migration_plan().unwrap().len(), 1);
+        assert!(conn.migration_plan().unwrap()[0].starts_with("CREATE TABLE shapes ("));
+        conn.migrate().unwrap();
+        assert!(conn.migration_plan().unwrap().is_empty());
+
+        conn.register::<ShapeLabeled>().unwrap();
+        let plan = conn.migration_plan().unwrap();
+        assert_eq!(plan.len(), 1);
+        assert!(plan[0].starts_with("ALTER TABLE shapes ADD COLUMN label"), "{}", plan[0]);
+
+        // Planning doesn't touch the database
+        assert!(!conn.get_all_columns("shapes").unwrap().iter().any(|c| c.name() == "label"));
+        assert_eq!(conn.migration_plan().unwrap(), plan);
+
+        conn.migrate().unwrap();
+        assert!(conn.get_all_columns("shapes").unwrap().iter().any(|c| c.name() == "label"));
+        assert!(conn.migration_plan().unwrap().is_empty());
+
+        // A type change rebuilds the table through a temporary one
+        conn.register::<ShapeV2>().unwrap();
+        let plan = conn.migration_plan().unwrap();
+        assert_eq!(plan.len(), 4);
+        assert!(plan[0].starts_with("CREATE TABLE temp_shapes_new ("));
+        assert_eq!(plan[3], "ALTER TABLE temp_shapes_new RENAME TO shapes;");
+    }
+
+    #[test]
+    fn is_distinct_from() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeLabeled>().unwrap();
+        conn.migrate().unwrap();
+        for (name, label) in [("square", Some("big")), ("circle", Some("small")), ("line", None)] {
+            ShapeLabeled { id: None, name: name.to_string(), size: 1, label: label.map(String::from) }.insert(&conn).unwrap();
+        }
+
+        // ne never matches NULL
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.ne("big")).exec(&conn).unwrap();
+        assert_eq!(shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["circle"]);
+
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.is_distinct_from("big")).exec(&conn).unwrap();
+        assert_eq!(shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["circle", "line"]);
+
+        // NULL is not distinct from NULL
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.is_distinct_from(None::<String>)).exec(&conn).unwrap();
+        assert_eq!(shapes.len(), 2);
+    }
+
+    #[test]
+    fn statement_cache() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeV1>().unwrap();
+        conn.migrate().unwrap();
+
+        // Same SQL with different parameters reuses the cached statement
+        for i in 0..100 {
+            ShapeV1 { id: None, name: format!("shape {}", i), size: i }.insert(&conn).unwrap();
+        }
+        for i in 0..100 {
+            let shapes = ShapeV1::select().filter(ShapeV1::size.eq(i)).exec(&conn).unwrap();
+            assert_eq!(shapes.len(), 1);
+            assert_eq!(shapes[0].name, format!("shape {}", i));
+        }
+        assert_eq!(ShapeV1::delete().filter(ShapeV1::size.ge(50)).exec(&conn).unwrap(), 50);
+
+        // Cached statements stay valid after the schema changes
+        conn.register::<ShapeLabeled>().unwrap();
+        conn.migrate().unwrap();
+        let shapes = ShapeV1::select().filter(ShapeV1::size.eq(10)).exec(&conn).unwrap();
+        assert_eq!(shapes[0].name, "shape 10");
+
+        conn.clear_statement_cache();
+        conn.set_statement_cache_capacity(0);
+        assert_eq!(ShapeLabeled::count().exec(&conn).unwrap(), 50);
+        ShapeLabeled { id: None, name: "labeled".to_string(), size: 1, label: Some("x".to_string()) }.insert(&conn).unwrap();
+        assert_eq!(ShapeLabeled::select().filter(ShapeLabeled::label.eq("x")).exec(&conn).unwrap().len(), 1);
+
+        // Parameters are rebound on every use, including NULLs replacing earlier values
+        conn.set_statement_cache_capacity(16);
+        for label in [Some("first"), None, Some("second"), None] {
+            ShapeLabeled::update().set(ShapeLabeled::label, label).filter(ShapeLabeled::name.eq("labeled")).exec(&conn).unwrap();
+            let shape = ShapeLabeled::select().filter(ShapeLabeled::name.eq("labeled")).exec(&conn).unwrap().remove(0);
+            assert_eq!(shape.label.as_deref(), label);
+        }
+    }
+
+    #[test]
+    fn with_schema() {
+        let conn = setup_authors();
+        conn.exec_raw("ATTACH DATABASE ':memory:' AS tenant", &[]).unwrap();
+        conn.exec_raw("CREATE TABLE tenant.authors (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL)", &[]).unwrap();
+        conn.exec_raw("CREATE TABLE tenant.books (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT NOT NULL, author INTEGER NOT NULL REFERENCES authors(id))", &[]).unwrap();
+
+        let books = conn.with_schema("tenant", |conn| {
+            assert_eq!(Author::count().exec(conn).unwrap(), 0);
+            let id = Author { id: None, name: "Tenant".to_string() }.insert(conn).unwrap();
+            Book { id: None, title: "Tenant book".to_string(), author: Relation::id(id) }.insert(conn).unwrap();
+
+            Author::update().set(Author::name, "Renamed").filter(Author::id.eq(id)).exec(conn).unwrap();
+            Book::select().join_relation(Book::author).filter(Author::name.eq("Renamed")).exec(conn).unwrap()
+        });
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].author.try_get().unwrap().name, "Renamed");
+
+        // Outside of the closure queries target the main database again
+        assert_eq!(Author::count().exec(&conn).unwrap(), 3);
+        assert_eq!(Book::count().exec(&conn).unwrap(), 3);
+        conn.with_schema("tenant", |conn| Book::delete().exec(conn)).unwrap();
+        assert_eq!(Book::count().exec(&conn).unwrap(), 3);
+    }
+
+    #[test]
+    fn insert_large_batch() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeLabeled>().unwrap();
+        conn.migrate().unwrap();
+
+        // 4 columns per row would need 20000 parameters in a single statement
+        let shapes = (0..5000).map(|i| ShapeLabeled {
+            id: Some(i + 1),
+            name: format!("shape {}", i),
+            size: i,
+            label: Some("batch".to_string()),
+        }).collect::<Vec<_>>();
+        let last_id = conn.insert(shapes.as_slice()).unwrap();
+        assert_eq!(last_id, 5000);
+        assert_eq!(ShapeLabeled::count().exec(&conn).unwrap(), 5000);
+        let last = ShapeLabeled::select().with_id(5000).exec(&conn).unwrap();
+        assert_eq!(last[0].name, "shape 4999");
+
+        // A failing chunk rolls back the whole batch (the last row has a duplicate id)
+        let shapes = (0..1000).map(|i| ShapeLabeled {
+            id: Some(if i == 999 { 1 } else { 10000 + i }),
+            name: "duplicate".to_string(),
+            size: i,
+            label: None,
+        }).collect::<Vec<_>>();
+        assert!(conn.insert(shapes.as_slice()).is_err());
+        assert_eq!(ShapeLabeled::count().exec(&conn).unwrap(), 5000);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "counters"]
+    struct CounterV1 {
+        id: Option<i32>,
+        name: String,
+        #[default_value(&0)]
+        hits: i32,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "counters"]
+    struct CounterV2 {
+        id: Option<i32>,
+        name: String,
+        #[default_value(&10)]
+        hits: i32,
+    }
+
+    #[test]
+    fn migrate_default_value_change() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<CounterV1>().unwrap();
+        conn.migrate().unwrap();
+        CounterV1 { id: None, name: "a".to_string(), hits: 3 }.insert(&conn).unwrap();
+
+        // Defaults are read back, so an unchanged schema doesn't migrate
+        let columns = conn.get_all_columns("counters").unwrap();
+        assert!(columns.iter().find(|c| c.name() == "hits").unwrap().has_default());
+        assert!(conn.migrate().unwrap().is_empty());
+
+        conn.register::<CounterV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["rebuild table counters"]);
+        assert!(conn.migrate().unwrap().is_empty());
+
+        conn.exec_raw("INSERT INTO counters (name) VALUES ('b')", &[]).unwrap();
+        let counters = CounterV2::select().exec(&conn).unwrap();
+        assert_eq!(counters[0].hits, 3);
+        assert_eq!(counters[1].hits, 10);
+    }
+
+    #[test]
+    fn group_by_having() {
+        let conn = setup_authors();
+        Book { id: None, title: "Fourth".to_string(), author: Relation::id(1) }.insert(&conn).unwrap();
+        Book { id: None, title: "Fifth".to_string(), author: Relation::id(2) }.insert(&conn).unwrap();
+
+        // John has three books, Jane has two
+        let books = Book::select()
+            .group_by(Book::author)
+            .having(count_all().gt(2))
+            .exec(&conn).unwrap();
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].author.get_id(), Some(1));
+
+        // HAVING goes before ORDER BY and LIMIT, and filters can be combined
+        let query = Book::select()
+            .filter(Book::title.ne("Second"))
+            .group_by(Book::author)
+            .having(count_all().ge(1) & Book::id.max().gt(0))
+            .order_by(Book::author.desc())
+            .limit(5);
+        assert_eq!(format!("{:?}", query), "ModelQuery { query: \"SELECT * FROM books WHERE books.title != ? GROUP BY books.author HAVING (COUNT(*) >= ?) AND (MAX(books.id) > ?) ORDER BY author DESC LIMIT ?\" }");
+        let books = query.exec(&conn).unwrap();
+        assert_eq!(books.iter().map(|b| b.author.get_id().unwrap()).collect::<Vec<_>>(), vec![2, 1]);
+
+        let books = Book::select()
+            .group_by(Book::author)
+            .having(Book::title.count().eq(2))
+            .exec(&conn).unwrap();
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].author.get_id(), Some(2));
+    }
+
+    #[test]
+    fn find_and_delete_by_id() {
+        let conn = setup_authors();
+
+        let author = Author::find(&conn, 2).unwrap();
+        assert_eq!(author.unwrap().name, "Jane");
+        assert!(Author::find(&conn, 42).unwrap().is_none());
+
+        assert_eq!(Book::delete_by_id(&conn, 1).unwrap(), 1);
+        assert!(Book::find(&conn, 1).unwrap().is_none());
+        assert_eq!(Book::count().exec(&conn).unwrap(), 2);
+        assert_eq!(Book::delete_by_id(&conn, 1).unwrap(), 0);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "members"]
+    struct MemberV1 {
+        id: Option<i32>,
+        first_name: String,
+        last_name: String,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "members"]
+    struct MemberV2 {
+        id: Option<i32>,
+        first_name: String,
+        last_name: String,
+        #[generated = "first_name || ' ' || last_name"]
+        full_name: String,
+    }
+
+    #[test]
+    fn generated_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<MemberV1>().unwrap();
+        conn.migrate().unwrap();
+        MemberV1 { id: None, first_name: "John".to_string(), last_name: "Doe".to_string() }.insert(&conn).unwrap();
+
+        // Stored generated columns can't be added, the table is rebuilt instead
+        conn.register::<MemberV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["rebuild table members"]);
+        assert!(conn.migrate().unwrap().is_empty());
+        assert!(conn.get_all_columns("members").unwrap().iter().any(|c| c.name() == "full_name" && c.is_generated()));
+
+        // The value of a generated column is ignored when inserting
+        conn.insert(&[
+            MemberV2 { id: None, first_name: "Jane".to_string(), last_name: "Roe".to_string(), full_name: "ignored".to_string() },
+            MemberV2 { id: None, first_name: "Bob".to_string(), last_name: "Smith".to_string(), full_name: String::new() },
+        ]).unwrap();
+
+        let members = MemberV2::select().exec(&conn).unwrap();
+        assert_eq!(members.iter().map(|m| m.full_name.as_str()).collect::<Vec<_>>(), vec!["John Doe", "Jane Roe", "Bob Smith"]);
+
+        MemberV2::update().set(MemberV2::last_name, "Doe").filter(MemberV2::first_name.eq("Jane")).exec(&conn).unwrap();
+        let does = MemberV2::select().filter(MemberV2::full_name.like("% Doe")).exec(&conn).unwrap();
+        assert_eq!(does.len(), 2);
+
+        // Generated columns can be dropped like any other column
+        conn.register::<MemberV1>().unwrap();
+        assert_eq!(conn.migrate().unwrap().operations, vec!["drop column full_name from table members"]);
+        assert_eq!(MemberV1::count().exec(&conn).unwrap(), 3);
+    }
+
+    #[test]
+    fn debug_sql() {
+        let query = Author::select()
+            .filter(Author::name.eq("O'Brien") | Author::name.eq("?"))
+            .filter(Author::id.gt(1.5))
+            .limit(10)
+            .offset(5);
+        assert_eq!(query.debug_sql(), "SELECT * FROM authors WHERE ((authors.name = 'O''Brien') OR (authors.name = '?')) AND (authors.id > 1.5) LIMIT 10 OFFSET 5");
+        // Debugging doesn't consume the query
+        let conn = setup_authors();
+        assert_eq!(query.exec(&conn).unwrap().len(), 0);
+
+        let query = Author::select().filter_raw("name = '?' OR name = ? OR name IS ?", vec![Box::new("John"), Box::new(None::<String>)]);
+        assert_eq!(query.debug_sql(), "SELECT * FROM authors WHERE name = '?' OR name = 'John' OR name IS NULL");
+
+        let query = Book::update()
+            .set(Book::title, "It's")
+            .set(Book::author, 2)
+            .filter(Book::title.in_(&["a", "b"]));
+        assert_eq!(query.debug_sql(), "UPDATE books SET title='It''s', author=2 WHERE books.title IN ('a', 'b')");
+
+        let query = Book::delete().filter(Book::title.eq(vec![0xDEu8, 0xAD, 0x01])).limit(1);
+        assert_eq!(query.debug_sql(), "DELETE FROM books WHERE books.title = X'DEAD01' LIMIT 1");
+
+        // RawQuery uses the same substitution (every placeholder gets its own value)
+        let raw = crate::connection::RawQuery::new("SELECT ?, ?".to_string(), vec![Box::new(1), Box::new("a") as Box<dyn SqliteToSql>]);
+        assert_eq!(raw.substitute_params(), "SELECT 1, 'a'");
+    }
+
+    #[test]
+    fn to_sql() {
+        let query = Book::select()
+            .filter(Book::title.like("%s%") & Book::author.eq(1))
+            .order_by(Book::title.desc())
+            .limit(2)
+            .offset(1);
+        assert_eq!(query.to_sql(), "SELECT * FROM books WHERE (books.title LIKE ?) AND (books.author = ?) ORDER BY title DESC LIMIT ? OFFSET ?");
+        assert_eq!(query.parameter_count(), 4);
+
+        let conn = setup_authors();
+        let books = query.exec(&conn).unwrap();
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].title, "First");
+
+        let query = Book::update().set(Book::title, "New").filter(Book::id.eq(1));
+        assert_eq!(query.to_sql(), "UPDATE books SET title=? WHERE books.id = ?");
+        assert_eq!(query.parameter_count(), 2);
+
+        let query = Book::delete().order_by(Book::id.asc()).limit(1);
+        assert_eq!(query.to_sql(), "DELETE FROM books ORDER BY id ASC LIMIT ?");
+        assert_eq!(query.parameter_count(), 1);
+    }
+
+    #[derive(Debug, FromRow)]
+    struct BookTitle {
+        id: i32,
+        title: String,
+    }
+
+    #[derive(Debug, FromRow)]
+    struct TitleWithAuthor(String, String);
+
+    #[test]
+    fn select_as_projection() {
+        let conn = setup_authors();
+
+        let titles = Book::select()
+            .columns(&[Book::id, Book::title])
+            .filter(Book::author.eq(1))
+            .order_by(Book::id.desc())
+            .select_as::<BookTitle>()
+            .exec(&conn).unwrap();
+        assert_eq!(titles.len(), 2);
+        assert_eq!(titles[0].id, 2);
+        assert_eq!(titles[0].title, "Second");
+
+        let titles = Book::select()
+            .join_relation(Book::author)
+            .columns(&[Book::title, Author::name])
+            .order_by(Book::title.asc())
+            .select_as::<TitleWithAuthor>()
+            .exec(&conn).unwrap();
+        assert_eq!(titles.iter().map(|t| (t.0.as_str(), t.1.as_str())).collect::<Vec<_>>(), vec![("First", "John"), ("Second", "John"), ("Third", "Jane")]);
+
+        // Missing columns are reported as errors instead of panicking
+        let result = Book::select()
+            .columns(&[Book::title])
+            .select_as::<BookTitle>()
+            .exec(&conn);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn insert_builder_defaults() {
+        let conn = setup_authors();
+        let id = Author::insert_builder().set(Author::name, "Alice").exec(&conn).unwrap();
+        assert_eq!(Author::find(&conn, id).unwrap().unwrap().name, "Alice");
+
+        // The table still has the old default of 0, but the model's default of 10 is used
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<CounterV1>().unwrap();
+        conn.migrate().unwrap();
+
+        let id = CounterV2::insert_builder().set(CounterV2::name, "a").exec(&conn).unwrap();
+        assert_eq!(CounterV2::find(&conn, id).unwrap().unwrap().hits, 10);
+        let id = CounterV1::insert_builder().set(CounterV1::name, "b").exec(&conn).unwrap();
+        assert_eq!(CounterV1::find(&conn, id).unwrap().unwrap().hits, 0);
+
+        // Set values win over defaults, setting a column twice keeps the last value
+        let id = CounterV2::insert_builder()
+            .set(CounterV2::hits, 1)
+            .set(CounterV2::name, "c")
+            .set(CounterV2::hits, 2)
+            .exec(&conn).unwrap();
+        assert_eq!(CounterV2::find(&conn, id).unwrap().unwrap().hits, 2);
+
+        // Missing NOT NULL columns without a default are rejected by the database
+        assert!(CounterV2::insert_builder().set(CounterV2::hits, 1).exec(&conn).is_err());
+    }
+
+    #[test]
+    fn query_exists() {
+        let conn = setup_authors();
+        assert!(Author::select().filter(Author::name.eq("Jane")).exists(&conn).unwrap());
+        assert!(!Author::select().filter(Author::name.eq("Nobody")).exists(&conn).unwrap());
+        assert!(Book::select().exists(&conn).unwrap());
+
+        // Limits and offsets are respected
+        assert!(!Book::select().filter(Book::author.eq(2)).offset(1).limit(1).exists(&conn).unwrap());
+        Book::delete().exec(&conn).unwrap();
+        assert!(!Book::select().exists(&conn).unwrap());
+    }
+
+    #[test]
+    fn count_distinct() {
+        let conn = setup_authors();
+        for name in ["John", "Jane", "Jane"] {
+            Author { id: None, name: name.to_string() }.insert(&conn).unwrap();
+        }
+
+        assert_eq!(Author::count().exec(&conn).unwrap(), 6);
+        assert_eq!(Author::count_distinct(Author::name).exec(&conn).unwrap(), 3);
+        assert_eq!(
+            ModelQuery::<Author>::count_distinct(Author::name)
+                .filter(Author::name.like("J%"))
+                .exec(&conn).unwrap(),
+            2
+        );
+        assert_eq!(Book::count_distinct(Book::author).exec(&conn).unwrap(), 2);
+    }
+
+    #[test]
+    fn stream_all() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Author>().unwrap();
+        conn.migrate().unwrap();
+
+        let authors = (1..=1200).map(|i| Author { id: Some(i), name: format!("Author {}", i) }).collect::<Vec<_>>();
+        conn.insert(authors.as_slice()).unwrap();
+
+        let mut ids = Vec::new();
+        let count = Author::stream_all(&conn, |author| ids.push(author.id.unwrap())).unwrap();
+        assert_eq!(count, 1200);
+        assert_eq!(ids, (1..=1200).collect::<Vec<_>>());
+
+        let mut empty = Connection::new_memory().unwrap();
+        empty.register::<Author>().unwrap();
+        empty.migrate().unwrap();
+        assert_eq!(Author::stream_all(&empty, |_| panic!("no rows expected")).unwrap(), 0);
+    }
+
+    #[test]
+    fn substitute_params_binary_blob() {
+        // Not valid UTF-8, must not panic when logged
+        let blob = vec![0xFFu8, 0xFE, 0x00, 0xC3, 0x28];
+        let raw = crate::connection::RawQuery::new(
+            "INSERT INTO files (data, name) VALUES (?, ?)".to_string(),
+            vec![Box::new(blob), Box::new("a.bin") as Box<dyn SqliteToSql>],
+        );
+        assert_eq!(raw.substitute_params(), "INSERT INTO files (data, name) VALUES (X'FFFE00C328', 'a.bin')");
+
+        let raw = crate::connection::RawQuery::new("SELECT ?".to_string(), vec![Box::new(Vec::<u8>::new()) as Box<dyn SqliteToSql>]);
+        assert_eq!(raw.substitute_params(), "SELECT X''");
+    }
+
+    #[test]
+    fn is_null_safe_eq() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeLabeled>().unwrap();
+        conn.migrate().unwrap();
+        for (name, label) in [("square", Some("big")), ("circle", Some("small")), ("line", None)] {
+            ShapeLabeled { id: None, name: name.to_string(), size: 1, label: label.map(String::from) }.insert(&conn).unwrap();
+        }
+
+        // eq with NULL never matches
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.eq(None::<String>)).exec(&conn).unwrap();
+        assert!(shapes.is_empty());
+
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.is(None::<String>)).exec(&conn).unwrap();
+        assert_eq!(shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["line"]);
+
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.is(Some("small"))).exec(&conn).unwrap();
+        assert_eq!(shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["circle"]);
+
+        let query = ShapeLabeled::select().filter(ShapeLabeled::label.is(None::<String>));
+        assert_eq!(query.debug_sql(), "SELECT * FROM shapes WHERE shapes.label IS NULL");
+    }
+
+    #[derive(Model, Debug)]
+    #[table_name = "notes"]
+    struct NoteWithCache {
+        id: Option<i32>,
+        text: String,
+        #[skip]
+        word_count: usize,
+        #[skip]
+        tokens: Vec<String>,
+    }
+
+    #[test]
+    fn skip_field() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<NoteWithCache>().unwrap();
+        conn.migrate().unwrap();
+
+        let columns = conn.get_all_columns("notes").unwrap();
+        assert_eq!(columns.iter().map(|c| c.name()).collect::<Vec<_>>(), vec!["id", "text"]);
+        assert_eq!(NoteWithCache::count_columns(), 2);
+        assert!(NoteWithCache::collections().is_empty());
+
+        NoteWithCache {
+            id: None,
+            text: "hello world".to_string(),
+            word_count: 2,
+            tokens: vec!["hello".to_string(), "world".to_string()],
+        }.insert(&conn).unwrap();
+
+        let note = NoteWithCache::find(&conn, 1).unwrap().unwrap();
+        assert_eq!(note.text, "hello world");
+        assert_eq!(note.word_count, 0);
+        assert!(note.tokens.is_empty());
+    }
+
+    #[test]
+    fn between_datetime() {
+        use chrono::NaiveDate;
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Event>().unwrap();
+        conn.migrate().unwrap();
+
+        let at = |day: u32, hour: u32| NaiveDate::from_ymd_opt(2023, 3, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        for (name, created_at) in [("early", at(1, 12)), ("start", at(10, 0)), ("middle", at(15, 8)), ("end", at(20, 0)), ("late", at(20, 1))] {
+            Event { id: None, name: name.to_string(), created_at: Some(created_at) }.insert(&conn).unwrap();
+        }
+
+        let names = |events: Vec<Event>| events.into_iter().map(|e| e.name).collect::<Vec<_>>();
+
+        // Bounds are inclusive
+        let events = Event::select().filter(Event::created_at.between(at(10, 0), at(20, 0))).exec(&conn).unwrap();
+        assert_eq!(names(events), vec!["start", "middle", "end"]);
+
+        let events = Event::select().filter(Event::created_at.not_between(at(10, 0), at(20, 0))).exec(&conn).unwrap();
+        assert_eq!(names(events), vec!["early", "late"]);
+
+        let events = Event::select()
+            .filter(Event::created_at.gt(at(10, 0)) & Event::created_at.le(at(20, 0)))
+            .exec(&conn).unwrap();
+        assert_eq!(names(events), vec!["middle", "end"]);
+
+        let events = Event::select().filter(Event::name.between("end", "late")).exec(&conn).unwrap();
+        assert_eq!(names(events), vec!["end", "late"]);
+    }
+
+    #[test]
+    fn prelude_exports() {
+        // Generic helpers written against the prelude alone
+        fn first_matching<M: Model, F: ModelQueryFilter>(filter: F, order: ColumnQueryOrder, conn: &Connection) -> Option<M> {
+            let query: ModelQuery<M> = M::select().filter(filter).order_by(order).limit(1);
+            query.exec(conn).unwrap().pop()
+        }
+        fn delete_matching<M: WritableModel, F: ModelQueryFilter>(filter: F, conn: &Connection) -> usize {
+            M::delete().filter(filter).exec(conn).unwrap()
+        }
+        fn enum_type<E: SqliteEnum>() -> crate::sql_types::SqliteType {
+            E::SQLITE_TYPE
+        }
+
+        let conn = setup_authors();
+        let author: Author = first_matching(Author::name.between("A", "K") & Author::name.is(Some("Jane")), Author::id.desc(), &conn).unwrap();
+        assert_eq!(author.name, "Jane");
+        let author: Author = first_matching(Author::name.ilike("j%"), Author::name.asc(), &conn).unwrap();
+        assert_eq!(author.name, "Jane");
+        assert_eq!(delete_matching::<Author, _>(Author::id.in_(vec![3]), &conn), 1);
+        assert_eq!(enum_type::<TaskPriority>(), crate::sql_types::SqliteType::Text);
+    }
+
+    #[derive(Model, Debug, PartialEq)]
+    #[table_name = "readings"]
+    struct SensorReading {
+        id: Option<i32>,
+        sensor: u8,
+        offset: i8,
+        temperature: i16,
+        samples: u32,
+        total: u64,
+        delta: Option<isize>,
+    }
+
+    #[test]
+    fn small_and_unsigned_integers() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<SensorReading>().unwrap();
+        conn.migrate().unwrap();
+
+        let reading = SensorReading {
+            id: None,
+            sensor: 255,
+            offset: -128,
+            temperature: -40,
+            samples: u32::MAX,
+            total: i64::MAX as u64,
+            delta: Some(-5),
+        };
+        reading.insert(&conn).unwrap();
+
+        let stored = SensorReading::select().filter(SensorReading::samples.gt(1_000_000u32)).exec(&conn).unwrap();
+        assert_eq!(stored, vec![SensorReading {
+            id: Some(1),
+            sensor: 255,
+            offset: -128,
+            temperature: -40,
+            samples: u32::MAX,
+            total: i64::MAX as u64,
+            delta: Some(-5),
+        }]);
+
+        // u64 values above i64::MAX can't be stored
+        let too_big = SensorReading { id: None, sensor: 0, offset: 0, temperature: 0, samples: 0, total: u64::MAX, delta: None };
+        assert!(too_big.insert(&conn).is_err());
+    }
+
+    #[derive(Model, Debug, PartialEq)]
+    #[table_name = "timestamps"]
+    struct Timestamps {
+        id: Option<i32>,
+        bare: NaiveDateTime,
+        qualified: chrono::NaiveDateTime,
+        optional: Option<NaiveDateTime>,
+        optional_qualified: Option<chrono::NaiveDateTime>,
+    }
+
+    #[test]
+    fn datetime_column_spellings() {
+        use crate::IntoSqlite;
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Timestamps>().unwrap();
+        conn.migrate().unwrap();
+
+        let columns = conn.get_all_columns("timestamps").unwrap();
+        assert_eq!(columns.iter().filter(|c| c.into_sqlite().contains("DATETIME")).count(), 4);
+        assert_eq!(columns.iter().filter(|c| c.can_insert_null()).count(), 3);
+
+        let time = chrono::NaiveDate::from_ymd_opt(2023, 5, 17).unwrap().and_hms_opt(9, 30, 15).unwrap();
+        let empty = Timestamps { id: None, bare: time, qualified: time, optional: None, optional_qualified: None };
+        let full = Timestamps { id: None, bare: time, qualified: time, optional: Some(time), optional_qualified: Some(time) };
+        empty.insert(&conn).unwrap();
+        full.insert(&conn).unwrap();
+
+        let rows = Timestamps::select().exec(&conn).unwrap();
+        assert_eq!(rows, vec![
+            Timestamps { id: Some(1), bare: time, qualified: time, optional: None, optional_qualified: None },
+            Timestamps { id: Some(2), bare: time, qualified: time, optional: Some(time), optional_qualified: Some(time) },
+        ]);
+
+        let rows = Timestamps::select().filter(Timestamps::optional.is_null()).exec(&conn).unwrap();
+        assert_eq!(rows.len(), 1);
+        let rows = Timestamps::select().filter(Timestamps::optional_qualified.eq(time)).exec(&conn).unwrap();
+        assert_eq!(rows[0].id, Some(2));
+    }
+
+    #[test]
+    fn on_conflict_do_nothing() {
+        let conn = setup_authors();
+        conn.exec_raw("CREATE UNIQUE INDEX authors_name ON authors (name)", &[]).unwrap();
+
+        // Conflict on the target is skipped
+        let author = Author { id: Some(1), name: "Alice".to_string() };
+        let skipped = author.into_insertable().on_conflict_do_nothing(Author::id).exec(&conn).unwrap();
+        assert_eq!(skipped, None);
+        assert_eq!(Author::find(&conn, 1).unwrap().unwrap().name, "John");
+
+        // Conflicts on other constraints still fail
+        let author = Author { id: Some(10), name: "Jane".to_string() };
+        assert!(author.into_insertable().on_conflict_do_nothing(Author::id).exec(&conn).is_err());
+
+        let authors = [
+            Author { id: Some(2), name: "Janet".to_string() },
+            Author { id: Some(4), name: "Alice".to_string() },
+        ];
+        let id = (&authors).into_insertable().on_conflict_do_nothing(Author::id).exec(&conn).unwrap();
+        assert_eq!(id, Some(4));
+        assert_eq!(Author::count().exec(&conn).unwrap(), 4);
+        assert_eq!(Author::find(&conn, 2).unwrap().unwrap().name, "Jane");
+
+        let author = Author { id: None, name: "Bob".to_string() };
+        let skipped = author.into_insertable().on_conflict_do_nothing(Author::name).exec(&conn).unwrap();
+        assert_eq!(skipped, None);
+        assert_eq!(Author::count().exec(&conn).unwrap(), 4);
+
+        // A skipped row doesn't report the id of an earlier insert
+        Author { id: None, name: "Carol".to_string() }.insert(&conn).unwrap();
+        let author = Author { id: None, name: "Alice".to_string() };
+        assert_eq!(author.into_insertable().on_conflict_do_nothing(Author::name).exec(&conn).unwrap(), None);
+        let author = Author { id: None, name: "Dave".to_string() };
+        let id = author.into_insertable().on_conflict_do_nothing(Author::name).exec(&conn).unwrap();
+        assert_eq!(id.map(|id| Author::find(&conn, id).unwrap().unwrap().name), Some("Dave".to_string()));
+    }
+
+    #[test]
+    fn set_foreign_keys() {
+        let conn = setup_authors();
+        assert!(conn.foreign_keys().unwrap());
+        assert!(Book { id: None, title: "Orphan".to_string(), author: Relation::id(42) }.insert(&conn).is_err());
+
+        conn.set_foreign_keys(false).unwrap();
+        assert!(!conn.foreign_keys().unwrap());
+        Book { id: None, title: "Orphan".to_string(), author: Relation::id(42) }.insert(&conn).unwrap();
+
+        conn.set_foreign_keys(true).unwrap();
+        assert!(conn.foreign_keys().unwrap());
+        assert!(Book { id: None, title: "Orphan".to_string(), author: Relation::id(43) }.insert(&conn).is_err());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[derive(Model, Debug, PartialEq)]
+    #[table_name = "products"]
+    struct Product {
+        id: Option<i32>,
+        category: String,
+        price: Decimal,
+        discount: Option<Decimal>,
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn decimal_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Product>().unwrap();
+        conn.migrate().unwrap();
+
+        let prices = [("food", "19.99", None), ("food", "0.1", Some("0.05")), ("toys", "0.2", None), ("toys", "1000000000000.01", None)];
+        for (category, price, discount) in prices {
+            Product {
+                id: None,
+                category: category.to_string(),
+                price: price.parse().unwrap(),
+                discount: discount.map(|d| d.parse().unwrap()),
+            }.insert(&conn).unwrap();
+        }
+
+        let product = Product::find(&conn, 2).unwrap().unwrap();
+        assert_eq!(product.price.to_string(), "0.1");
+        assert_eq!(product.discount, Some("0.05".parse().unwrap()));
+        assert_eq!(Product::find(&conn, 1).unwrap().unwrap().discount, None);
+
+        let sum = |sql: &str| conn.query_raw(sql, &[], |rows| {
+            rows.next().unwrap().unwrap().get::<_, Option<Decimal>>(0).unwrap()
+        }).unwrap();
+        // Floating point SUM loses the cents
+        assert_ne!(sum("SELECT SUM(price) FROM products").unwrap().to_string(), "1000000000020.30");
+        assert_eq!(sum("SELECT decimal_sum(price) FROM products").unwrap().to_string(), "1000000000020.30");
+        assert_eq!(sum("SELECT decimal_sum(discount) FROM products").unwrap().to_string(), "0.05");
+        assert_eq!(sum("SELECT decimal_sum(price) FROM products WHERE id > 10"), None);
+
+        let categories = Product::select()
+            .group_by(Product::category)
+            .having(Product::price.decimal_sum().eq("20.09"))
+            .exec(&conn).unwrap();
+        assert_eq!(categories[0].category, "food");
+    }
+
+    #[test]
+    fn query_scalar() {
+        let conn = setup_authors();
+
+        let max_id: Option<i64> = conn.query_scalar("SELECT MAX(id) FROM authors", &[]).unwrap();
+        assert_eq!(max_id, Some(3));
+
+        let books: Option<usize> = conn.query_scalar("SELECT COUNT(*) FROM books WHERE author = ?", &[&1]).unwrap();
+        assert_eq!(books, Some(2));
+
+        let name: Option<String> = conn.query_scalar("SELECT name FROM authors WHERE id = ?", &[&2]).unwrap();
+        assert_eq!(name.as_deref(), Some("Jane"));
+
+        // No rows
+        let name: Option<String> = conn.query_scalar("SELECT name FROM authors WHERE id = ?", &[&42]).unwrap();
+        assert_eq!(name, None);
+
+        // NULL value
+        let max_id: Option<i64> = conn.query_scalar("SELECT MAX(id) FROM authors WHERE id > 42", &[]).unwrap();
+        assert_eq!(max_id, None);
+
+        assert!(conn.query_scalar::<i64>("SELECT name FROM authors", &[]).is_err());
+    }
+
+    #[test]
+    fn empty_in() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<ShapeLabeled>().unwrap();
+        conn.migrate().unwrap();
+        for (name, label) in [("square", Some("big")), ("line", None)] {
+            ShapeLabeled { id: None, name: name.to_string(), size: 1, label: label.map(String::from) }.insert(&conn).unwrap();
+        }
+
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::id.in_(Vec::<i32>::new())).exec(&conn).unwrap();
+        assert!(shapes.is_empty());
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::name.in_(&[] as &'static [&str])).exec(&conn).unwrap();
+        assert!(shapes.is_empty());
+
+        // NOT IN () also matches NULL values
+        let shapes = ShapeLabeled::select().filter(ShapeLabeled::label.not_in(Vec::<String>::new())).exec(&conn).unwrap();
+        assert_eq!(shapes.len(), 2);
+
+        let deleted = ShapeLabeled::delete().filter(ShapeLabeled::id.in_(Vec::<i32>::new())).exec(&conn).unwrap();
+        assert_eq!(deleted, 0);
+    }
+
+    #[test]
+    fn exec_tuples() {
+        let conn = setup_authors();
+
+        let authors: Vec<(i32, String)> = Author::select()
+            .columns(&[Author::id, Author::name])
+            .order_by(Author::name.asc())
+            .exec_tuples(&conn).unwrap();
+        assert_eq!(authors, vec![(3, "Bob".to_string()), (2, "Jane".to_string()), (1, "John".to_string())]);
+
+        let books: Vec<(String, i64)> = Book::select()
+            .columns(&[Book::title, Book::author])
+            .filter(Book::author.eq(1))
+            .exec_tuples(&conn).unwrap();
+        assert_eq!(books, vec![("First".to_string(), 1), ("Second".to_string(), 1)]);
+
+        let single: Vec<(String,)> = Author::select().columns(&[Author::name]).limit(1).exec_tuples(&conn).unwrap();
+        assert_eq!(single, vec![("John".to_string(),)]);
+
+        // Too many columns in the tuple
+        assert!(Author::select().columns(&[Author::name]).exec_tuples::<(String, i32)>(&conn).is_err());
+    }
+
+    #[test]
+    fn get_or_create() {
+        let conn = setup_authors();
+
+        // Found
+        let author = Author::get_or_create(Author::name.eq("Jane"), Author { id: None, name: "Jane".to_string() }, &conn).unwrap();
+        assert_eq!(author.id, Some(2));
+        assert_eq!(Author::count().exec(&conn).unwrap(), 3);
+
+        // Created
+        let author = Author::get_or_create(Author::name.eq("Alice"), Author { id: None, name: "Alice".to_string() }, &conn).unwrap();
+        assert_eq!(author.id, Some(4));
+        assert_eq!(author.name, "Alice");
+        let again = Author::get_or_create(Author::name.eq("Alice"), Author { id: None, name: "Alice".to_string() }, &conn).unwrap();
+        assert_eq!(again.id, Some(4));
+        assert_eq!(Author::count().exec(&conn).unwrap(), 4);
+
+        // Inside of a transaction, nothing is committed by get_or_create
+        let transaction = conn.connection.unchecked_transaction().unwrap();
+        Author::get_or_create(Author::name.eq("Eve"), Author { id: None, name: "Eve".to_string() }, &conn).unwrap();
+        transaction.rollback().unwrap();
+        assert_eq!(Author::count().exec(&conn).unwrap(), 4);
+        assert!(conn.connection.is_autocommit());
+    }
+
+    #[test]
+    fn find_or_create_locks_database() {
+        let path = std::env::temp_dir().join(format!("sequelite_find_or_create_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let mut conn = Connection::new(path).unwrap();
+        conn.register::<Author>().unwrap();
+        conn.migrate().unwrap();
+        let other = Connection::new(path).unwrap();
+
+        let author = Author::find_or_create(Author::name.eq("Alice"), Author { id: None, name: "Alice".to_string() }, &conn).unwrap();
+        assert_eq!(author.id, Some(1));
+        let author = Author::find_or_create(Author::name.eq("Alice"), Author { id: None, name: "Alice".to_string() }, &other).unwrap();
+        assert_eq!(author.id, Some(1));
+
+        // While another connection is writing, the lookup waits for the lock instead of racing it
+        other.exec_raw("BEGIN IMMEDIATE", &[]).unwrap();
+        conn.set_busy_timeout(std::time::Duration::ZERO).unwrap();
+        let result = Author::find_or_create(Author::name.eq("Bob"), Author { id: None, name: "Bob".to_string() }, &conn);
+        assert_eq!(result.unwrap_err().sqlite_error_code(), Some(rusqlite::ErrorCode::DatabaseBusy));
+        other.exec_raw("COMMIT", &[]).unwrap();
+
+        assert_eq!(Author::find_or_create(Author::name.eq("Bob"), Author { id: None, name: "Bob".to_string() }, &conn).unwrap().id, Some(2));
+        assert_eq!(Author::count().exec(&other).unwrap(), 2);
+
+        drop(conn);
+        drop(other);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn to_raw() {
+        let conn = setup_authors();
+
+        let query = Book::select()
+            .filter(Book::title.like("%i%"))
+            .order_by(Book::title.desc())
+            .limit(1)
+            .offset(1);
+        let raw = query.to_raw();
+        assert_eq!(raw.sql, "SELECT * FROM books WHERE books.title LIKE ? ORDER BY title DESC LIMIT ? OFFSET ?");
+        assert_eq!(raw.params.len(), 3);
+        assert_eq!(raw.substitute_params(), "SELECT * FROM books WHERE books.title LIKE '%i%' ORDER BY title DESC LIMIT 1 OFFSET 1");
+
+        // The raw query can be executed on its own
+        let params = raw.params.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
+        let titles = conn.query_raw(&raw.sql, &params, |rows| {
+            let mut titles = Vec::new();
+            while let Some(row) = rows.next().unwrap() {
+                titles.push(row.get::<_, String>("title").unwrap());
+            }
+            titles
+        }).unwrap();
+        assert_eq!(titles, vec!["First"]);
+
+        let raw = Book::update().set(Book::title, "New").filter(Book::id.eq(1)).to_raw();
+        assert_eq!(raw.sql, "UPDATE books SET title=? WHERE books.id = ?");
+        assert_eq!(raw.params.len(), 2);
+
+        let raw = Book::delete().filter(Book::author.in_(vec![1, 2])).limit(5).to_raw();
+        assert_eq!(raw.sql, "DELETE FROM books WHERE books.author IN (?, ?) LIMIT ?");
+        assert_eq!(raw.params.len(), 3);
+    }
+
+    #[test]
+    fn glob_and_literal_percent() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+
+        for name in ["100% done", "100 done", "John", "john", "Jane"] {
+            TestModel { id: None, name: name.to_string(), age: 0, even: None }.insert(&conn).unwrap();
+        }
+
+        // % is a wildcard unless escaped
+        assert_eq!(TestModel::count_where(TestModel::name.like("100%"), &conn).unwrap(), 2);
+        let users = TestModel::select().filter(TestModel::name.like_escape("100!%%", '!')).exec(&conn).unwrap();
+        assert_eq!(users.iter().map(|u| u.name.as_str()).collect::<Vec<_>>(), vec!["100% done"]);
+
+        // LIKE ignores case, GLOB doesn't
+        assert_eq!(TestModel::count_where(TestModel::name.like("j%"), &conn).unwrap(), 3);
+        assert_eq!(TestModel::count_where(TestModel::name.glob("j*"), &conn).unwrap(), 1);
+        assert_eq!(TestModel::count_where(TestModel::name.glob("J[ao]*"), &conn).unwrap(), 2);
+        assert_eq!(TestModel::count_where(TestModel::name.glob("*%*"), &conn).unwrap(), 1);
+        assert_eq!(TestModel::count_where(TestModel::name.not_glob("J*"), &conn).unwrap(), 3);
+    }
+
+    #[derive(Model, Debug)]
+    #[table_name = "documents"]
+    struct Document {
+        id: Option<i32>,
+        title: String,
+        #[soft_delete]
+        deleted_at: Option<NaiveDateTime>,
+    }
+
+    #[test]
+    fn soft_delete() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Document>().unwrap();
+        conn.migrate().unwrap();
+        for title in ["Draft", "Report", "Notes"] {
+            Document { id: None, title: title.to_string(), deleted_at: None }.insert(&conn).unwrap();
+        }
+
+        let query = Document::delete().filter(Document::title.eq("Draft"));
+        assert_eq!(query.to_sql(), "UPDATE documents SET deleted_at = CURRENT_TIMESTAMP WHERE documents.deleted_at IS NULL AND (documents.title = ?)");
+        assert_eq!(query.exec(&conn).unwrap(), 1);
+        // Already deleted rows are not deleted again
+        assert_eq!(Document::delete().filter(Document::title.eq("Draft")).exec(&conn).unwrap(), 0);
+
+        // Hidden from selects and counts
+        assert_eq!(Document::select().to_sql(), "SELECT * FROM documents WHERE documents.deleted_at IS NULL");
+        let titles = Document::select().exec(&conn).unwrap().into_iter().map(|d| d.title).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Report", "Notes"]);
+        assert_eq!(Document::count().exec(&conn).unwrap(), 2);
+        assert!(Document::find(&conn, 1).unwrap().is_none());
+        assert!(!Document::select().filter(Document::title.eq("Draft")).exists(&conn).unwrap());
+
+        // But still stored
+        let deleted = Document::select().with_deleted().filter(Document::deleted_at.is_not_null()).exec(&conn).unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].title, "Draft");
+        assert!(deleted[0].deleted_at.is_some());
+        assert_eq!(Document::count().with_deleted().exec(&conn).unwrap(), 3);
+
+        // Recover
+        Document::update().set(Document::deleted_at, None::<NaiveDateTime>).filter(Document::id.eq(1)).exec(&conn).unwrap();
+        assert_eq!(Document::find(&conn, 1).unwrap().unwrap().title, "Draft");
+
+        // Delete everything, then remove the rows for good
+        assert_eq!(Document::delete().exec(&conn).unwrap(), 3);
+        assert_eq!(Document::count().exec(&conn).unwrap(), 0);
+        assert_eq!(Document::delete().permanent().filter(Document::title.ne("Notes")).exec(&conn).unwrap(), 2);
+        assert_eq!(Document::count().with_deleted().exec(&conn).unwrap(), 1);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "reviews"]
+    struct Review {
+        id: Option<i32>,
+        title: String,
+        editor: Option<Relation<Author>>,
+    }
+
+    #[test]
+    fn optional_relation() {
+        let mut conn = setup_authors();
+        conn.register::<Review>().unwrap();
+        conn.migrate().unwrap();
+
+        let columns = conn.get_all_columns("reviews").unwrap();
+        assert!(columns.iter().find(|c| c.name() == "editor").unwrap().can_insert_null());
+
+        Review { id: None, title: "Unedited".to_string(), editor: None }.insert(&conn).unwrap();
+        Review { id: None, title: "Edited".to_string(), editor: Some(Relation::id(2)) }.insert(&conn).unwrap();
+
+        // Stored as NULL, not as 0
+        let missing: Option<i64> = conn.query_scalar("SELECT editor FROM reviews WHERE id = 1", &[]).unwrap();
+        assert_eq!(missing, None);
+        // A relation without an id is NULL too
+        assert_eq!(Relation::<Author>::default().to_sql().unwrap(), rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Null));
+
+        let articles = Review::select().exec(&conn).unwrap();
+        assert!(articles[0].editor.is_none());
+        assert_eq!(articles[1].editor.as_ref().unwrap().get_id(), Some(2));
+
+        let articles = Review::select().join_relation(Review::editor).exec(&conn).unwrap();
+        assert!(articles[0].editor.is_none());
+        assert_eq!(articles[0].title, "Unedited");
+        assert_eq!(articles[1].editor.as_ref().unwrap().try_get().unwrap().name, "Jane");
+
+        let unedited = Review::select().filter(Review::editor.is_null()).exec(&conn).unwrap();
+        assert_eq!(unedited.len(), 1);
+        assert_eq!(unedited[0].title, "Unedited");
+    }
+
+    #[derive(Debug, Model)]
+    struct Warehouse {
+        id: Option<i32>,
+        #[unique]
+        code: i64,
+        city: String,
+    }
+
+    #[derive(Debug, Model)]
+    struct Shipment {
+        id: Option<i32>,
+        #[references = "code"]
+        warehouse: Relation<Warehouse>,
+    }
+
+    #[test]
+    fn relation_to_non_primary_key() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Warehouse>().unwrap();
+        conn.register::<Shipment>().unwrap();
+        conn.migrate().unwrap();
+
+        let schema: String = conn.query_scalar("SELECT sql FROM sqlite_master WHERE name = 'shipments'", &[]).unwrap().unwrap();
+        assert!(schema.contains("REFERENCES warehouses(code)"), "{}", schema);
+
+        Warehouse { id: None, code: 500, city: "Oslo".to_string() }.insert(&conn).unwrap();
+        Warehouse { id: None, code: 700, city: "Bergen".to_string() }.insert(&conn).unwrap();
+        // The relation holds the code, not the id
+        Shipment { id: None, warehouse: Relation::by_key(Warehouse::code, 700) }.insert(&conn).unwrap();
+        Shipment { id: None, warehouse: Relation::by_key(Warehouse::code, 500) }.insert(&conn).unwrap();
+        assert!(Shipment { id: None, warehouse: Relation::by_key(Warehouse::code, 2) }.insert(&conn).is_err());
+
+        let mut shipments = Shipment::select().exec(&conn).unwrap();
+        assert_eq!(shipments[0].warehouse.get_key(), Some(&rusqlite::types::Value::Integer(700)));
+        // The code is not the id of the warehouse
+        assert_eq!(shipments[0].warehouse.get_id(), None);
+        assert_eq!(shipments[0].warehouse.fetch(&conn).unwrap().city, "Bergen");
+        assert_eq!(Relation::<Warehouse>::by_key(Warehouse::code, 500).fetch_once(&conn).unwrap().city, "Oslo");
+
+        let joined = Shipment::select().join_relation(Shipment::warehouse).exec(&conn).unwrap();
+        assert_eq!(joined[0].warehouse.get_key(), Some(&rusqlite::types::Value::Integer(700)));
+        assert_eq!(joined[0].warehouse.try_get().unwrap().city, "Bergen");
+        assert_eq!(joined[1].warehouse.try_get().unwrap().city, "Oslo");
+
+        let oslo = Warehouse::select().filter(Warehouse::city.eq("Oslo")).exec(&conn).unwrap().remove(0);
+        let related = oslo.related::<Shipment>(Shipment::warehouse).exec(&conn).unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].id, Some(2));
+    }
+
+    #[derive(Debug, Model)]
+    struct Member {
+        id: Option<i32>,
+        #[unique]
+        email: String,
+        name: String,
+    }
+
+    #[derive(Debug, Model)]
+    struct Login {
+        id: Option<i32>,
+        #[references = "email"]
+        member: Relation<Member>,
+        #[references = "email"]
+        invited_by: Option<Relation<Member>>,
+    }
+
+    #[test]
+    fn relation_to_text_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Member>().unwrap();
+        conn.register::<Login>().unwrap();
+        conn.migrate().unwrap();
+
+        // The foreign key has the type of the referenced column
+        assert_eq!(Login::member.ty, crate::sql_types::SqliteType::Text);
+        let schema: String = conn.query_scalar("SELECT sql FROM sqlite_master WHERE name = 'logins'", &[]).unwrap().unwrap();
+        assert!(schema.contains("member TEXT NOT NULL REFERENCES members(email)"), "{}", schema);
+
+        Member { id: None, email: "john@example.com".to_string(), name: "John".to_string() }.insert(&conn).unwrap();
+        Member { id: None, email: "jane@example.com".to_string(), name: "Jane".to_string() }.insert(&conn).unwrap();
+        Login { id: None, member: Relation::by_key(Member::email, "jane@example.com".to_string()), invited_by: None }.insert(&conn).unwrap();
+        Login {
+            id: None,
+            member: Relation::by_key(Member::email, "john@example.com".to_string()),
+            invited_by: Some(Relation::by_key(Member::email, "jane@example.com".to_string())),
+        }.insert(&conn).unwrap();
+        assert!(Login { id: None, member: Relation::by_key(Member::email, "nobody@example.com".to_string()), invited_by: None }.insert(&conn).is_err());
+
+        let mut logins = Login::select().exec(&conn).unwrap();
+        assert_eq!(logins[0].member.get_key(), Some(&rusqlite::types::Value::Text("jane@example.com".to_string())));
+        assert_eq!(logins[0].member.get_id(), None);
+        assert!(logins[0].invited_by.is_none());
+        assert_eq!(logins[1].member.fetch(&conn).unwrap().name, "John");
+        assert_eq!(logins[1].invited_by.as_ref().unwrap().fetch_once(&conn).unwrap().name, "Jane");
+
+        let joined = Login::select().join_relation(Login::member).exec(&conn).unwrap();
+        assert_eq!(joined[0].member.try_get().unwrap().name, "Jane");
+        assert_eq!(joined[1].member.try_get().unwrap().name, "John");
+
+        let jane = Member::select().filter(Member::email.eq("jane@example.com")).exec(&conn).unwrap().remove(0);
+        let related = jane.related::<Login>(Login::member).exec(&conn).unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].id, Some(1));
+    }
+
+    #[derive(Debug, Model)]
+    struct Comment {
+        id: Option<i32>,
+        body: String,
+        #[auto_now_add]
+        created_at: NaiveDateTime,
+        #[auto_now]
+        updated_at: NaiveDateTime,
+    }
+
+    #[test]
+    fn auto_now_timestamps() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Comment>().unwrap();
+        conn.migrate().unwrap();
+
+        // The given timestamps are ignored
+        let old = NaiveDateTime::parse_from_str("2000-01-01 00:00:00", "%F %T").unwrap();
+        let id = Comment { id: None, body: "First".to_string(), created_at: old, updated_at: old }.insert(&conn).unwrap();
+        let comment = Comment::find(&conn, id).unwrap().unwrap();
+        assert_ne!(comment.created_at, old);
+        assert_ne!(comment.updated_at, old);
+
+        // Move both back in time, so that the update is visible at second precision
+        conn.exec_raw("UPDATE comments SET created_at = '2000-01-01 00:00:00', updated_at = '2000-01-01 00:00:00'", &[]).unwrap();
+
+        let update = Comment::update().set(Comment::body, "Edited").filter(Comment::id.eq(id));
+        assert_eq!(update.to_sql(), "UPDATE comments SET body=?, updated_at=CURRENT_TIMESTAMP WHERE comments.id = ?");
+        update.exec(&conn).unwrap();
+
+        let comment = Comment::find(&conn, id).unwrap().unwrap();
+        assert_eq!(comment.body, "Edited");
+        assert_eq!(comment.created_at, old);
+        assert!(comment.updated_at > old);
+
+        // Setting the column explicitly takes precedence
+        Comment::update().set(Comment::updated_at, old).exec(&conn).unwrap();
+        assert_eq!(Comment::find(&conn, id).unwrap().unwrap().updated_at, old);
+    }
+
+    #[test]
+    fn query_id() {
+        let first = Author::select().filter(Author::id.eq(1)).to_raw();
+        let second = Author::select().filter(Author::id.eq(2)).to_raw();
+        let other = Author::select().filter(Author::name.eq("John")).to_raw();
+
+        // Same template, different parameters
+        assert_eq!(first.query_id(), second.query_id());
+        assert_ne!(first.query_id(), other.query_id());
+        assert_eq!(first.query_id(), crate::connection::query_id(&first.sql));
+        // Stable across runs (FNV-1a)
+        assert_eq!(crate::connection::query_id(""), 0xcbf29ce484222325);
+        assert_eq!(crate::connection::query_id("a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn relation_without_key_is_null() {
+        let conn = setup_authors();
+
+        // A required relation without a key is rejected instead of pointing at row 0
+        let result = Book { id: None, title: "Orphan".to_string(), author: Relation::default() }.insert(&conn);
+        match result {
+            Err(rusqlite::Error::SqliteFailure(error, _)) => assert_eq!(error.code, rusqlite::ErrorCode::ConstraintViolation),
+            other => panic!("Expected a constraint violation, got {:?}", other),
+        }
+        assert!(!Book::select().filter(Book::title.eq("Orphan")).exists(&conn).unwrap());
+
+        // Without the NOT NULL constraint, the value is stored as NULL
+        conn.exec_raw("CREATE TABLE loose_books (title TEXT, author INTEGER)", &[]).unwrap();
+        conn.exec_raw("INSERT INTO loose_books VALUES (?, ?)", &[&"Loose", &Relation::<Author>::default()]).unwrap();
+        let author: Option<i64> = conn.query_scalar("SELECT author FROM loose_books", &[]).unwrap();
+        assert_eq!(author, None);
+        assert_eq!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM loose_books WHERE author = 0", &[]).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn compound_order_by() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        for (name, age) in [("Bob", 30), ("Alice", 20), ("Carol", 30), ("Dave", 20)] {
+            TestModel { id: None, name: name.to_string(), age, even: None }.insert(&conn).unwrap();
+        }
+
+        let query = TestModel::select().order_by((TestModel::age.desc(), TestModel::name.asc()));
+        let sql = query.to_sql();
+        assert_eq!(sql, "SELECT * FROM test ORDER BY age DESC, name ASC");
+        let names = query.exec(&conn).unwrap().into_iter().map(|m| m.name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Bob", "Carol", "Alice", "Dave"]);
+
+        // Slices, vectors and chained calls give the same order
+        let slice = TestModel::select().order_by([TestModel::age.desc(), TestModel::name.asc()]).to_sql();
+        let vec = TestModel::select().order_by(vec![TestModel::age.desc(), TestModel::name.asc()]).to_sql();
+        let chained = TestModel::select().order_by(TestModel::age.desc()).order_by(TestModel::name.asc()).to_sql();
+        assert_eq!(slice, sql);
+        assert_eq!(vec, sql);
+        assert_eq!(chained, sql);
+        // An empty list doesn't order at all
+        assert!(!TestModel::select().order_by(Vec::<ColumnQueryOrder>::new()).to_sql().contains("ORDER BY"));
+
+        let update = TestModel::update().set(TestModel::even, true).order_by((TestModel::age.asc(), TestModel::name.desc())).limit(1);
+        assert_eq!(update.to_sql(), "UPDATE test SET even=? ORDER BY age ASC, name DESC LIMIT ?");
+        let delete = TestModel::delete().order_by((TestModel::age.asc(), TestModel::name.desc())).limit(1);
+        assert_eq!(delete.to_sql(), "DELETE FROM test ORDER BY age ASC, name DESC LIMIT ?");
+    }
+
+    #[test]
+    fn group_count() {
+        let conn = setup_authors();
+
+        let books_per_author: std::collections::HashMap<i64, i64> = Book::group_count(Book::author, &conn).unwrap();
+        assert_eq!(books_per_author.len(), 2);
+        assert_eq!(books_per_author[&1], 2);
+        assert_eq!(books_per_author[&2], 1);
+
+        let filtered: std::collections::HashMap<i64, i64> = Book::select()
+            .filter(Book::title.ne("First"))
+            .group_count(Book::author, &conn).unwrap();
+        assert_eq!(filtered[&1], 1);
+        assert_eq!(filtered[&2], 1);
+
+        // The selected columns are replaced, including on joined and distinct queries
+        let joined: std::collections::HashMap<i64, i64> = Book::select()
+            .columns(&[Book::title])
+            .distinct()
+            .join_relation(Book::author)
+            .filter(Author::name.eq("Jane"))
+            .group_count(Book::author, &conn).unwrap();
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[&2], 1);
+
+        // NULL is a group of its own
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        for even in [Some(true), Some(true), Some(false), None] {
+            TestModel { id: None, name: "User".to_string(), age: 0, even }.insert(&conn).unwrap();
+        }
+        let tally: std::collections::HashMap<Option<bool>, i64> = TestModel::group_count(TestModel::even, &conn).unwrap();
+        assert_eq!(tally[&Some(true)], 2);
+        assert_eq!(tally[&Some(false)], 1);
+        assert_eq!(tally[&None], 1);
+    }
+
+    #[test]
+    fn column_functions() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        for (name, age) in [("John", -30), ("  jane ", 20), ("JOHN", 5)] {
+            TestModel { id: None, name: name.to_string(), age, even: None }.insert(&conn).unwrap();
+        }
+
+        let query = TestModel::select().filter(TestModel::name.lower().eq("john"));
+        assert_eq!(query.to_sql(), "SELECT * FROM test WHERE lower(test.name) = ?");
+        assert_eq!(query.exec(&conn).unwrap().len(), 2);
+
+        assert_eq!(TestModel::count_where(TestModel::name.upper().eq("JOHN"), &conn).unwrap(), 2);
+        assert_eq!(TestModel::count_where(TestModel::name.trim().eq("jane"), &conn).unwrap(), 1);
+        assert_eq!(TestModel::count_where(TestModel::name.length().gt(4), &conn).unwrap(), 1);
+        assert_eq!(TestModel::count_where(TestModel::age.abs().ge(20), &conn).unwrap(), 2);
+
+        // Functions can be chained and combined with other filters
+        let query = TestModel::select().filter(TestModel::name.trim().upper().like("J%") & TestModel::age.gt(0));
+        assert_eq!(query.to_sql(), "SELECT * FROM test WHERE (upper(trim(test.name)) LIKE ?) AND (test.age > ?)");
+        let names = query.exec(&conn).unwrap().into_iter().map(|m| m.name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["  jane ", "JOHN"]);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "accounts"]
+    struct AccountV1 {
+        id: Option<i32>,
+        #[check = "balance >= 0"]
+        balance: i32,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "accounts"]
+    struct AccountV2 {
+        id: Option<i32>,
+        #[check = "balance >= -100"]
+        balance: i32,
+    }
+
+    #[test]
+    fn check_constraint() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<AccountV1>().unwrap();
+        conn.migrate().unwrap();
+        assert_eq!(AccountV1::balance.get_check(), Some("balance >= 0"));
+
+        AccountV1 { id: None, balance: 10 }.insert(&conn).unwrap();
+        match (AccountV1 { id: None, balance: -1 }).insert(&conn) {
+            Err(rusqlite::Error::SqliteFailure(error, _)) => assert_eq!(error.code, rusqlite::ErrorCode::ConstraintViolation),
+            other => panic!("Expected a constraint violation, got {:?}", other),
+        }
+        assert!(AccountV1::update().set(AccountV1::balance, -5).exec(&conn).is_err());
+        assert!(conn.migrate().unwrap().is_empty());
+
+        // A changed expression rebuilds the table
+        conn.register::<AccountV2>().unwrap();
+        let report = conn.migrate().unwrap();
+        assert_eq!(report.operations, vec!["rebuild table accounts"]);
+        assert!(conn.migrate().unwrap().is_empty());
+
+        AccountV2 { id: None, balance: -50 }.insert(&conn).unwrap();
+        assert!(AccountV2 { id: None, balance: -101 }.insert(&conn).is_err());
+        assert_eq!(AccountV2::count().exec(&conn).unwrap(), 2);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "tags"]
+    struct TagV1 {
+        id: Option<i32>,
+        #[unique]
+        name: String,
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "tags"]
+    struct TagV2 {
+        id: Option<i32>,
+        #[unique]
+        #[collate = "NOCASE"]
+        name: String,
+    }
+
+    #[test]
+    fn collate_column() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TagV1>().unwrap();
+        conn.migrate().unwrap();
+        for name in ["banana", "Apple", "cherry"] {
+            TagV1 { id: None, name: name.to_string() }.insert(&conn).unwrap();
+        }
+
+        // Binary collation by default, NOCASE at sort time
+        let names = |tags: Vec<TagV1>| tags.into_iter().map(|t| t.name).collect::<Vec<_>>();
+        assert_eq!(names(TagV1::select().order_by(TagV1::name.asc()).exec(&conn).unwrap()), vec!["Apple", "banana", "cherry"]);
+        TagV1 { id: None, name: "apple".to_string() }.insert(&conn).unwrap();
+        let query = TagV1::select().order_by((TagV1::name.desc().collate("NOCASE"), TagV1::id.asc()));
+        assert_eq!(query.to_sql(), "SELECT * FROM tags ORDER BY name COLLATE NOCASE DESC, id ASC");
+        assert_eq!(names(query.exec(&conn).unwrap()), vec!["cherry", "banana", "Apple", "apple"]);
+        TagV1::delete().filter(TagV1::name.eq("apple")).exec(&conn).unwrap();
+
+        // Adding the collation rebuilds the table
+        conn.register::<TagV2>().unwrap();
+        assert_eq!(conn.migrate().unwrap().operations, vec!["rebuild table tags"]);
+        assert!(conn.migrate().unwrap().is_empty());
+        assert_eq!(TagV2::name.get_collation(), Some("NOCASE"));
+
+        // Uniqueness, filters and ordering ignore case now
+        assert!(TagV2 { id: None, name: "APPLE".to_string() }.insert(&conn).is_err());
+        assert_eq!(TagV2::count_where(TagV2::name.eq("CHERRY"), &conn).unwrap(), 1);
+        TagV2 { id: None, name: "apricot".to_string() }.insert(&conn).unwrap();
+        let tags = TagV2::select().order_by(TagV2::name.asc()).exec(&conn).unwrap();
+        assert_eq!(tags.into_iter().map(|t| t.name).collect::<Vec<_>>(), vec!["Apple", "apricot", "banana", "cherry"]);
+    }
+
+    #[derive(Debug, Model)]
+    #[table_name = "handles"]
+    struct Handle {
+        id: Option<i32>,
+        #[check = "name COLLATE NOCASE <> 'admin'"]
+        name: String,
+        #[default_value(&"CHECK(x) COLLATE y")]
+        note: String,
+    }
+
+    #[test]
+    fn constraints_in_table_definition() {
+        // Keywords inside expressions and defaults are not constraints of their own
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Handle>().unwrap();
+        conn.migrate().unwrap();
+        assert!(conn.migration_plan().unwrap().is_empty());
+        assert!(Handle { id: None, name: "ADMIN".to_string(), note: String::new() }.insert(&conn).is_err());
+
+        // Tables written by hand are compared by their constraints, not by their formatting
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<AccountV1>().unwrap();
+        let create = conn.migration_plan().unwrap().remove(0).replace("CHECK(balance >= 0)", "check ( balance >= 0 )");
+        conn.execute_batch(&create).unwrap();
+        assert!(conn.migration_plan().unwrap().is_empty());
+
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TagV2>().unwrap();
+        let create = conn.migration_plan().unwrap().remove(0).replace("COLLATE NOCASE", "collate nocase");
+        conn.execute_batch(&create).unwrap();
+        assert!(conn.migration_plan().unwrap().is_empty());
+
+        // Removing a constraint rebuilds the table
+        conn.register::<TagV1>().unwrap();
+        assert_eq!(conn.migrate().unwrap().operations, vec!["rebuild table tags"]);
+        assert!(conn.migrate().unwrap().is_empty());
+        conn.register::<AccountV1>().unwrap();
+        conn.migrate().unwrap();
+        conn.execute_batch("DROP TABLE accounts; CREATE TABLE accounts (id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, balance INTEGER NOT NULL)").unwrap();
+        assert_eq!(conn.migrate().unwrap().operations, vec!["rebuild table accounts"]);
+    }
+
+    #[test]
+    fn execute_batch_and_run_all() {
+        use crate::connection::RawQuery;
+
+        let conn = setup_authors();
+        conn.execute_batch("
+            CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT);
+            INSERT INTO settings VALUES ('theme', 'dark');
+            INSERT INTO settings VALUES ('language', 'en');
+            UPDATE authors SET name = 'Johnny' WHERE id = 1;
+        ").unwrap();
+        assert_eq!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM settings", &[]).unwrap(), Some(2));
+        assert_eq!(Author::find(&conn, 1).unwrap().unwrap().name, "Johnny");
+
+        let affected = conn.run_all(&[
+            Book::update().set(Book::title, "Renamed").filter(Book::author.eq(1)).to_raw(),
+            RawQuery::new("DELETE FROM settings WHERE key = ?".to_string(), vec![Box::new("theme")]),
+        ]).unwrap();
+        assert_eq!(affected, 3);
+        assert_eq!(Book::count_where(Book::title.eq("Renamed"), &conn).unwrap(), 2);
+        assert_eq!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM settings", &[]).unwrap(), Some(1));
+
+        // All or nothing
+        let result = conn.run_all(&[
+            RawQuery::new("DELETE FROM settings".to_string(), Vec::new()),
+            RawQuery::new("INSERT INTO missing VALUES (1)".to_string(), Vec::new()),
+        ]);
+        assert!(result.is_err());
+        assert_eq!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM settings", &[]).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn register_and_create() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register_and_create::<Author>().unwrap();
+        conn.register_and_create::<Article>().unwrap();
+
+        let tables = conn.get_all_tables().unwrap();
+        for table in ["authors", "articles", "articles_tags", "articles_scores"] {
+            assert!(tables.contains(&table.to_string()), "missing table {}", table);
+        }
+        Author { id: None, name: "John".to_string() }.insert(&conn).unwrap();
+        Article { id: None, title: "First".to_string(), tags: vec!["rust".to_string()], scores: Vec::new() }.insert(&conn).unwrap();
+        assert_eq!(Article::select().exec(&conn).unwrap()[0].tags, vec!["rust"]);
+
+        // Existing tables are kept, and the schema is already up to date
+        conn.register_and_create::<Author>().unwrap();
+        assert_eq!(Author::count().exec(&conn).unwrap(), 1);
+        assert!(conn.migrate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Article>().unwrap();
+        conn.migrate().unwrap();
+        for title in ["First", "Second", "Third"] {
+            Article { id: None, title: title.to_string(), tags: vec!["a".to_string(), "b".to_string()], scores: vec![1] }.insert(&conn).unwrap();
+        }
+
+        assert_eq!(Article::truncate(&conn).unwrap(), 3);
+        assert_eq!(Article::count().exec(&conn).unwrap(), 0);
+        assert_eq!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM articles_tags", &[]).unwrap(), Some(0));
+
+        // Ids start over
+        let id = Article { id: None, title: "Again".to_string(), tags: vec!["c".to_string()], scores: Vec::new() }.insert(&conn).unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(conn.query_scalar::<i64>("SELECT id FROM articles_tags", &[]).unwrap(), Some(1));
+        assert_eq!(Article::find(&conn, 1).unwrap().unwrap().tags, vec!["c"]);
+
+        // Other tables are not affected
+        let conn = setup_authors();
+        assert_eq!(conn.truncate::<Book>().unwrap(), 3);
+        assert_eq!(Author::count().exec(&conn).unwrap(), 3);
+    }
+
+    #[derive(Debug, Model)]
+    struct Employee {
+        id: Option<i32>,
+        name: String,
+        manager: Option<Relation<Self>>,
+        mentor: Option<Relation<Employee>>,
+    }
+
+    #[test]
+    fn self_relation() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Employee>().unwrap();
+        conn.migrate().unwrap();
+
+        let schema: String = conn.query_scalar("SELECT sql FROM sqlite_master WHERE name = 'employees'", &[]).unwrap().unwrap();
+        assert!(schema.contains("manager INTEGER REFERENCES employees(id)"), "{}", schema);
+
+        // Boss -> Lead -> Dev, and Boss mentors Dev
+        let boss = Employee { id: None, name: "Boss".to_string(), manager: None, mentor: None }.insert(&conn).unwrap();
+        let lead = Employee { id: None, name: "Lead".to_string(), manager: Some(Relation::id(boss)), mentor: None }.insert(&conn).unwrap();
+        let dev = Employee { id: None, name: "Dev".to_string(), manager: Some(Relation::id(lead)), mentor: Some(Relation::id(boss)) }.insert(&conn).unwrap();
+
+        let mut dev = Employee::find(&conn, dev).unwrap().unwrap();
+        let manager = dev.manager.as_mut().unwrap().get(&conn).unwrap();
+        assert_eq!(manager.name, "Lead");
+        let mut grand_manager = manager.manager.clone().unwrap();
+        assert_eq!(grand_manager.get(&conn).unwrap().name, "Boss");
+        assert_eq!(dev.mentor.as_ref().unwrap().fetch_once(&conn).unwrap().name, "Boss");
+
+        // Reports of the boss
+        let boss = Employee::find(&conn, boss).unwrap().unwrap();
+        let reports = boss.related::<Employee>(Employee::manager).exec(&conn).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].name, "Lead");
+
+        // The joined table is aliased, so both relations can be joined
+        let query = Employee::select()
+            .join_relation(Employee::manager)
+            .join_relation(Employee::mentor)
+            .filter(Employee::name.eq("Dev"));
+        assert_eq!(query.to_sql(), "SELECT * FROM employees LEFT JOIN employees AS manager ON manager.id = employees.manager LEFT JOIN employees AS mentor ON mentor.id = employees.mentor WHERE employees.name = ?");
+        let dev = query.exec(&conn).unwrap().remove(0);
+        assert_eq!(dev.manager.as_ref().unwrap().try_get().unwrap().name, "Lead");
+        assert_eq!(dev.mentor.as_ref().unwrap().try_get().unwrap().name, "Boss");
+    }
+
+    #[test]
+    fn delete_by_ids() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        let ids = (0..10)
+            .map(|i| TestModel { id: None, name: format!("User {}", i), age: i, even: None }.insert(&conn).unwrap())
+            .collect::<Vec<_>>();
+
+        // Ids computed at runtime, one of them doesn't exist
+        let to_delete = ids.iter().copied().filter(|id| id % 2 == 0).chain([42]).collect::<Vec<_>>();
+        assert_eq!(TestModel::delete_by_ids(&conn, &to_delete).unwrap(), 5);
+        let remaining = TestModel::select().exec(&conn).unwrap().into_iter().map(|m| m.id.unwrap()).collect::<Vec<_>>();
+        assert_eq!(remaining, vec![1, 3, 5, 7, 9]);
+
+        assert_eq!(TestModel::delete_by_ids(&conn, &[]).unwrap(), 0);
+
+        // More ids than can be bound in a single statement
+        let many = (1..=2500).collect::<Vec<i64>>();
+        assert_eq!(TestModel::delete_by_ids(&conn, &many).unwrap(), 5);
+        assert_eq!(TestModel::count().exec(&conn).unwrap(), 0);
+    }
+
+    #[derive(Debug, Model)]
+    struct Feature {
+        id: Option<i32>,
+        name: String,
+        enabled: bool,
+        #[default_value(&true)]
+        visible: bool,
+        beta: Option<bool>,
+    }
+
+    #[test]
+    fn bool_round_trip() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<Feature>().unwrap();
+        conn.migrate().unwrap();
+
+        Feature { id: None, name: "dark mode".to_string(), enabled: true, visible: false, beta: Some(false) }.insert(&conn).unwrap();
+        Feature { id: None, name: "search".to_string(), enabled: false, visible: true, beta: None }.insert(&conn).unwrap();
+        // Stored as integers
+        assert_eq!(conn.query_scalar::<i64>("SELECT enabled FROM features WHERE id = 1", &[]).unwrap(), Some(1));
+        assert_eq!(conn.query_scalar::<i64>("SELECT visible FROM features WHERE id = 1", &[]).unwrap(), Some(0));
+
+        let features = Feature::select().exec(&conn).unwrap();
+        assert!(features[0].enabled);
+        assert!(!features[0].visible);
+        assert_eq!(features[0].beta, Some(false));
+        assert!(!features[1].enabled);
+        assert!(features[1].visible);
+        assert_eq!(features[1].beta, None);
+
+        // Filters, updates and the default value
+        assert_eq!(Feature::count_where(Feature::enabled.eq(true), &conn).unwrap(), 1);
+        Feature::update().set(Feature::enabled, true).set(Feature::beta, true).filter(Feature::id.eq(2)).exec(&conn).unwrap();
+        let search = Feature::find(&conn, 2).unwrap().unwrap();
+        assert!(search.enabled);
+        assert_eq!(search.beta, Some(true));
+        conn.exec_raw("INSERT INTO features (name, enabled) VALUES ('export', 0)", &[]).unwrap();
+        let export = Feature::find(&conn, 3).unwrap().unwrap();
+        assert!(!export.enabled);
+        assert!(export.visible);
+    }
+
+    #[test]
+    fn table_exists_and_row_count() {
+        let conn = setup_authors();
+
+        assert!(conn.table_exists("authors").unwrap());
+        assert!(conn.table_exists("books").unwrap());
+        assert!(!conn.table_exists("missing").unwrap());
+        assert!(!conn.table_exists("authors' OR '1'='1").unwrap());
+
+        assert_eq!(conn.row_count("authors").unwrap(), 3);
+        assert_eq!(conn.row_count("books").unwrap(), 3);
+        assert!(conn.row_count("missing").is_err());
+
+        // Names are quoted
+        conn.exec_raw("CREATE TABLE \"odd \"\"name\" (value INTEGER)", &[]).unwrap();
+        conn.exec_raw("INSERT INTO \"odd \"\"name\" VALUES (1), (2)", &[]).unwrap();
+        assert!(conn.table_exists("odd \"name").unwrap());
+        assert_eq!(conn.row_count("odd \"name").unwrap(), 2);
+        assert!(conn.row_count("authors; DROP TABLE books").is_err());
+        assert!(conn.table_exists("books").unwrap());
+    }
+
+    #[test]
+    fn in_borrowed_slice() {
+        fn titles_of(conn: &Connection, authors: &[i64]) -> Vec<String> {
+            Book::select().filter(Book::author.in_(authors)).exec(conn).unwrap().into_iter().map(|b| b.title).collect()
+        }
+
+        let conn = setup_authors();
+        let authors = vec![2, 3];
+        assert_eq!(titles_of(&conn, &authors), vec!["Third"]);
+        assert_eq!(titles_of(&conn, &authors[..0]), Vec::<String>::new());
+
+        // Borrowed strings and arrays
+        let names = vec!["John".to_string(), "Bob".to_string()];
+        assert_eq!(Author::count_where(Author::name.in_(names.as_slice()), &conn).unwrap(), 2);
+        let ids = [1, 2];
+        assert_eq!(Author::count_where(Author::id.not_in(&ids), &conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn keyset_pagination() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        let rows = (0..100)
+            .map(|i| TestModel { id: None, name: format!("User {}", i), age: i, even: Some(i % 2 == 0) })
+            .collect::<Vec<_>>();
+        conn.insert(rows.as_slice()).unwrap();
+
+        let query = TestModel::select().after(TestModel::id, 10).limit(5);
+        assert_eq!(query.to_sql(), "SELECT * FROM test WHERE test.id > ? ORDER BY test.id ASC LIMIT ?");
+
+        // Page through all rows by the id cursor
+        let mut seen = Vec::new();
+        let mut last_id = 0;
+        loop {
+            let page = TestModel::select().after(TestModel::id, last_id).limit(7).exec(&conn).unwrap();
+            match page.last() {
+                Some(last) => last_id = last.id.unwrap(),
+                None => break,
+            }
+            seen.extend(page.into_iter().map(|m| m.id.unwrap()));
+        }
+        assert_eq!(seen, (1..=100).collect::<Vec<_>>());
+
+        // Combined with a filter
+        let mut seen = Vec::new();
+        let mut last_age = -1;
+        loop {
+            let page = TestModel::select()
+                .filter(TestModel::even.eq(true))
+                .after(TestModel::age, last_age)
+                .limit(10)
+                .exec(&conn).unwrap();
+            match page.last() {
+                Some(last) => last_age = last.age,
+                None => break,
+            }
+            seen.extend(page.into_iter().map(|m| m.age));
+        }
+        assert_eq!(seen, (0..100).step_by(2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn keyset_pagination_joined() {
+        let conn = setup_authors();
+
+        // Both tables have an id column
+        let query = Book::select().join_relation(Book::author).after(Book::id, 1);
+        assert_eq!(query.to_sql(), "SELECT * FROM books LEFT JOIN authors ON authors.id = books.author WHERE books.id > ? ORDER BY books.id ASC");
+        let books = query.exec(&conn).unwrap();
+        assert_eq!(books.iter().map(|book| book.title.as_str()).collect::<Vec<_>>(), vec!["Second", "Third"]);
+        assert_eq!(books[1].author.try_get().unwrap().name, "Jane");
+    }
+
+    #[test]
+    fn offset_without_limit() {
+        let conn = setup_authors();
+
+        let query = Book::select().order_by(Book::title.asc()).offset(1);
+        assert_eq!(query.to_sql(), "SELECT * FROM books ORDER BY title ASC LIMIT -1 OFFSET ?");
+        let books = query.exec(&conn).unwrap();
+        assert_eq!(books.iter().map(|b| b.title.as_str()).collect::<Vec<_>>(), vec!["Second", "Third"]);
+
+        let books = Book::select().offset(5).exec(&conn).unwrap();
+        assert!(books.is_empty());
+    }
+
+    #[test]
+    fn arbitrary_pragmas() {
+        let conn = Connection::new_memory().unwrap();
+
+        conn.pragma("cache_size", -4000).unwrap();
+        assert_eq!(conn.pragma_query::<i64>("cache_size").unwrap(), -4000);
+
+        conn.pragma("temp_store", "MEMORY").unwrap();
+        assert_eq!(conn.pragma_query::<i64>("temp_store").unwrap(), 2);
+
+        // Pragmas returning the new value don't fail either
+        conn.pragma("mmap_size", 0).unwrap();
+
+        assert!(conn.pragma_query::<i64>("not_a_pragma").is_err());
+    }
+
+    #[test]
+    fn paginate() {
+        let mut conn = Connection::new_memory().unwrap();
+        conn.register::<TestModel>().unwrap();
+        conn.migrate().unwrap();
+        let rows = (0..25)
+            .map(|i| TestModel { id: None, name: format!("User {}", i), age: i, even: Some(i % 2 == 0) })
+            .collect::<Vec<_>>();
+        conn.insert(rows.as_slice()).unwrap();
+
+        let pages = (1..=3)
+            .map(|page| TestModel::select().order_by(TestModel::age.asc()).paginate(page, 10, &conn).unwrap())
+            .collect::<Vec<_>>();
+        for (i, page) in pages.iter().enumerate() {
+            assert_eq!(page.page, i as u32 + 1);
+            assert_eq!(page.total, 25);
+            assert_eq!(page.total_pages, 3);
+        }
+        assert_eq!(pages.iter().map(|p| p.items.len()).collect::<Vec<_>>(), vec![10, 10, 5]);
+        let ages = pages.into_iter().flat_map(|p| p.items).map(|m| m.age).collect::<Vec<_>>();
+        assert_eq!(ages, (0..25).collect::<Vec<_>>());
+
+        // The total counts only the filtered rows, and pages past the end are empty
+        let page = TestModel::select().filter(TestModel::even.eq(true)).limit(1).paginate(2, 10, &conn).unwrap();
+        assert_eq!((page.total, page.total_pages, page.items.len()), (13, 2, 3));
+        let page = TestModel::select().paginate(4, 10, &conn).unwrap();
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 25);
+        assert!(conn.connection.is_autocommit());
+
+        // Invalid pages are errors instead of panics or clamped offsets
+        for (page, per_page) in [(0, 10), (1, 0), (u32::MAX, 2)] {
+            match TestModel::select().paginate(page, per_page, &conn) {
+                Err(rusqlite::Error::ToSqlConversionFailure(_)) => {},
+                other => panic!("Expected an error for page {} of {}, got {:?}", page, per_page, other.map(|page| page.items.len())),
+            }
+        }
+        assert_eq!(TestModel::select().paginate(u32::MAX / 10 + 1, 10, &conn).unwrap().items.len(), 0);
+        assert!(conn.connection.is_autocommit());
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use rusqlite::{ToSql, types::FromSql};
+
+use crate::connection::{Connection, MAX_INSERT_PARAMS, qualified_table_name};
+
+use super::Column;
+
+/// A side table that stores the values of a `Vec<T>` field of a model.
+///
+/// Every value is stored in a separate row of the `{table}_{field}` table, keyed by the id of the parent row.
+/// This is automatically generated for every `Vec<T>` field (except `Vec<u8>`, which is a blob) of a struct that derives [Model](sequelite_macro::Model).
+#[derive(Debug)]
+pub struct ModelCollection {
+    pub table: &'static str,
+    pub columns: &'static [Column<'static>],
+}
+
+impl ModelCollection {
+    pub const fn new(table: &'static str, columns: &'static [Column<'static>]) -> Self {
+        ModelCollection {
+            table,
+            columns,
+        }
+    }
+
+    /// Load the values of many parent rows at once, using one query per 999 parents.
+    ///
+    /// ## Arguments
+    /// * `parent_ids` - Ids of the parent rows
+    /// * `conn` - The connection to load the values from
+    ///
+    /// ## Returns
+    /// The values of every parent row that has any, in the order they were saved.
+    pub fn load_values<T: FromSql>(&self, parent_ids: &[i64], conn: &Connection) -> Result<HashMap<i64, Vec<T>>, rusqlite::Error> {
+        let mut values: HashMap<i64, Vec<T>> = HashMap::new();
+        for ids in parent_ids.chunks(MAX_INSERT_PARAMS) {
+            let placeholders = vec!["?"; ids.len()].join(", ");
+            let sql = format!("SELECT parent_id, value FROM {} WHERE parent_id IN ({}) ORDER BY id", qualified_table_name(self.table), placeholders);
+            let mut statement = conn.connection.prepare_cached(&sql)?;
+            let mut rows = statement.query(rusqlite::params_from_iter(ids))?;
+            while let Some(row) = rows.next()? {
+                values.entry(row.get(0)?).or_default().push(row.get(1)?);
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// The values of a `Vec<T>` field of a single model, which are saved to its side table.
+pub struct CollectionValues {
+    pub collection: &'static ModelCollection,
+    pub values: Vec<Box<dyn ToSql>>,
+}
+
+impl CollectionValues {
+    /// Replace the values stored for the parent row with these values.
+    pub(crate) fn save(&self, parent_id: i64, conn: &Connection) -> Result<(), rusqlite::Error> {
+        let table = qualified_table_name(self.collection.table);
+        conn.connection.prepare_cached(&format!("DELETE FROM {} WHERE parent_id = ?", table))?
+            .execute([parent_id])?;
+        let mut statement = conn.connection.prepare_cached(&format!("INSERT INTO {} (parent_id, value) VALUES (?, ?)", table))?;
+        for value in self.values.iter() {
+            statement.execute(rusqlite::params![parent_id, value])?;
+        }
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use crate::{sql_types::{SqliteType, SqliteFlag}, IntoSqlite, prelude::ColumnQueryFilterImpl};
 
-use super::{relation::ColumnRelation, query::InQueryFilter, Model, ModelExt};
+use super::{relation::ColumnRelation, query::{InQueryFilter, ExistsQueryFilter, Aggregate, ColumnExpression, exists}, Model, ModelExt};
 
 /// A column of a model.
 /// This struct is quite big, so it is automatically implemented for every column in a struct that derives [Model](sequelite_macro::Model).
@@ -17,6 +17,17 @@ pub struct Column<'a> {
     relation: Option<ColumnRelation<'a>>,
 
     default: Option<DefaultValue>,
+
+    renamed_from: Option<&'a str>,
+
+    generated: Option<&'a str>,
+
+    check: Option<&'a str>,
+
+    collation: Option<&'a str>,
+
+    auto_now_add: bool,
+    auto_now: bool,
 }
 
 /// A default value for a column.
@@ -37,6 +48,15 @@ impl IntoSqlite for DefaultValue {
     }
 }
 
+/// A default value read back from the database, which is already an SQL expression.
+pub(crate) struct DefaultExpression(pub String);
+
+impl IntoSqlite for DefaultExpression {
+    fn into_sqlite(&self) -> String {
+        self.0.clone()
+    }
+}
+
 impl PartialEq for Column<'_> {
     fn eq(&self, other: &Self) -> bool {
         self.name() == other.name() && self.ty == other.ty && self.flags() == other.flags()
@@ -56,6 +76,17 @@ impl Clone for Column<'_> {
             relation: self.relation,
 
             default: None,
+
+            renamed_from: self.renamed_from,
+
+            generated: self.generated,
+
+            check: self.check,
+
+            collation: self.collation,
+
+            auto_now_add: self.auto_now_add,
+            auto_now: self.auto_now,
         }
     }
 }
@@ -74,18 +105,28 @@ impl<'a> IntoSqlite for Column<'a> {
     fn into_sqlite(&self) -> String {
         // Base
         let mut sql = format!("{} {}", self.name, self.ty.into_sqlite());
+        // Collation right after the type
+        if let Some(collation) = self.collation {
+            sql = format!("{} COLLATE {}", sql, collation);
+        }
         // Flags
         for flag in self.flags.iter() {
             sql = format!("{} {}", sql, flag.into_sqlite());
         }
-        // Default
-        if let Some(def) = &self.default {
+        // Default (generated columns can't have one)
+        if let Some(expr) = self.generated {
+            sql = format!("{} GENERATED ALWAYS AS ({}) STORED", sql, expr);
+        } else if let Some(def) = &self.default {
             sql = format!("{} DEFAULT {}", sql, def.into_sqlite());
         }
 
-        // Foreign key
+        if let Some(expr) = self.check {
+            sql = format!("{} CHECK({})", sql, expr);
+        }
+
+        // Foreign key (as a column constraint, so that other columns can follow it)
         if let Some(relation) = &self.relation {
-            sql = format!("{}, FOREIGN KEY({}) {}", sql, self.name(), relation.into_sqlite());
+            sql = format!("{} {}", sql, relation.into_sqlite());
         }
 
         sql
@@ -104,7 +145,18 @@ impl<'a> Column<'a> {
 
             relation,
 
-            default: default.map(|def| DefaultValue::Owned(def)),
+            default: default.map(DefaultValue::Owned),
+
+            renamed_from: None,
+
+            generated: None,
+
+            check: None,
+
+            collation: None,
+
+            auto_now_add: false,
+            auto_now: false,
         }
     }
     
@@ -116,6 +168,45 @@ impl<'a> Column<'a> {
         }
     }
 
+    /// Check if the column is generated from other columns (set using the `generated` attribute).
+    /// Generated columns are computed by the database and skipped when inserting.
+    pub fn is_generated(&self) -> bool {
+        self.generated.is_some()
+    }
+
+    /// Marks a column read from the database as generated.
+    /// The expression can't be read back from `PRAGMA table_xinfo`, so it is left empty.
+    pub(crate) fn mark_generated(mut self) -> Self {
+        self.generated = Some("");
+        self
+    }
+
+    /// Check if the column is set to the current time when a row is inserted (set using the `auto_now_add` or `auto_now` attribute).
+    /// These columns are skipped when inserting, so that their default (`CURRENT_TIMESTAMP`) is used.
+    pub fn is_auto_now_add(&self) -> bool {
+        self.auto_now_add || self.auto_now
+    }
+
+    /// Check if the column is set to the current time whenever a row is updated (set using the `auto_now` attribute).
+    pub fn is_auto_now(&self) -> bool {
+        self.auto_now
+    }
+
+    /// Get the CHECK constraint expression of the column (set using the `check` attribute).
+    pub fn get_check(&self) -> Option<&'a str> {
+        self.check
+    }
+
+    /// Get the collation of the column (set using the `collate` attribute).
+    pub fn get_collation(&self) -> Option<&'a str> {
+        self.collation
+    }
+
+    /// Get the previous name of the column (set using the `renamed_from` attribute).
+    pub fn get_renamed_from(&self) -> Option<&'a str> {
+        self.renamed_from
+    }
+
     /// Returns the name of the column.
     /// 
     /// ## Example
@@ -140,17 +231,90 @@ impl<'a> Column<'a> {
         self.flags().contains(&flag)
     }
 
+    /// Count non-NULL values of the column in a group (`COUNT(table.column)`).
+    pub fn count(&self) -> Aggregate {
+        self.aggregate("COUNT")
+    }
+
+    /// Sum of the column in a group (`SUM(table.column)`).
+    pub fn sum(&self) -> Aggregate {
+        self.aggregate("SUM")
+    }
+
+    /// Average of the column in a group (`AVG(table.column)`).
+    pub fn avg(&self) -> Aggregate {
+        self.aggregate("AVG")
+    }
+
+    /// Minimum of the column in a group (`MIN(table.column)`).
+    pub fn min(&self) -> Aggregate {
+        self.aggregate("MIN")
+    }
+
+    /// Maximum of the column in a group (`MAX(table.column)`).
+    pub fn max(&self) -> Aggregate {
+        self.aggregate("MAX")
+    }
+
+    /// Exact sum of a [Decimal](crate::model::decimal::Decimal) column in a group (`decimal_sum(table.column)`).
+    #[cfg(feature = "decimal")]
+    pub fn decimal_sum(&self) -> Aggregate {
+        self.aggregate("decimal_sum")
+    }
+
+    fn aggregate(&self, function: &str) -> Aggregate {
+        Aggregate::new(self.table_name, format!("{}({}.{})", function, self.table_name, self.name()))
+    }
+
+    /// The column converted to lower case (`lower(table.column)`), eg. for case-insensitive comparisons.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::select().filter(User::name.lower().eq("john")).exec(&conn).unwrap();
+    /// ```
+    pub fn lower(&self) -> ColumnExpression {
+        self.expression().lower()
+    }
+
+    /// The column converted to upper case (`upper(table.column)`).
+    pub fn upper(&self) -> ColumnExpression {
+        self.expression().upper()
+    }
+
+    /// Length of the column (`length(table.column)`), in characters for text and bytes for blobs.
+    pub fn length(&self) -> ColumnExpression {
+        self.expression().length()
+    }
+
+    /// Absolute value of the column (`abs(table.column)`).
+    pub fn abs(&self) -> ColumnExpression {
+        self.expression().abs()
+    }
+
+    /// The column with spaces removed from both ends (`trim(table.column)`).
+    pub fn trim(&self) -> ColumnExpression {
+        self.expression().trim()
+    }
+
+    fn expression(&self) -> ColumnExpression {
+        ColumnExpression::new(self.table_name, format!("{}.{}", self.table_name, self.name()))
+    }
+
     /// Check if the column has a default value.
     pub fn has_default(&self) -> bool {
         self.default.is_some()
     }
 
-    // For the future use.
-    #[allow(dead_code)]
+    /// Get the default value of the column as an SQL expression.
+    pub(crate) fn default_sql(&self) -> Option<String> {
+        self.default.as_ref().map(|default| default.into_sqlite())
+    }
+
+    /// Check if both columns have the same default value (compared by its SQL).
     pub(crate) fn same_default(&self, other: &Self) -> bool {
         match (&self.default, &other.default) {
-            (Some(DefaultValue::Owned(a)), Some(DefaultValue::Owned(b))) => a.into_sqlite() == b.into_sqlite(),
-            (Some(DefaultValue::Ref(a)), Some(DefaultValue::Ref(b))) => a.into_sqlite() == b.into_sqlite(),
+            (Some(a), Some(b)) => a.into_sqlite() == b.into_sqlite(),
+            (None, None) => true,
             _ => false
         }
     }
@@ -213,9 +377,91 @@ impl Column<'static> {
             relation,
 
             default: def,
+
+            renamed_from: None,
+
+            generated: None,
+
+            check: None,
+
+            collation: None,
+
+            auto_now_add: false,
+            auto_now: false,
         }
     }
+
+    /// Marks the column as renamed from another column, so that the migrator renames it instead of dropping the old one.
+    /// This is used to implement the `renamed_from` attribute in the [Model](sequelite_macro::Model) macro.
+    pub const fn renamed_from(mut self, old_name: &'static str) -> Column<'static> {
+        self.renamed_from = Some(old_name);
+        self
+    }
+
+    /// Makes the column a STORED generated column, computed from the given SQL expression (which can use other columns).
+    /// This is used to implement the `generated` attribute in the [Model](sequelite_macro::Model) macro.
+    pub const fn generated(mut self, expr: &'static str) -> Column<'static> {
+        self.generated = Some(expr);
+        self
+    }
     
+    /// Adds a CHECK constraint to the column, rows for which the SQL expression is false are rejected.
+    /// This is used to implement the `check` attribute in the [Model](sequelite_macro::Model) macro.
+    pub const fn check(mut self, expr: &'static str) -> Column<'static> {
+        self.check = Some(expr);
+        self
+    }
+
+    /// Sets the collation of the column (eg. `NOCASE` for case-insensitive comparisons), which adds `COLLATE name` to the column definition.
+    /// This is used to implement the `collate` attribute in the [Model](sequelite_macro::Model) macro,
+    /// to sort a single query with a collation use [ColumnQueryOrder::collate](super::query::ColumnQueryOrder::collate).
+    /// 
+    /// ## Panics
+    /// If the collation name is not a valid identifier
+    pub const fn collation(mut self, collation: &'static str) -> Column<'static> {
+        let bytes = collation.as_bytes();
+        if bytes.is_empty() {
+            panic!("Invalid collation name");
+        }
+        let mut i = 0;
+        while i < bytes.len() {
+            if !(bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                panic!("Invalid collation name");
+            }
+            i += 1;
+        }
+        self.collation = Some(collation);
+        self
+    }
+
+    /// Sets the column to the current time when a row is inserted.
+    /// This is used to implement the `auto_now_add` attribute in the [Model](sequelite_macro::Model) macro.
+    pub const fn auto_now_add(mut self) -> Column<'static> {
+        self.auto_now_add = true;
+        self
+    }
+
+    /// Sets the column to the current time when a row is inserted or updated.
+    /// This is used to implement the `auto_now` attribute in the [Model](sequelite_macro::Model) macro.
+    pub const fn auto_now(mut self) -> Column<'static> {
+        self.auto_now = true;
+        self
+    }
+
+    /// Shorthand method for filtering rows which are referenced by at least one row of another model.
+    /// 
+    /// # Expanded Example
+    /// ```rust
+    /// // Short form (users who have at least one post)
+    /// User::select().filter(User::id.has_related::<Post>(Post::author))
+    /// 
+    /// // Expanded form
+    /// User::select().filter(exists(Post::select().filter(Post::author.eq_col(User::id))))
+    /// ```
+    pub fn has_related<M: Model + ModelExt<M>>(self, foreign_key: Column<'static>) -> ExistsQueryFilter {
+        exists(M::select().filter(foreign_key.eq_col(self)))
+    }
+
     pub const fn name_const(&self) -> &'static str {
         self.name
     }
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+use rusqlite::{ToSql, functions::{Aggregate, Context, FunctionFlags}, types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef}};
+
+use crate::sql_types::{SqliteType, SqliteTyped};
+
+/// A column that stores an exact decimal number as text, eg. for money.
+///
+/// SQLite's own numeric functions (like `SUM`) convert text to floating point,
+/// use the `decimal_sum` aggregate (see [Column::decimal_sum](crate::model::Column::decimal_sum)) to sum these columns exactly.
+/// Values are compared as text, so only equality filters (`eq`, `ne`, `in_`) work as expected,
+/// ordering (`gt`, `lt`, `ORDER BY`) is not numeric.
+///
+/// ## Example use
+/// ```rust
+/// use sequelite::prelude::*;
+///
+/// #[derive(Debug, Model)]
+/// struct Product {
+///     id: Option<i32>,
+///     price: Decimal,
+/// }
+///
+/// let product = Product { id: None, price: "19.99".parse().unwrap() };
+/// ```
+///
+/// ## Note
+/// This requires the `decimal` feature to be enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(pub rust_decimal::Decimal);
+
+impl Decimal {
+    /// Take the inner value out of the wrapper
+    pub fn into_inner(self) -> rust_decimal::Decimal {
+        self.0
+    }
+}
+
+impl Deref for Decimal {
+    type Target = rust_decimal::Decimal;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Decimal {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<rust_decimal::Decimal> for Decimal {
+    fn from(value: rust_decimal::Decimal) -> Self {
+        Decimal(value)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        rust_decimal::Decimal::from_str_exact(s).map(Decimal)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl SqliteTyped for Decimal {
+    const SQLITE_TYPE: SqliteType = SqliteType::Text;
+}
+
+impl ToSql for Decimal {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0.to_string()))
+    }
+}
+
+impl FromSql for Decimal {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Text(_) => value.as_str()?
+                .parse()
+                .map_err(|e| FromSqlError::Other(Box::new(e))),
+            ValueRef::Integer(i) => Ok(Decimal(i.into())),
+            ValueRef::Real(f) => rust_decimal::Decimal::try_from(f)
+                .map(Decimal)
+                .map_err(|e| FromSqlError::Other(Box::new(e))),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+// Exact sum of decimal text values, NULL for no rows like SUM
+struct DecimalSum;
+
+impl Aggregate<Option<rust_decimal::Decimal>, Option<String>> for DecimalSum {
+    fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<Option<rust_decimal::Decimal>> {
+        Ok(None)
+    }
+
+    fn step(&self, ctx: &mut Context<'_>, sum: &mut Option<rust_decimal::Decimal>) -> rusqlite::Result<()> {
+        if let Some(value) = ctx.get::<Option<Decimal>>(0)? {
+            let total = sum.unwrap_or_default().checked_add(value.0)
+                .ok_or_else(|| rusqlite::Error::UserFunctionError("decimal_sum overflow".into()))?;
+            *sum = Some(total);
+        }
+        Ok(())
+    }
+
+    fn finalize(&self, _: &mut Context<'_>, sum: Option<Option<rust_decimal::Decimal>>) -> rusqlite::Result<Option<String>> {
+        Ok(sum.flatten().map(|sum| sum.to_string()))
+    }
+}
+
+pub(crate) fn register_functions(connection: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
+    connection.create_aggregate_function("decimal_sum", 1, FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC, DecimalSum)
+}
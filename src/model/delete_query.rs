@@ -1,12 +1,18 @@
 use std::marker::PhantomData;
 
-use crate::{connection::{RawQuery, Queryable, Executable}, IntoSqlite};
+use crate::connection::{RawQuery, Queryable, Executable, qualified_table_name, substitute_params};
 
-use super::{Model, query::{ModelQueryFilter, ColumnQueryOrder}};
+use super::{Model, WritableModel, query::{ModelQueryFilter, SharedFilter, QueryOrder, append_order, combine_filters, debug_assert_filter_tables, limit_offset, soft_delete_condition, where_clause}};
 
 /// A query that deletes rows from a table.
 pub struct ModelDeleteQuery<M: Model> {
+    // Everything that goes after the WHERE clause (ORDER BY, ...)
     query: RawQuery,
+    filter: Option<RawQuery>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    // Actually delete rows of soft deleted models
+    permanent: bool,
     model: PhantomData<M>,
 }
 
@@ -19,7 +25,11 @@ impl<M: Model> Default for ModelDeleteQuery<M> {
 impl<M: Model> ModelDeleteQuery<M> {
     pub fn new() -> Self {
         ModelDeleteQuery {
-            query: RawQuery::new(format!("DELETE FROM {}", M::table_name()), Vec::new()),
+            query: RawQuery::new(String::new(), Vec::new()),
+            filter: None,
+            limit: None,
+            offset: None,
+            permanent: false,
             model: PhantomData,
         }
     }
@@ -29,7 +39,7 @@ impl<M: Model> ModelDeleteQuery<M> {
         params_old.extend(other.params);
         ModelDeleteQuery {
             query: RawQuery::new(format!("{} {}", self.query.sql, other.sql), params_old),
-            model: PhantomData,
+            ..self
         }
     }
 
@@ -45,10 +55,43 @@ impl<M: Model> ModelDeleteQuery<M> {
     /// ```rs
     /// User::delete().filter(User::id.eq(1)).exec(&conn).unwrap();
     /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times will combine the filters with AND.
     pub fn filter(self, mut filter: impl ModelQueryFilter) -> Self {
-        let mut filter_query = filter.get_query();
-        filter_query.sql = format!("WHERE {}", filter_query.sql);
-        ModelDeleteQuery::combine(self, filter_query)
+        debug_assert_filter_tables(&filter, M::table_name(), &[]);
+        let filter_query = filter.get_query();
+        ModelDeleteQuery {
+            filter: Some(combine_filters(self.filter, filter_query)),
+            ..self
+        }
+    }
+
+    /// Delete the rows even if the model has a `#[soft_delete]` column, instead of setting it.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::delete().permanent().filter(User::deleted_at.lt(cutoff)).exec(&conn).unwrap();
+    /// ```
+    pub fn permanent(self) -> Self {
+        ModelDeleteQuery {
+            permanent: true,
+            ..self
+        }
+    }
+
+    /// Filter the query with a reusable filter
+    /// 
+    /// ## Arguments
+    /// * `filter` - The shared filter to apply, it is cloned so it can be applied again
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let inactive = SharedFilter::new(User::last_login.lt(cutoff)).unwrap();
+    /// User::delete().apply_filter(&inactive).exec(&conn).unwrap();
+    /// ```
+    pub fn apply_filter(self, filter: &SharedFilter) -> Self {
+        self.filter(filter.clone())
     }
 
     // Limit and offset
@@ -66,7 +109,10 @@ impl<M: Model> ModelDeleteQuery<M> {
     /// User::delete().limit(1).exec(&conn).unwrap();
     /// ```
     pub fn limit(self, limit: u32) -> Self {
-        self.combine(RawQuery::new("LIMIT ?".to_string(), vec![Box::new(limit)]))
+        ModelDeleteQuery {
+            limit: Some(limit),
+            ..self
+        }
     }
 
     /// Offset the number of rows returned by the query.
@@ -83,7 +129,10 @@ impl<M: Model> ModelDeleteQuery<M> {
     /// User::delete().offset(1).exec(&conn).unwrap();
     /// ```
     pub fn offset(self, offset: u32) -> Self {
-        self.combine(RawQuery::new("OFFSET ?".to_string(), vec![Box::new(offset)]))
+        ModelDeleteQuery {
+            offset: Some(offset),
+            ..self
+        }
     }
 
     // Order
@@ -99,18 +148,97 @@ impl<M: Model> ModelDeleteQuery<M> {
     /// ## Example
     /// ```rs
     /// User::delete().order_by(User::id.asc()).exec(&conn).unwrap();
-    pub fn order_by(self, order: ColumnQueryOrder) -> Self {
-        self.combine(RawQuery::new(format!("ORDER BY {}", order.into_sqlite()), Vec::new()))
+    pub fn order_by(self, order: impl QueryOrder) -> Self {
+        let sql = append_order(&self.query.sql, &order.order_sql());
+        ModelDeleteQuery {
+            query: RawQuery::new(sql, self.query.params),
+            ..self
+        }
     }
 }
 
-impl<M: Model> Queryable<()> for ModelDeleteQuery<M> {
+impl<M: Model + WritableModel> ModelDeleteQuery<M> {
+    /// Build the SQL of the query (with `?` in place of parameters)
+    fn sql(&self) -> String {
+        let table = qualified_table_name(M::table_name());
+        let sql = match M::soft_delete_column() {
+            // Soft deleted models only mark rows which are not deleted yet
+            Some(column) if !self.permanent => format!(
+                "UPDATE {} SET {} = CURRENT_TIMESTAMP{}",
+                table, column.name(), where_clause(soft_delete_condition::<M>().as_deref(), self.filter.as_ref())
+            ),
+            _ => format!("DELETE FROM {}{}", table, where_clause(None, self.filter.as_ref())),
+        };
+        format!("{}{}{}", sql, self.query.sql, limit_offset(self.limit, self.offset).sql)
+    }
+
+    /// Get the SQL of the query, with `?` in place of parameters.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let query = User::delete().filter(User::age.lt(18)).limit(10);
+    /// assert_eq!(query.to_sql(), "DELETE FROM users WHERE users.age < ? LIMIT ?");
+    /// assert_eq!(query.parameter_count(), 2);
+    /// ```
+    pub fn to_sql(&self) -> String {
+        self.sql()
+    }
+
+    /// Get the SQL of the query together with its bound parameters.
+    /// 
+    /// This consumes the query, as its parameters are moved into the returned [RawQuery](crate::connection::RawQuery).
+    /// Use [to_sql](Self::to_sql) to only inspect the SQL of a query that is executed later.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let raw = User::delete().filter(User::age.lt(18)).limit(10).to_raw();
+    /// assert_eq!(raw.sql, "DELETE FROM users WHERE users.age < ? LIMIT ?");
+    /// assert_eq!(raw.params.len(), 2);
+    /// ```
+    pub fn to_raw(mut self) -> RawQuery {
+        self.get_query()
+    }
+
+    /// Get the number of parameters bound to the query (the number of `?` in [to_sql](Self::to_sql)).
+    pub fn parameter_count(&self) -> usize {
+        let limits = limit_offset(self.limit, self.offset);
+        self.filter.iter()
+            .chain([&self.query, &limits])
+            .map(|query| query.params.len())
+            .sum()
+    }
+
+    /// Get the SQL of the query with parameters substituted, eg. for logging.
+    /// 
+    /// Text is quoted and escaped, and blobs are hex encoded, so the result can be copied and executed as it is.
+    /// This is only meant for debugging, always use parameters when executing queries.
+    pub fn debug_sql(&self) -> String {
+        let limits = limit_offset(self.limit, self.offset);
+        let params = self.filter.iter()
+            .chain([&self.query, &limits])
+            .flat_map(|query| query.params.iter().map(|p| p.as_ref()))
+            .collect::<Vec<&dyn rusqlite::ToSql>>();
+        substitute_params(&self.sql(), &params)
+    }
+}
+
+impl<M: Model + WritableModel> Queryable<()> for ModelDeleteQuery<M> {
     fn get_query(&mut self) -> RawQuery {
-        self.query.move_clone()
+        let sql = self.sql();
+
+        let mut params = Vec::new();
+        if let Some(filter) = &mut self.filter {
+            params.append(&mut filter.params);
+        }
+        params.append(&mut self.query.params);
+        params.append(&mut limit_offset(self.limit, self.offset).params);
+
+        RawQuery::new(sql, params)
     }
 
-    fn parse_result(&mut self, _rows: rusqlite::Rows) {
+    fn parse_result(&mut self, _rows: rusqlite::Rows) -> Result<(), rusqlite::Error> {
         // Nothing to parse
+        Ok(())
     }
 
     fn should_execute(&self) -> bool {
@@ -118,7 +246,7 @@ impl<M: Model> Queryable<()> for ModelDeleteQuery<M> {
     }
 }
 
-impl<M: Model> Executable<usize> for ModelDeleteQuery<M> {
+impl<M: Model + WritableModel> Executable<usize> for ModelDeleteQuery<M> {
     fn exec(self, conn: &crate::prelude::Connection) -> Result<usize, rusqlite::Error> {
         conn.exec(self)
     }
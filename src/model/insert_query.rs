@@ -0,0 +1,100 @@
+use rusqlite::ToSql;
+
+use crate::{connection::{RawQuery, Queryable, Executable, Connection, qualified_table_name}, sql_types::SqliteFlag};
+
+use super::{Model, WritableModel, Column};
+
+/// Query that inserts a single row with explicitly set columns.
+/// 
+/// Columns which are not set are left to the database, except for NOT NULL columns with a `default_value`,
+/// which are filled with the model's default so that the result is the same as when inserting the whole model.
+pub struct ModelInsertBuilder<M: Model> {
+    columns: Vec<Column<'static>>,
+    values: Vec<Box<dyn ToSql>>,
+    marker: std::marker::PhantomData<M>,
+}
+
+impl<M: Model> Default for ModelInsertBuilder<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: Model> ModelInsertBuilder<M> {
+    pub fn new() -> Self {
+        ModelInsertBuilder {
+            columns: Vec::new(),
+            values: Vec::new(),
+            marker: Default::default(),
+        }
+    }
+
+    /// Set the value of a column in the inserted row.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to set the value of
+    /// * `value` - The value to insert
+    /// 
+    /// ## Returns
+    /// A new query with the value set (replacing the previous value of the column).
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let id = User::insert_builder()
+    ///     .set(User::name, "John")
+    ///     .exec(&conn).unwrap();
+    /// ```
+    pub fn set<V: ToSql + 'static>(mut self, column: Column<'static>, value: V) -> Self {
+        match self.columns.iter().position(|c| c.name() == column.name()) {
+            Some(i) => self.values[i] = Box::new(value),
+            None => {
+                self.columns.push(column);
+                self.values.push(Box::new(value));
+            }
+        }
+        self
+    }
+}
+
+impl<M: Model + WritableModel> Queryable<()> for ModelInsertBuilder<M> {
+    fn get_query(&mut self) -> RawQuery {
+        let mut columns = self.columns.iter().map(|c| c.name()).collect::<Vec<_>>();
+        let mut values = vec!["?".to_string(); columns.len()];
+
+        // Fill unset NOT NULL columns with the model's defaults
+        for column in M::columns().iter() {
+            if columns.contains(&column.name()) || column.is_generated() || !column.has_flag(SqliteFlag::NotNull) {
+                continue;
+            }
+            if let Some(default) = column.default_sql() {
+                columns.push(column.name());
+                values.push(default);
+            }
+        }
+
+        let table = qualified_table_name(M::table_name());
+        let sql = if columns.is_empty() {
+            format!("INSERT INTO {} DEFAULT VALUES", table)
+        } else {
+            format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), values.join(", "))
+        };
+        RawQuery::new(sql, std::mem::take(&mut self.values))
+    }
+
+    fn parse_result(&mut self, _rows: rusqlite::Rows) -> Result<(), rusqlite::Error> {
+        // Nothing to parse
+        Ok(())
+    }
+
+    fn should_execute(&self) -> bool {
+        true
+    }
+}
+
+impl<M: Model + WritableModel> Executable<i64> for ModelInsertBuilder<M> {
+    /// Insert the row and return its id.
+    fn exec(self, conn: &Connection) -> Result<i64, rusqlite::Error> {
+        conn.exec(self)?;
+        Ok(conn.connection.last_insert_rowid())
+    }
+}
@@ -0,0 +1,79 @@
+use std::ops::{Deref, DerefMut};
+
+use rusqlite::{ToSql, types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef}};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::sql_types::{SqliteType, SqliteTyped};
+
+/// A column that stores its value as JSON text.
+/// 
+/// ## Example use
+/// ```rust
+/// use sequelite::prelude::*;
+/// use serde::{Serialize, Deserialize};
+/// 
+/// #[derive(Debug, Clone, Serialize, Deserialize)]
+/// struct UserSettings {
+///     theme: String,
+///     notifications: bool,
+/// }
+/// 
+/// #[derive(Debug, Model)]
+/// struct User {
+///     id: Option<i32>,
+///     settings: Json<UserSettings>,
+/// }
+/// ```
+/// 
+/// ## Note
+/// This requires the `serde` feature to be enabled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    /// Take the inner value out of the wrapper
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Json<T> {
+    fn from(value: T) -> Self {
+        Json(value)
+    }
+}
+
+impl<T> SqliteTyped for Json<T> {
+    const SQLITE_TYPE: SqliteType = SqliteType::Text;
+}
+
+impl<T: Serialize> ToSql for Json<T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let json = serde_json::to_string(&self.0)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        Ok(ToSqlOutput::from(json))
+    }
+}
+
+impl<T: DeserializeOwned> FromSql for Json<T> {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let json = value.as_str()?;
+        serde_json::from_str(json)
+            .map(Json)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
@@ -5,128 +5,540 @@ use crate::{connection::Connection, IntoSqlite};
 
 use super::{Model, column::Column};
 
+/// Name of the table which records the applied [MigrationStep]s.
+/// It is not part of any schema, so the migrator never drops it.
+pub const MIGRATIONS_TABLE: &str = "_sequelite_migrations";
+
 /// Migrator ensures that the database is up to date with the latest schema.
 /// 
 /// This is done by comparing the latest schema with the current schema and updating the database as needed.
-/// There is no rollback support yet, so if the migration fails, the database will be in an inconsistent state.
+/// The whole migration runs in a transaction, so if it fails, the database is left unchanged.
 pub struct Migrator;
 
 impl Migrator {
     /// Migrate the database to the latest schema.
-    pub fn migrate(latest_schema: &DbSchema, connection: &Connection) {
+    /// 
+    /// ## Returns
+    /// An error if the migration failed or if the [MigrationPolicy](MigrationPolicy) refused to perform destructive operations.
+    pub fn migrate(latest_schema: &DbSchema, connection: &Connection) -> Result<MigrationReport, MigrationError> {
+        if connection.migration_policy() == MigrationPolicy::Safe {
+            let operations = Self::destructive_operations(latest_schema, connection)?;
+            if !operations.is_empty() {
+                return Err(MigrationError::Destructive(operations));
+            }
+        }
+
+        // Tables are dropped and recreated while migrating, which would trigger foreign key actions
+        // (eg. cascade deletes), so enforcement is disabled for the duration of the migration.
+        // This has to be done outside of the transaction, as the pragma is a no-op inside of one.
+        let foreign_keys: bool = connection.connection
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+        if foreign_keys {
+            connection.execute_no_params("PRAGMA foreign_keys = OFF")?;
+        }
+
         // Compare the latest schema with the current schema updating the database as needed.
-        Self::migrate_models(latest_schema, connection);
+        let result = connection.connection.unchecked_transaction()
+            .map_err(MigrationError::from)
+            .and_then(|transaction| {
+                let mut report = MigrationReport::default();
+                Self::migrate_models(latest_schema, connection, &mut report)?;
+                transaction.commit()?;
+                Ok(report)
+            });
+
+        if foreign_keys {
+            connection.execute_no_params("PRAGMA foreign_keys = ON")?;
+        }
+        result
     }
 
-    #[allow(unreachable_code)]
-    pub fn migrate_models(latest_schema: &DbSchema, connection: &Connection) {
+    /// Run every step with a version above the current database version, up to and including `version`.
+    /// Each step runs in its own transaction together with recording its version, so a failing step
+    /// leaves the database at the previous version.
+    pub fn migrate_to(version: u32, steps: &[MigrationStep], connection: &Connection) -> Result<MigrationReport, MigrationError> {
+        connection.execute_no_params(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (version INTEGER PRIMARY KEY NOT NULL, applied_at DATETIME DEFAULT CURRENT_TIMESTAMP)",
+            MIGRATIONS_TABLE
+        ))?;
+        let current = Self::current_version(connection)?;
+
+        let mut pending = steps.iter()
+            .filter(|step| step.version > current && step.version <= version)
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|step| step.version);
+
+        let mut report = MigrationReport::default();
+        for step in pending {
+            let transaction = connection.connection.unchecked_transaction()?;
+            (step.run)(connection)?;
+            connection.connection.execute(&format!("INSERT INTO {} (version) VALUES (?)", MIGRATIONS_TABLE), [step.version])?;
+            transaction.commit()?;
+
+            warn!(target: "migration", "Applied migration {}.", step.version);
+            report.operations.push(format!("apply migration {}", step.version));
+        }
+        Ok(report)
+    }
+
+    /// Get the version of the last applied [MigrationStep], or 0 if none were applied.
+    pub fn current_version(connection: &Connection) -> Result<u32, rusqlite::Error> {
+        let exists: bool = connection.connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type='table' AND name=?)",
+            [MIGRATIONS_TABLE],
+            |row| row.get(0)
+        )?;
+        if !exists {
+            return Ok(0);
+        }
+        connection.connection.query_row(
+            &format!("SELECT COALESCE(MAX(version), 0) FROM {}", MIGRATIONS_TABLE),
+            [],
+            |row| row.get(0)
+        )
+    }
+
+    /// List operations which would lose data (dropping tables and columns that are not in the latest schema).
+    fn destructive_operations(latest_schema: &DbSchema, connection: &Connection) -> Result<Vec<String>, rusqlite::Error> {
+        let mut operations = Vec::new();
+        for table in connection.get_all_tables()?.iter() {
+            match latest_schema.tables.get(table) {
+                Some(latest_columns) => {
+                    let columns = connection.get_all_columns(table)?;
+                    let renames = pending_renames(latest_columns, &columns);
+                    for column in columns.iter() {
+                        // Renamed columns keep their data
+                        if renames.iter().any(|(old_name, _)| *old_name == column.name()) {
+                            continue;
+                        }
+                        if !latest_columns.iter().any(|c| c.name() == column.name()) {
+                            operations.push(format!("drop column {} from table {}", column.name(), table));
+                        }
+                    }
+                },
+                None => operations.push(format!("drop table {}", table)),
+            }
+        }
+        Ok(operations)
+    }
+
+    pub fn migrate_models(latest_schema: &DbSchema, connection: &Connection, report: &mut MigrationReport) -> Result<(), MigrationError> {
+        for operation in Self::plan_operations(latest_schema, connection)? {
+            for statement in operation.statements.iter() {
+                connection.execute_no_params(statement)?;
+                debug!(target: "query_internal", "Migrated using: {}", statement);
+            }
+
+            warn!(target: "migration", "Migration: {}.", operation.description);
+            report.operations.push(operation.description);
+        }
+        Ok(())
+    }
+
+    /// List the SQL statements a migration would execute, without changing the database.
+    /// 
+    /// ## Returns
+    /// The statements in the order they would be executed. This does not check the [MigrationPolicy](MigrationPolicy).
+    pub fn plan(latest_schema: &DbSchema, connection: &Connection) -> Result<Vec<String>, rusqlite::Error> {
+        Ok(Self::plan_operations(latest_schema, connection)?
+            .into_iter()
+            .flat_map(|operation| operation.statements)
+            .collect())
+    }
+
+    fn plan_operations(latest_schema: &DbSchema, connection: &Connection) -> Result<Vec<PlannedOperation>, rusqlite::Error> {
         // Iterate over the tables in database and compare them to the latest schema.
         // If the table is not in the latest schema, drop it.
         // If the table is in the latest schema, compare the columns.
         // If the column is not in the database, add it.
-        
-        let tables = connection.get_all_tables().unwrap();
+        let mut operations = Vec::new();
+        let tables = connection.get_all_tables()?;
 
         for table in tables.iter() {
-            if latest_schema.tables.get(&table.clone()).is_some() {
-                // The table is in the latest schema, compare the columns.
-                let columns = connection.get_all_columns(table).unwrap();
-                
-                // Remove columns that are not in the latest schema.
-                for column in columns.iter() {
-                    if !latest_schema.tables.get(&table.clone()).unwrap().iter().any(|c| c.name() == column.name()) {
-                        // The column is not in the latest schema, drop it.
-                        // safety note: this is safe because the column name is checked against the latest schema.
-                        connection.execute_no_params(&format!(
-                            "ALTER TABLE {} DROP COLUMN {};",
-                            table, column.name()
-                        )).unwrap();
-
-                        warn!(target: "migration", "Dropped column {} from table {}.", column.name(), table);
-                    }
-                }
-
-                for latest_column in latest_schema.tables.get(&table.clone()).unwrap().iter() {
-                    // Change existing columns.
-                    if !columns.iter().any(|c| c.name() == latest_column.name()) {
-                        // The column is not in the latest schema, add it without modifying the data.
-                        // safety note: this is safe because the column name is checked against the latest schema.
-                        connection.execute_no_params(&format!(
-                            "ALTER TABLE {} ADD COLUMN {};",
-                            table, latest_column.into_sqlite()
-                        )).unwrap();
-
-                        warn!(target: "migration", "Added column {} to table {} without migrating data.", latest_column.name(), table);
-                    }
+            let latest_columns = match latest_schema.tables.get(table) {
+                Some(latest_columns) => latest_columns,
+                None => {
+                    // The table is not in the latest schema, drop it.
+                    operations.push(PlannedOperation {
+                        description: format!("drop table {}", table),
+                        statements: vec![format!("DROP TABLE IF EXISTS {}", table)],
+                    });
+                    continue;
                 }
-                let columns = connection.get_all_columns(table).unwrap();
+            };
+            let columns = connection.get_all_columns(table)?;
 
-                for latest_column in latest_schema.tables.get(&table.clone()).unwrap().iter() {
-                    let column = columns.iter().find(|c| c.name() == latest_column.name()).unwrap();
+            // Rename columns marked with renamed_from before anything is dropped, to keep their data.
+            let renames = pending_renames(latest_columns, &columns);
+            for (old_name, new_name) in renames.iter() {
+                operations.push(PlannedOperation {
+                    description: format!("rename column {} to {} in table {}", old_name, new_name, table),
+                    statements: vec![format!("ALTER TABLE {} RENAME COLUMN {} TO {};", table, old_name, new_name)],
+                });
+            }
+            // Name of every database column after the renames
+            let renamed = |column: &Column| renames.iter()
+                .find(|(old_name, _)| *old_name == column.name())
+                .map(|(_, new_name)| new_name.clone())
+                .unwrap_or_else(|| column.name());
 
-                    // The column is in the latest schema, compare the types.
-                    // TODO: Default value
-                    if column.ty != latest_column.ty || !column.same_flags(latest_column) {
-                        // The column type is not the same, use alter table to change it.
-                        // safety note: this is safe because the column name is checked against the latest schema.
-                        replace_table_full(connection, table, latest_schema.tables.get(&table.clone()).unwrap());
+            // Remove columns that are not in the latest schema.
+            // safety note: this is safe because the column names are checked against the latest schema.
+            for column in columns.iter() {
+                if !latest_columns.iter().any(|c| c.name() == renamed(column)) {
+                    operations.push(PlannedOperation {
+                        description: format!("drop column {} from table {}", column.name(), table),
+                        statements: vec![format!("ALTER TABLE {} DROP COLUMN {};", table, column.name())],
+                    });
+                }
+            }
 
-                        warn!(target: "migration", "Migrated whole table while altering column {} in table {} from '{}' to '{}'.", column.name(), table, column.ty.into_sqlite(), latest_column.ty.into_sqlite());
-                        break; // The table has been replaced, no need to continue.
-                    }
+            // Add columns that are not in the database without modifying the data.
+            // Stored generated columns can't be added, so the table is rebuilt instead.
+            for latest_column in latest_columns.iter().filter(|c| !c.is_generated()) {
+                if !columns.iter().any(|c| renamed(c) == latest_column.name()) {
+                    operations.push(PlannedOperation {
+                        description: format!("add column {} to table {}", latest_column.name(), table),
+                        statements: vec![format!("ALTER TABLE {} ADD COLUMN {};", table, latest_column.into_sqlite())],
+                    });
                 }
+            }
 
-            } else {
-                // The table is not in the latest schema, drop it.
-                connection.execute_no_params(&format!("DROP TABLE IF EXISTS {}", table)).unwrap();
+            // Compare the types, flags and defaults of existing columns, a change requires rebuilding the whole table.
+            // Changes of a generation expression are not detected, as it can't be read back from the database.
+            let changed = latest_columns.iter().any(|latest_column| {
+                columns.iter()
+                    .find(|c| renamed(c) == latest_column.name())
+                    .map(|column| column.ty != latest_column.ty
+                        || !column.same_flags(latest_column)
+                        || !column.same_default(latest_column)
+                        || column.is_generated() != latest_column.is_generated())
+                    .unwrap_or(latest_column.is_generated())
+            });
+            // CHECK constraints and collations can't be read back from PRAGMA table_info, so they are looked up in the table definition.
+            let table_sql = connection.query_scalar::<String>("SELECT sql FROM sqlite_master WHERE type='table' AND name=?", &[table])?
+                .unwrap_or_default();
+            let definitions = column_definitions(&table_sql);
+            let constraints_changed = columns.iter().any(|column| {
+                let latest_column = latest_columns.iter().find(|c| c.name() == renamed(column));
+                match (latest_column, definitions.get(&column.name().to_lowercase())) {
+                    (Some(latest_column), Some(definition)) => !definition.matches(latest_column),
+                    _ => false,
+                }
+            });
 
-                warn!(target: "migration", "Dropped table {}.", table);
+            if changed || constraints_changed {
+                operations.push(PlannedOperation {
+                    description: format!("rebuild table {}", table),
+                    statements: rebuild_table_statements(table, latest_columns),
+                });
             }
         }
 
         // Create any tables that are in the latest schema but not in the database.
         for (table, columns) in latest_schema.tables.iter() {
             if !tables.contains(table) {
-                // The table is not in the database, create it.
-                let mut sql = format!("CREATE TABLE {} (", table);
-                for column in columns.iter() {
-                    sql.push_str(&format!("{},", column.into_sqlite()));
+                operations.push(PlannedOperation {
+                    description: format!("create table {}", table),
+                    statements: vec![create_table_statement(table, columns)],
+                });
+            }
+        }
+        Ok(operations)
+    }
+}
+
+/// A single migration operation and the statements performing it.
+struct PlannedOperation {
+    description: String,
+    statements: Vec<String>,
+}
+
+/// Constraints of a column which can't be read back from `PRAGMA table_info`.
+#[derive(Debug, Default, PartialEq)]
+struct ColumnDefinition {
+    check: Option<String>,
+    collation: Option<String>,
+}
+
+impl ColumnDefinition {
+    /// Check if the column of the latest schema has the same constraints
+    fn matches(&self, column: &Column) -> bool {
+        let same_collation = match (self.collation.as_deref(), column.get_collation()) {
+            (Some(collation), Some(latest)) => collation.eq_ignore_ascii_case(latest),
+            (collation, latest) => collation.is_none() && latest.is_none(),
+        };
+        same_collation && self.check.as_deref() == column.get_check().map(str::trim)
+    }
+}
+
+/// A token of a table definition, only as detailed as needed to find the constraints of columns.
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    // A keyword or an identifier
+    Word(&'a str),
+    // An identifier in quotes, it is never a keyword
+    Quoted(&'a str),
+    // Everything between matching parentheses
+    Group(&'a str),
+    Comma,
+    // String literals, numbers and operators
+    Other,
+}
+
+fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    // Index right after the closing quote of a literal starting at `start`, a doubled quote is escaped
+    let skip_quoted = |start: usize, close: u8| {
+        let mut end = start + 1;
+        while end < bytes.len() {
+            if bytes[end] == close {
+                if bytes.get(end + 1) != Some(&close) || close == b']' {
+                    return end + 1;
                 }
-                sql.pop();
-                sql.push(')');
-                connection.execute_no_params(&sql).unwrap();
+                end += 1;
+            }
+            end += 1;
+        }
+        bytes.len()
+    };
 
-                debug!(target: "query_internal", "Created table using: {}", sql);
+    while i < bytes.len() {
+        match bytes[i] {
+            b',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            },
+            b'(' => {
+                let mut depth = 0;
+                let mut end = i;
+                while end < bytes.len() {
+                    match bytes[end] {
+                        b'(' => depth += 1,
+                        b')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        },
+                        quote @ (b'\'' | b'"' | b'`') => {
+                            end = skip_quoted(end, quote);
+                            continue;
+                        },
+                        b'[' => {
+                            end = skip_quoted(end, b']');
+                            continue;
+                        },
+                        _ => {}
+                    }
+                    end += 1;
+                }
+                tokens.push(Token::Group(&sql[i + 1..end.min(bytes.len())]));
+                i = end + 1;
+            },
+            b'\'' => {
+                i = skip_quoted(i, b'\'');
+                tokens.push(Token::Other);
+            },
+            quote @ (b'"' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                let end = skip_quoted(i, close);
+                tokens.push(Token::Quoted(&sql[i + 1..end.saturating_sub(1).max(i + 1)]));
+                i = end;
+            },
+            c if c.is_ascii_alphanumeric() || c == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token::Word(&sql[start..i]));
+            },
+            c if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                tokens.push(Token::Other);
+                i += 1;
+            },
+        }
+    }
+    tokens
+}
 
-                warn!(target: "migration", "Created table {} as it has not been found in current database.", table);
+/// Find the CHECK constraint and the collation of every column in a `CREATE TABLE` statement,
+/// by the lowercase name of the column. Table constraints (eg. `UNIQUE(a, b)`) are skipped.
+fn column_definitions(table_sql: &str) -> HashMap<String, ColumnDefinition> {
+    let body = match tokenize(table_sql).into_iter().find_map(|token| match token {
+        Token::Group(body) => Some(body),
+        _ => None,
+    }) {
+        Some(body) => body,
+        None => return HashMap::new(),
+    };
+
+    let tokens = tokenize(body);
+    let mut definitions = HashMap::new();
+    for definition in tokens.split(|token| *token == Token::Comma) {
+        let name = match definition.first() {
+            Some(Token::Word(word)) if ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"].iter().any(|keyword| word.eq_ignore_ascii_case(keyword)) => continue,
+            Some(Token::Word(name)) | Some(Token::Quoted(name)) => name.to_lowercase(),
+            _ => continue,
+        };
+
+        let mut column = ColumnDefinition::default();
+        for pair in definition.windows(2) {
+            match pair {
+                [Token::Word(keyword), Token::Group(expr)] if keyword.eq_ignore_ascii_case("CHECK") => column.check = Some(expr.trim().to_string()),
+                [Token::Word(keyword), Token::Word(collation) | Token::Quoted(collation)] if keyword.eq_ignore_ascii_case("COLLATE") => column.collation = Some(collation.to_string()),
+                _ => {}
             }
         }
+        definitions.insert(name, column);
     }
+    definitions
+}
+
+/// Find columns of the latest schema which were renamed from a column that still exists in the database.
+/// Returns pairs of (old name, new name). If the old column doesn't exist, the column is added as usual.
+fn pending_renames(latest_columns: &[Column], columns: &[Column]) -> Vec<(String, String)> {
+    latest_columns.iter()
+        .filter_map(|latest_column| {
+            let old_name = latest_column.get_renamed_from()?;
+            let has_old = columns.iter().any(|c| c.name() == old_name);
+            let has_new = columns.iter().any(|c| c.name() == latest_column.name());
+            if has_old && !has_new {
+                Some((old_name.to_string(), latest_column.name()))
+            } else {
+                None
+            }
+        })
+        .collect()
 }
 
-fn replace_table_full(connection: &Connection, table: &str, columns: &[Column]) {
-    let mut sql = format!("CREATE TABLE temp_{}_new (", table);
+fn create_table_statement(table: &str, columns: &[Column]) -> String {
+    let mut sql = format!("CREATE TABLE {} (", table);
     for column in columns.iter() {
         sql.push_str(&format!("{},", column.into_sqlite()));
     }
     sql.pop();
     sql.push(')');
-    connection.execute_no_params(&sql).unwrap();
+    sql
+}
+
+/// Statement creating the table unless it already exists, used when a model is created without migrating.
+pub(crate) fn create_table_if_not_exists_statement(table: &str, columns: &[Column]) -> String {
+    create_table_statement(table, columns).replacen("CREATE TABLE", "CREATE TABLE IF NOT EXISTS", 1)
+}
 
-    // Copy the data from the old table to the new table.
-    connection.execute_no_params(&format!(
-        "INSERT INTO temp_{}_new SELECT * FROM {};",
-        table, table
-    )).unwrap();
+/// Statements recreating the table with the given columns, keeping the data.
+/// Expects every column to already exist in the old table (the migration adds missing columns beforehand),
+/// except for generated columns, which are computed again.
+fn rebuild_table_statements(table: &str, columns: &[Column]) -> Vec<String> {
+    let temp_table = format!("temp_{}_new", table);
+    // Columns are listed explicitly, as their order may differ between the tables.
+    let column_names = columns.iter()
+        .filter(|c| !c.is_generated())
+        .map(|c| c.name())
+        .collect::<Vec<_>>()
+        .join(", ");
 
-    // Drop the old table.
-    connection.execute_no_params(&format!("DROP TABLE IF EXISTS {}", table)).unwrap();
+    vec![
+        create_table_statement(&temp_table, columns),
+        // Copy the data from the old table to the new table.
+        format!("INSERT INTO {} ({}) SELECT {} FROM {};", temp_table, column_names, column_names, table),
+        // Drop the old table.
+        format!("DROP TABLE IF EXISTS {}", table),
+        // Rename the new table to the old table.
+        format!("ALTER TABLE {} RENAME TO {};", temp_table, table),
+    ]
+}
 
-    // Rename the new table to the old table.
-    connection.execute_no_params(&format!(
-        "ALTER TABLE temp_{}_new RENAME TO {};",
-        table, table
-    )).unwrap();
+/// Operations applied by a migration, in the order they were performed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Human readable description of every operation, eg. `add column name to table users`.
+    pub operations: Vec<String>,
+}
+
+impl MigrationReport {
+    /// Returns true if the database was already up to date.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+/// An imperative migration which is executed once and recorded in the migrations table.
+/// 
+/// ## Example
+/// ```rs
+/// let steps = [
+///     MigrationStep::new(1, |conn| conn.exec_raw("UPDATE users SET name = trim(name)", &[]).map(|_| ())),
+/// ];
+/// conn.migrate_to(1, &steps).unwrap();
+/// ```
+pub struct MigrationStep {
+    version: u32,
+    run: Box<MigrationFn>,
+}
+
+type MigrationFn = dyn Fn(&Connection) -> Result<(), rusqlite::Error>;
+
+impl MigrationStep {
+    /// Creates a new migration step.
+    /// 
+    /// ## Arguments
+    /// * `version` - The version the database is at after this step, has to be unique and greater than 0.
+    /// * `run` - The function performing the migration.
+    pub fn new(version: u32, run: impl Fn(&Connection) -> Result<(), rusqlite::Error> + 'static) -> Self {
+        assert!(version > 0, "Migration versions have to be greater than 0");
+        MigrationStep {
+            version,
+            run: Box::new(run),
+        }
+    }
+
+    /// Get the version of this step.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+/// Decides which operations the migrator is allowed to perform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MigrationPolicy {
+    /// Refuse to drop tables or columns that are not in the latest schema.
+    /// The migration fails with [MigrationError::Destructive] and nothing is changed.
+    Safe,
+    /// Drop tables and columns that are not in the latest schema (the default).
+    #[default]
+    Destructive,
+}
+
+/// An error that occurred while migrating the database.
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The migration would lose data, but the [MigrationPolicy](MigrationPolicy) is `Safe`.
+    /// Contains a description of every refused operation.
+    Destructive(Vec<String>),
+    /// A query failed while migrating.
+    Sqlite(rusqlite::Error),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::Destructive(operations) => write!(f, "Refused destructive migration: {}", operations.join(", ")),
+            MigrationError::Sqlite(error) => write!(f, "Migration failed: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(error: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(error)
+    }
 }
 
 #[derive(Default)]
@@ -144,5 +556,8 @@ impl DbSchema<'_> {
 
     pub fn add_table<M: Model>(&mut self) {
         self.tables.insert(M::table_name().to_string(), M::columns());
+        for collection in M::collections() {
+            self.tables.insert(collection.table.to_string(), collection.columns);
+        }
     }
 }
\ No newline at end of file
@@ -1,20 +1,35 @@
-use crate::connection::Connection;
+use std::{collections::HashMap, hash::Hash};
+
+use rusqlite::types::FromSql;
+
+use crate::connection::{Connection, Executable, MAX_INSERT_PARAMS};
 
 use self::delete_query::ModelDeleteQuery;
-use self::query::CountQuery;
+use self::query::{CountQuery, ModelQueryFilter, ColumnQueryFilterImpl};
 use self::update_query::ModelUpdateQuery;
+use self::insert_query::ModelInsertBuilder;
 
 mod column;
 pub mod migrator;
 pub mod query;
 pub mod update_query;
+pub mod insert_query;
 pub mod delete_query;
 pub mod relation;
+pub mod collection;
+#[cfg(feature = "serde")]
+pub mod json;
+#[cfg(feature = "decimal")]
+pub mod decimal;
 
 pub use rusqlite::Row as SqliteRow;
 pub use rusqlite::Rows as SqliteRows;
 pub use rusqlite::ToSql as SqliteToSql;
 pub use column::Column;
+pub(crate) use column::DefaultExpression;
+
+/// The number of rows loaded per query by [ModelExt::stream_all](ModelExt::stream_all).
+pub const SYNC_STREAM_BATCH: u32 = 500;
 
 /// A trait that needs to be implemented for all models that are used with sequelite.
 /// 
@@ -29,14 +44,119 @@ pub trait Model {
     fn get_id(&self) -> i64;
     fn id_column() -> Column<'static>;
     fn column_value(&self, column: &'static Column<'static>) -> Option<Box<dyn rusqlite::types::ToSql>>;
-    fn parse_rows(rows: rusqlite::Rows, offset: usize, joins: &Vec<String>) -> Vec<Self>
+    fn parse_rows(rows: rusqlite::Rows, offset: usize, joins: &Vec<String>) -> Result<Vec<Self>, rusqlite::Error>
     where
         Self: Sized;
-    fn parse_row(row: &rusqlite::Row, offset: usize, joins: &Vec<String>) -> Self
+    fn parse_row(row: &rusqlite::Row, offset: usize, joins: &Vec<String>) -> Result<Self, rusqlite::Error>
     where
         Self: Sized;
+
+    /// The `deleted_at` column marked with `#[soft_delete]`, if the model is soft deleted.
+    fn soft_delete_column() -> Option<Column<'static>> {
+        None
+    }
+
+    /// Side tables storing the `Vec<T>` fields of the model.
+    fn collections() -> &'static [collection::ModelCollection] {
+        &[]
+    }
+
+    /// Values of the `Vec<T>` fields of the model, in the order of `collections()`.
+    fn collection_values(&self) -> Vec<collection::CollectionValues> {
+        Vec::new()
+    }
+
+    /// Load the `Vec<T>` fields of all of the models from their side tables, using one query per side table.
+    fn load_all_collections(_models: &mut [Self], _conn: &Connection) -> Result<(), rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
+    /// Load the `Vec<T>` fields of the model from their side tables.
+    fn load_collections(&mut self, conn: &Connection) -> Result<(), rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        Self::load_all_collections(std::slice::from_mut(self), conn)
+    }
+
+    /// Replace the values stored in side tables for the row with the given id.
+    /// 
+    /// This runs in a transaction (unless one is already open), so either all or none of the values are replaced.
+    fn save_collections(&self, id: i64, conn: &Connection) -> Result<(), rusqlite::Error> {
+        let transaction = if conn.connection.is_autocommit() {
+            Some(conn.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+        for values in self.collection_values() {
+            values.save(id, conn)?;
+        }
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a nullable column of a model from a row.
+/// 
+/// Columns which were not selected (eg. when using `ModelQuery::columns`) are either missing from the row
+/// or hold a different column, so they are read as `None`. Values which can't be converted are an error.
+/// 
+/// ## Note
+/// This is used by the generated `Model::parse_row` and should not be called manually.
+pub fn parse_optional_column<T: FromSql>(row: &rusqlite::Row, idx: usize, name: &str) -> Result<Option<T>, rusqlite::Error> {
+    match row.as_ref().column_name(idx) {
+        Ok(column) if column.eq_ignore_ascii_case(name) => row.get(idx),
+        _ => Ok(None),
+    }
+}
+
+/// A trait for types that can be created from a single row of a query result.
+/// 
+/// It is implemented for every [Model](Model), which is parsed from all of its columns,
+/// and for tuples of up to 8 [FromSql](rusqlite::types::FromSql) values, which are parsed from the columns in order.
+pub trait FromRow {
+    fn from_row(row: &rusqlite::Row) -> Result<Self, rusqlite::Error>
+    where
+        Self: Sized;
+}
+
+impl<M: Model> FromRow for M {
+    fn from_row(row: &rusqlite::Row) -> Result<Self, rusqlite::Error> {
+        M::parse_row(row, 0, &Vec::new())
+    }
 }
 
+// Tuples are parsed positionally, (A, B) reads columns 0 and 1
+macro_rules! impl_from_row_tuple {
+    ($($ty:ident $index:tt),+) => {
+        impl<$($ty: rusqlite::types::FromSql),+> FromRow for ($($ty,)+) {
+            fn from_row(row: &rusqlite::Row) -> Result<Self, rusqlite::Error> {
+                Ok(($(row.get::<_, $ty>($index)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_row_tuple!(A 0);
+impl_from_row_tuple!(A 0, B 1);
+impl_from_row_tuple!(A 0, B 1, C 2);
+impl_from_row_tuple!(A 0, B 1, C 2, D 3);
+impl_from_row_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_from_row_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_from_row_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_from_row_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// A marker trait for models that can be written to (inserted, updated and deleted).
+/// 
+/// It is automatically implemented for every struct that derives [Model](sequelite_macro::Model),
+/// unless the struct is marked with the `#[readonly]` attribute.
+pub trait WritableModel: Model {}
+
 /// A trait that extends the [Model](Model) trait with some useful methods.
 pub trait ModelExt<M: Model> {
     fn select() -> query::ModelQuery<M>
@@ -45,20 +165,67 @@ pub trait ModelExt<M: Model> {
 
     fn insert(self, conn: &Connection) -> Result<i64, rusqlite::Error> 
     where
-        Self: Sized;
+        Self: Sized + WritableModel;
 
     fn count() -> query::ModelQuery<CountQuery>
     where
         Self: Sized;
 
-    fn update() -> ModelUpdateQuery<M>
+    fn count_where<F: ModelQueryFilter>(filter: F, conn: &Connection) -> Result<usize, rusqlite::Error>
     where
         Self: Sized;
 
-    fn delete() -> ModelDeleteQuery<M>
+    fn count_distinct(column: Column<'static>) -> query::ModelQuery<CountQuery>
+    where
+        Self: Sized;
+
+    fn group_count<K: FromSql + Eq + Hash>(column: Column<'static>, conn: &Connection) -> Result<HashMap<K, i64>, rusqlite::Error>
     where
         Self: Sized;
 
+    fn find(conn: &Connection, id: i64) -> Result<Option<M>, rusqlite::Error>
+    where
+        Self: Sized;
+
+    fn stream_all<F: FnMut(M)>(conn: &Connection, f: F) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized;
+
+    fn insert_builder() -> ModelInsertBuilder<M>
+    where
+        Self: Sized + WritableModel;
+
+    fn update() -> ModelUpdateQuery<M>
+    where
+        Self: Sized + WritableModel;
+
+    fn delete() -> ModelDeleteQuery<M>
+    where
+        Self: Sized + WritableModel;
+
+    fn delete_by_id(conn: &Connection, id: i64) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel;
+
+    fn delete_by_ids(conn: &Connection, ids: &[i64]) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel;
+
+    fn truncate(conn: &Connection) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel;
+
+    fn get_or_create<F: ModelQueryFilter>(filter: F, default: M, conn: &Connection) -> Result<M, rusqlite::Error>
+    where
+        Self: Sized + WritableModel;
+
+    fn find_or_create<F: ModelQueryFilter>(filter: F, row: M, conn: &Connection) -> Result<M, rusqlite::Error>
+    where
+        Self: Sized + WritableModel;
+
+    fn related<C: Model>(&self, foreign_key: Column<'static>) -> query::ModelQuery<C>
+    where
+        Self: Sized;
 }
 
 impl<M: Model> ModelExt<M> for M {
@@ -70,10 +237,10 @@ impl<M: Model> ModelExt<M> for M {
         query::ModelQuery::select()
     }
 
-    /// Inserts the model into the database.
+    /// Inserts the model into the database (including the values of its `Vec<T>` fields).
     fn insert(self, conn: &Connection) -> Result<i64, rusqlite::Error> 
         where
-            Self: Sized {
+            Self: Sized + WritableModel {
         conn.insert(self)
     }
 
@@ -85,10 +252,103 @@ impl<M: Model> ModelExt<M> for M {
         query::ModelQuery::<M>::count()
     }
 
+    /// Counts rows matching the filter.
+    /// 
+    /// This is a shorthand for `Model::count().filter(filter).exec(conn)`.
+    fn count_where<F: ModelQueryFilter>(filter: F, conn: &Connection) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        query::ModelQuery::<M>::count().filter(filter).exec(conn)
+    }
+
+    /// Creates a new [ModelQuery](query::ModelQuery) that counts distinct non-NULL values of the column.
+    fn count_distinct(column: Column<'static>) -> query::ModelQuery<CountQuery>
+    where
+        Self: Sized,
+    {
+        query::ModelQuery::<M>::count_distinct(column)
+    }
+
+    /// Counts rows for every value of the column (`SELECT column, COUNT(*) ... GROUP BY column`).
+    /// 
+    /// This is a shorthand for `Model::select().group_count(column, conn)`.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let users_per_city: HashMap<String, i64> = User::group_count(User::city, &conn).unwrap();
+    /// ```
+    fn group_count<K: FromSql + Eq + Hash>(column: Column<'static>, conn: &Connection) -> Result<HashMap<K, i64>, rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        query::ModelQuery::<M>::select().group_count(column, conn)
+    }
+
+    /// Selects the row with the given id.
+    /// 
+    /// This is a shorthand for `Model::select().with_id(id).exec(conn)`.
+    /// 
+    /// ## Returns
+    /// The model, or `None` if there is no row with the given id.
+    fn find(conn: &Connection, id: i64) -> Result<Option<M>, rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        Ok(query::ModelQuery::<M>::select().with_id(id).exec(conn)?.into_iter().next())
+    }
+
+    /// Calls `f` for every row of the table, in primary key order.
+    /// 
+    /// Rows are loaded in batches of [SYNC_STREAM_BATCH](SYNC_STREAM_BATCH) using keyset pagination
+    /// (`WHERE id > last_id ORDER BY id LIMIT n`), so no statement is kept open between batches
+    /// and the whole table is never held in memory at once.
+    /// 
+    /// ## Returns
+    /// The number of rows passed to `f`.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::stream_all(&conn, |user| writer.write(&user)).unwrap();
+    /// ```
+    fn stream_all<F: FnMut(M)>(conn: &Connection, mut f: F) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized,
+    {
+        let mut last_id = None;
+        let mut total = 0;
+        loop {
+            let query = match last_id {
+                Some(last_id) => query::ModelQuery::<M>::select().after(M::id_column(), last_id),
+                None => query::ModelQuery::<M>::select().order_by(M::id_column().asc()),
+            }.limit(SYNC_STREAM_BATCH);
+
+            let batch = query.exec(conn)?;
+            let count = batch.len();
+            for model in batch {
+                last_id = Some(model.get_id());
+                f(model);
+            }
+            total += count;
+
+            if count < SYNC_STREAM_BATCH as usize {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Creates a new [ModelInsertBuilder](insert_query::ModelInsertBuilder) that inserts a row with only some of the columns set.
+    fn insert_builder() -> ModelInsertBuilder<M>
+    where
+        Self: Sized + WritableModel,
+    {
+        ModelInsertBuilder::new()
+    }
+
     /// Creates a new [ModelUpdateQuery](update_query::ModelUpdateQuery) that can be used to update rows in the database.
     fn update() -> ModelUpdateQuery<M>
     where
-        Self: Sized,
+        Self: Sized + WritableModel,
     {
         ModelUpdateQuery::new()
     }
@@ -96,8 +356,134 @@ impl<M: Model> ModelExt<M> for M {
     /// Creates a new [ModelDeleteQuery](delete_query::ModelDeleteQuery) that can be used to delete rows from the database.
     fn delete() -> ModelDeleteQuery<M>
     where
-        Self: Sized,
+        Self: Sized + WritableModel,
     {
         ModelDeleteQuery::new()
     }
+
+    /// Deletes the row with the given id.
+    /// 
+    /// This is a shorthand for `Model::delete().filter(Model::id.eq(id)).exec(conn)`.
+    /// 
+    /// ## Returns
+    /// The number of deleted rows (0 if there is no row with the given id).
+    fn delete_by_id(conn: &Connection, id: i64) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel,
+    {
+        ModelDeleteQuery::<M>::new().filter(M::id_column().eq(id)).exec(conn)
+    }
+
+    /// Deletes the rows with the given ids.
+    /// 
+    /// This is a shorthand for `Model::delete().filter(Model::id.in_(ids)).exec(conn)`,
+    /// long lists are split into multiple statements executed in a single transaction.
+    /// 
+    /// ## Returns
+    /// The number of deleted rows (ids without a row are ignored).
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let deleted = User::delete_by_ids(&conn, &[1, 2, 3]).unwrap();
+    /// ```
+    fn delete_by_ids(conn: &Connection, ids: &[i64]) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel,
+    {
+        let queries = ids.chunks(MAX_INSERT_PARAMS)
+            .map(|ids| ModelDeleteQuery::<M>::new().filter(M::id_column().in_(ids)).to_raw())
+            .collect::<Vec<_>>();
+        conn.run_all(&queries)
+    }
+
+    /// Deletes all rows and resets the id counter, so the next inserted row gets id 1.
+    /// 
+    /// This is a shorthand for `conn.truncate::<Model>()`.
+    fn truncate(conn: &Connection) -> Result<usize, rusqlite::Error>
+    where
+        Self: Sized + WritableModel,
+    {
+        conn.truncate::<M>()
+    }
+
+    /// Selects the first row matching the filter, or inserts `default` if there is none.
+    /// 
+    /// Outside of a transaction, this runs in an immediate transaction which locks the database for writing
+    /// before selecting, so no other connection can insert a matching row in between.
+    /// Inside of an existing transaction, the caller is responsible for that.
+    /// 
+    /// ## Arguments
+    /// * `filter` - The filter used to find an existing row
+    /// * `default` - The row to insert if no row matches the filter
+    /// * `conn` - The connection to use
+    /// 
+    /// ## Returns
+    /// The existing row, or the inserted row (read back from the database, so it includes the generated id and defaults).
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let tag = Tag::get_or_create(Tag::name.eq("rust"), Tag { id: None, name: "rust".to_string() }, &conn).unwrap();
+    /// ```
+    fn get_or_create<F: ModelQueryFilter>(filter: F, default: M, conn: &Connection) -> Result<M, rusqlite::Error>
+    where
+        Self: Sized + WritableModel,
+    {
+        let transaction = if conn.connection.is_autocommit() {
+            Some(rusqlite::Transaction::new_unchecked(&conn.connection, rusqlite::TransactionBehavior::Immediate)?)
+        } else {
+            None
+        };
+
+        let model = match query::ModelQuery::<M>::select().filter(filter).limit(1).exec(conn)?.pop() {
+            Some(model) => model,
+            None => {
+                let id = default.insert(conn)?;
+                M::find(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?
+            }
+        };
+
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(model)
+    }
+
+    /// Selects the first row matching the filter, or inserts `row` if there is none.
+    /// 
+    /// This is an alias for [get_or_create](ModelExt::get_or_create).
+    fn find_or_create<F: ModelQueryFilter>(filter: F, row: M, conn: &Connection) -> Result<M, rusqlite::Error>
+    where
+        Self: Sized + WritableModel,
+    {
+        M::get_or_create(filter, row, conn)
+    }
+
+    /// Creates a new [ModelQuery](query::ModelQuery) that selects all rows of another model
+    /// which point back at this model through the given relation column (one-to-many).
+    /// 
+    /// ## Arguments
+    /// * `foreign_key` - The relation column of the related model
+    /// 
+    /// ## Panics
+    /// If the column is not a relation to this model.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let posts = user.related::<Post>(Post::author).exec(&conn).unwrap();
+    /// ```
+    fn related<C: Model>(&self, foreign_key: Column<'static>) -> query::ModelQuery<C>
+    where
+        Self: Sized,
+    {
+        match foreign_key.get_relation() {
+            Some(relation) if relation.table == M::table_name() && relation.local_table == C::table_name() => {
+                // The foreign key holds the referenced column (the id unless #[references = "column"] is used)
+                match self.column_value(relation.foreign_key_column) {
+                    Some(value) => query::ModelQuery::<C>::select().filter(foreign_key.eq(value)),
+                    None => query::ModelQuery::<C>::select().filter(foreign_key.eq(self.get_id())),
+                }
+            },
+            _ => panic!("Column {} is not a relation from {} to {}", foreign_key.name(), C::table_name(), M::table_name()),
+        }
+    }
 }
\ No newline at end of file
@@ -1,14 +1,31 @@
-use std::{marker::PhantomData, fmt::Debug, ops::{BitAnd, BitOr}};
+use std::{marker::PhantomData, fmt::Debug, ops::{BitAnd, BitOr, Not}, collections::HashMap, hash::Hash};
 
-use rusqlite::ToSql;
+use log::debug;
+use rusqlite::{ToSql, types::{Value, FromSql}};
 
-use crate::{connection::{Queryable, RawQuery, IntoInsertable, Insertable, Executable}, IntoSqlite};
+use crate::{connection::{Queryable, RawQuery, IntoInsertable, Insertable, Executable, Connection, PreparedQuery, qualified_table_name, query_id, substitute_params}, IntoSqlite};
 
-use super::{Model, column::Column};
+use super::{Model, WritableModel, FromRow, column::Column, collection::CollectionValues};
 
 /// Just a marker type for count queries
 pub struct CountQuery;
 
+/// A marker type for queries which are parsed into `T` using [FromRow](super::FromRow) (see [select_as](ModelQuery::select_as))
+pub struct Projection<T>(PhantomData<T>);
+
+/// A page of results with the total number of rows (see [paginate](ModelQuery::paginate))
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<M> {
+    /// Rows on this page
+    pub items: Vec<M>,
+    /// Number of rows matching the query, on all pages
+    pub total: usize,
+    /// Number of this page, starting at 1
+    pub page: u32,
+    /// Number of pages needed to show all rows
+    pub total_pages: u32,
+}
+
 /// A trait for filtering queries
 /// 
 /// This allows you to filter, limit, offset, and order elements that you are querying.
@@ -24,15 +41,31 @@ pub struct CountQuery;
 pub struct ModelQuery<M> {
     model: PhantomData<M>,
     table_name: String,
-    query: String,
+    // The selected columns (all of them if empty), counted for count queries
+    columns: Vec<String>,
+    count: bool,
+    distinct: bool,
+    // FROM ... (with joins)
+    from: String,
     joins: Vec<String>,
-    params: Vec<Box<dyn ToSql>>,
+    joined_tables: Vec<String>,
+    // WHERE ...
+    filter: Option<RawQuery>,
+    // Condition hiding soft deleted rows, see `with_deleted`
+    soft_delete: Option<String>,
+    // GROUP BY ... HAVING ...
+    group_by: Vec<String>,
+    having: Option<RawQuery>,
+    // Everything that goes after the WHERE and GROUP BY clauses (ORDER BY, ...)
+    clauses: RawQuery,
+    limit: Option<u32>,
+    offset: Option<u32>,
 }
 
 impl<M: Model> Debug for ModelQuery<M> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ModelQuery")
-            .field("query", &self.query)
+            .field("query", &self.sql())
             .finish()
     }
 }
@@ -42,9 +75,19 @@ impl<M> Default for ModelQuery<M> {
         Self {
             model: Default::default(),
             table_name: "unknown".to_string(),
-            query: String::new(),
+            columns: Vec::new(),
+            count: false,
+            distinct: false,
+            from: String::new(),
             joins: Vec::new(),
-            params: Vec::new(),
+            joined_tables: Vec::new(),
+            filter: None,
+            soft_delete: None,
+            group_by: Vec::new(),
+            having: None,
+            clauses: RawQuery::new(String::new(), Vec::new()),
+            limit: None,
+            offset: None,
         }
     }
 }
@@ -53,21 +96,45 @@ impl<M> Default for ModelQuery<M> {
 impl<M: Model> ModelQuery<M> {
     // ====< Constructors >====
     pub fn select() -> Self {
-        let query = format!("SELECT * FROM {}", M::table_name());
         ModelQuery {
             model: PhantomData,
             table_name: M::table_name().to_string(),
-            query,
+            from: qualified_table_name(M::table_name()),
+            soft_delete: soft_delete_condition::<M>(),
             ..Default::default()
         }
     }
 
     pub fn count() -> ModelQuery<CountQuery> {
-        let query = format!("SELECT COUNT(*) FROM {}", M::table_name());
         ModelQuery {
             model: PhantomData,
             table_name: M::table_name().to_string(),
-            query,
+            count: true,
+            from: qualified_table_name(M::table_name()),
+            soft_delete: soft_delete_condition::<M>(),
+            ..Default::default()
+        }
+    }
+
+    /// Count distinct non-NULL values of the column
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let cities = ModelQuery::<User>::count_distinct(User::city).exec(&conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT COUNT(DISTINCT users.city) FROM users;
+    /// ```
+    pub fn count_distinct(column: Column<'static>) -> ModelQuery<CountQuery> {
+        ModelQuery {
+            model: PhantomData,
+            table_name: M::table_name().to_string(),
+            columns: vec![format!("{}.{}", column.table_name, column.name())],
+            count: true,
+            distinct: true,
+            from: qualified_table_name(M::table_name()),
+            soft_delete: soft_delete_condition::<M>(),
             ..Default::default()
         }
     }
@@ -77,16 +144,143 @@ impl<M: Model> ModelQuery<M> {
 impl<M> ModelQuery<M> {
 
     // ====< Utils >====
+    /// Append raw SQL to the end of the query (after the WHERE clause, but before LIMIT and OFFSET)
     pub fn combine(self, query: String, params: Vec<Box<dyn ToSql>>) -> Self {
-        let mut params_old = self.params;
+        let mut params_old = self.clauses.params;
         params_old.extend(params);
         ModelQuery {
-            model: PhantomData,
-            table_name: self.table_name,
-            query: format!("{} {}", self.query, query),
-            joins: self.joins,
-            params: params_old,
+            clauses: RawQuery::new(format!("{} {}", self.clauses.sql, query), params_old),
+            ..self
+        }
+    }
+
+    /// Add a condition to the WHERE clause, joining it with AND if there is already one
+    fn combine_filter(self, filter: RawQuery) -> Self {
+        ModelQuery {
+            filter: Some(combine_filters(self.filter, filter)),
+            ..self
+        }
+    }
+
+    /// Build the SQL of the query (with `?` in place of parameters)
+    fn sql(&self) -> String {
+        let mut sql = format!("SELECT {} FROM {}", self.select_list(), self.from);
+        sql.push_str(&where_clause(self.soft_delete.as_deref(), self.filter.as_ref()));
+        if !self.group_by.is_empty() {
+            sql = format!("{} GROUP BY {}", sql, self.group_by.join(", "));
+        }
+        if let Some(having) = &self.having {
+            sql = format!("{} HAVING {}", sql, having.sql);
+        }
+        format!("{}{}{}", sql, self.clauses.sql, limit_offset(self.limit, self.offset).sql)
+    }
+
+    /// Build the list of selected columns (what goes between SELECT and FROM)
+    fn select_list(&self) -> String {
+        let columns = if self.columns.is_empty() {
+            "*".to_string()
+        } else {
+            self.columns.join(", ")
+        };
+
+        match (self.count, self.distinct) {
+            // COUNT(DISTINCT *) is not valid, and counting distinct full rows makes no sense
+            (true, true) if !self.columns.is_empty() => format!("COUNT(DISTINCT {})", columns),
+            (true, _) => format!("COUNT({})", columns),
+            (false, true) => format!("DISTINCT {}", columns),
+            (false, false) => columns,
+        }
+    }
+
+    /// Build the query, taking out its parameters
+    fn build_query(&mut self) -> RawQuery {
+        let sql = self.sql();
+        let mut params = Vec::new();
+        if let Some(filter) = &mut self.filter {
+            params.append(&mut filter.params);
+        }
+        if let Some(having) = &mut self.having {
+            params.append(&mut having.params);
         }
+        params.append(&mut self.clauses.params);
+        params.append(&mut limit_offset(self.limit, self.offset).params);
+        RawQuery::new(sql, params)
+    }
+
+    /// Check if the query returns any rows, without loading them.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let taken = User::select()
+    ///     .filter(User::name.eq("John"))
+    ///     .exists(&conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT EXISTS(SELECT * FROM users WHERE users.name = ?);
+    /// ```
+    pub fn exists(mut self, conn: &Connection) -> Result<bool, rusqlite::Error> {
+        let query = self.build_query();
+        let sql = format!("SELECT EXISTS({})", query.sql);
+        let params = query.params.iter().map(|p| p.as_ref()).collect::<Vec<&dyn ToSql>>();
+        conn.connection.prepare_cached(&sql)?.query_row(params.as_slice(), |row| row.get(0))
+    }
+
+    /// Get the SQL of the query, with `?` in place of parameters.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let query = User::select().filter(User::age.gt(18)).order_by(User::name.asc()).limit(10);
+    /// assert_eq!(query.to_sql(), "SELECT * FROM users WHERE users.age > ? ORDER BY name ASC LIMIT ?");
+    /// assert_eq!(query.parameter_count(), 2);
+    /// ```
+    pub fn to_sql(&self) -> String {
+        self.sql()
+    }
+
+    /// Get the SQL of the query together with its bound parameters.
+    /// 
+    /// This consumes the query, as its parameters are moved into the returned [RawQuery](crate::connection::RawQuery).
+    /// Use [to_sql](Self::to_sql) to only inspect the SQL of a query that is executed later.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let raw = User::select().filter(User::age.gt(18)).limit(10).to_raw();
+    /// assert_eq!(raw.sql, "SELECT * FROM users WHERE users.age > ? LIMIT ?");
+    /// assert_eq!(raw.params.len(), 2);
+    /// ```
+    pub fn to_raw(mut self) -> RawQuery {
+        self.build_query()
+    }
+
+    /// Get the number of parameters bound to the query (the number of `?` in [to_sql](Self::to_sql)).
+    pub fn parameter_count(&self) -> usize {
+        let limits = limit_offset(self.limit, self.offset);
+        self.filter.iter()
+            .chain(self.having.iter())
+            .chain([&self.clauses, &limits])
+            .map(|query| query.params.len())
+            .sum()
+    }
+
+    /// Get the SQL of the query with parameters substituted, eg. for logging.
+    /// 
+    /// Text is quoted and escaped, and blobs are hex encoded, so the result can be copied and executed as it is.
+    /// This is only meant for debugging, always use parameters when executing queries.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let sql = User::select().filter(User::name.eq("O'Brien")).limit(1).debug_sql();
+    /// assert_eq!(sql, "SELECT * FROM users WHERE users.name = 'O''Brien' LIMIT 1");
+    /// ```
+    pub fn debug_sql(&self) -> String {
+        let limits = limit_offset(self.limit, self.offset);
+        let params = self.filter.iter()
+            .chain(self.having.iter())
+            .chain([&self.clauses, &limits])
+            .flat_map(|query| query.params.iter().map(|p| p.as_ref()))
+            .collect::<Vec<&dyn ToSql>>();
+        substitute_params(&self.sql(), &params)
     }
 
     // ====< Additional Methods >====
@@ -101,9 +295,61 @@ impl<M> ModelQuery<M> {
     ///     .filter(User::id.eq(1) & User::name.like("%test%"))
     ///     .exec(&conn).unwrap();
     /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times will combine the filters with AND.
     pub fn filter(self, mut filter: impl ModelQueryFilter) -> Self {
+        debug_assert_filter_tables(&filter, &self.table_name, &self.joined_tables);
         let filter_query = filter.get_query();
-        ModelQuery::combine(self, format!("WHERE {}", filter_query.sql), filter_query.params)
+        self.combine_filter(filter_query)
+    }
+
+    /// Filter the query with a reusable filter
+    /// 
+    /// ## Arguments
+    /// * `filter` - The shared filter to apply, it is cloned so it can be applied again
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let adults = SharedFilter::new(User::age.ge(18)).unwrap();
+    /// let count = User::count().apply_filter(&adults).exec(&conn).unwrap();
+    /// let users = User::select().apply_filter(&adults).exec(&conn).unwrap();
+    /// ```
+    pub fn apply_filter(self, filter: &SharedFilter) -> Self {
+        self.filter(filter.clone())
+    }
+
+    /// Include soft deleted rows, which are hidden by default for models with a `#[soft_delete]` column.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let deleted = User::select().with_deleted().filter(User::deleted_at.is_not_null()).exec(&conn).unwrap();
+    /// ```
+    pub fn with_deleted(self) -> Self {
+        ModelQuery {
+            soft_delete: None,
+            ..self
+        }
+    }
+
+    /// Filter the query with a raw SQL expression
+    /// 
+    /// ## Arguments
+    /// * `sql` - The SQL expression to filter by (use `?` for parameters)
+    /// * `params` - The parameters of the expression
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let posts = Post::select()
+    ///     .filter(Post::author.eq(1))
+    ///     .filter_raw("julianday('now') - julianday(created_at) > ?", vec![Box::new(7)])
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Like with `filter`, calling this when there already is a filter will combine them with AND.
+    pub fn filter_raw(self, sql: &str, params: Vec<Box<dyn ToSql>>) -> Self {
+        self.combine_filter(RawQuery::new(sql.to_string(), params))
     }
 
     /// Select element by id
@@ -119,10 +365,11 @@ impl<M> ModelQuery<M> {
     /// ```
     /// 
     /// ## Note
-    /// This is equivalent to `.filter(User::id.eq(id)).limit(1)` and should not be combined with other filters or limits.
+    /// This is equivalent to `.filter(User::id.eq(id)).limit(1)` and should not be combined with limits.
     pub fn with_id(self, id: i64) -> Self {
         let table_name = self.table_name.clone();
-        ModelQuery::combine(self, format!("WHERE {}.id = ? LIMIT 1", table_name), vec![Box::new(id)])
+        self.combine_filter(RawQuery::new(format!("{}.id = ?", table_name), vec![Box::new(id)]))
+            .limit(1)
     }
 
     /// Limit the number of elements returned
@@ -137,7 +384,10 @@ impl<M> ModelQuery<M> {
     ///     .exec(&conn).unwrap();
     /// ```
     pub fn limit(self, limit: u32) -> Self {
-        ModelQuery::combine(self, "LIMIT ?".to_string(), vec![Box::new(limit)])
+        ModelQuery {
+            limit: Some(limit),
+            ..self
+        }
     }
 
     /// Offset selection by the given number of elements
@@ -151,23 +401,155 @@ impl<M> ModelQuery<M> {
     ///     .offset(10)
     ///     .exec(&conn).unwrap();
     /// ```
+    ///
+    /// ## Note
+    /// Without a `limit` this skips the given number of elements and returns all of the remaining ones (`LIMIT -1 OFFSET ?`).
     pub fn offset(self, offset: u32) -> Self {
-        ModelQuery::combine(self, "OFFSET ?".to_string(), vec![Box::new(offset)])
+        ModelQuery {
+            offset: Some(offset),
+            ..self
+        }
     }
 
     /// Order the elements by the given order
     /// 
     /// ## Arguments
-    /// * `order` - The order to apply to the elements
+    /// * `order` - The order to apply to the elements, a single column or multiple ones (see [QueryOrder])
     /// 
     /// ## Example
     /// ```rs
     /// let users = User::select()
-    ///     .order(User::id.desc())
+    ///     .order_by(User::id.desc())
+    ///     .exec(&conn).unwrap();
+    /// 
+    /// // Oldest first, then by name
+    /// let users = User::select()
+    ///     .order_by((User::age.desc(), User::name.asc()))
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times adds the orders as tie-breakers, in the order of the calls.
+    pub fn order_by(self, order: impl QueryOrder) -> Self {
+        let sql = append_order(&self.clauses.sql, &order.order_sql());
+        ModelQuery {
+            clauses: RawQuery::new(sql, self.clauses.params),
+            ..self
+        }
+    }
+
+    /// Keyset (cursor) pagination, select the rows after the given value of the column, in ascending order.
+    /// 
+    /// Unlike `offset`, this doesn't have to skip over the previous pages, so it stays fast on large tables.
+    /// The column should be unique (eg. the id), otherwise rows with the same value can be skipped.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to page by
+    /// * `value` - The last value of the column seen on the previous page
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut last_id = 0;
+    /// loop {
+    ///     let page = User::select().after(User::id, last_id).limit(20).exec(&conn).unwrap();
+    ///     match page.last() {
+    ///         Some(user) => last_id = user.id.unwrap(),
+    ///         None => break,
+    ///     }
+    /// }
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM users WHERE users.id > ? ORDER BY users.id ASC LIMIT ?;
+    /// ```
+    /// 
+    /// ## Note
+    /// This adds an ORDER BY, so other orders should be added after calling this, as tie-breakers.
+    pub fn after<V: ToSql + 'static>(self, column: Column<'static>, value: V) -> Self {
+        // Qualified like the filter, so that it isn't ambiguous with joined tables
+        let order = column.clone().asc().qualified_sql();
+        let query = self.filter(column.gt(value));
+        let sql = append_order(&query.clauses.sql, &order);
+        ModelQuery {
+            clauses: RawQuery::new(sql, query.clauses.params),
+            ..query
+        }
+    }
+
+    /// Group the rows by the given column
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to group by
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let cities = User::select()
+    ///     .columns(&[User::city])
+    ///     .group_by(User::city)
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times will group by all of the columns.
+    pub fn group_by(mut self, column: Column<'static>) -> Self {
+        self.group_by.push(format!("{}.{}", column.table_name, column.name()));
+        self
+    }
+
+    /// Filter the groups with the given filter, which can use aggregates
+    /// 
+    /// ## Arguments
+    /// * `filter` - The filter to apply to the groups
+    /// 
+    /// ## Example
+    /// ```rs
+    /// // Cities with more than 5 users
+    /// let cities = User::select()
+    ///     .group_by(User::city)
+    ///     .having(count_all().gt(5))
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM users GROUP BY users.city HAVING COUNT(*) > ?;
+    /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times will combine the filters with AND.
+    pub fn having(self, mut filter: impl ModelQueryFilter) -> Self {
+        debug_assert_filter_tables(&filter, &self.table_name, &self.joined_tables);
+        let filter_query = filter.get_query();
+        ModelQuery {
+            having: Some(combine_filters(self.having, filter_query)),
+            ..self
+        }
+    }
+
+    /// Order the query by a column of a joined relation.
+    /// The column is qualified by the joined table, so it does not clash with columns of the queried model.
+    /// 
+    /// ## Arguments
+    /// * `order` - The order, created from a column of the joined model
+    /// 
+    /// ## Panics
+    /// If the column's table was not joined using [join_relation](ModelQuery::join_relation)
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let posts = Post::select()
+    ///     .join_relation(Post::author)
+    ///     .order_by_relation(User::name.asc())
     ///     .exec(&conn).unwrap();
     /// ```
-    pub fn order_by(self, order: ColumnQueryOrder) -> Self {
-        ModelQuery::combine(self, format!("ORDER BY {}", order.into_sqlite()), Vec::new())
+    pub fn order_by_relation(self, order: ColumnQueryOrder) -> Self {
+        if !self.joined_tables.contains(&order.table) {
+            panic!("Cannot order by a column of table {} which was not joined", order.table);
+        }
+        let sql = append_order(&self.clauses.sql, &order.qualified_sql());
+        ModelQuery {
+            clauses: RawQuery::new(sql, self.clauses.params),
+            ..self
+        }
     }
 
     /// **WARNING:** This is highly experimental and may not work as expected
@@ -176,6 +558,10 @@ impl<M> ModelQuery<M> {
     /// ## Arguments
     /// * `relation` - The relation to join
     /// 
+    /// ## Note
+    /// A relation of a model to itself (eg. `manager: Option<Relation<Self>>`) joins the table under the name of the relation column,
+    /// eg. `LEFT JOIN employees AS manager ON manager.id = employees.manager`.
+    /// 
     /// ## Example
     /// ```rs
     /// let users = Post::select()
@@ -186,85 +572,425 @@ impl<M> ModelQuery<M> {
         // Ensure that the relation is a relation
         match relation.get_relation() {
             Some(relation) => {
-                // Left join the relation table
-                let query = format!("{} LEFT JOIN {} ON {}.{} = {}.{}", self.query, relation.table, relation.table, relation.foreign_key_column.name_const(), relation.local_table, relation.local_key_column_name );
+                // Left join the relation table, a relation to the same table is aliased with the name of the column
+                let query = if relation.table == relation.local_table {
+                    format!("{} LEFT JOIN {} AS {} ON {}.{} = {}.{}", self.from, qualified_table_name(relation.table), relation.local_key_column_name, relation.local_key_column_name, relation.foreign_key_column.name_const(), relation.local_table, relation.local_key_column_name)
+                } else {
+                    format!("{} LEFT JOIN {} ON {}.{} = {}.{}", self.from, qualified_table_name(relation.table), relation.table, relation.foreign_key_column.name_const(), relation.local_table, relation.local_key_column_name)
+                };
 
                 self.joins.push(relation.local_key_column_name.to_string());
+                self.joined_tables.push(relation.table.to_string());
                 // Add the relation to the joins
                 ModelQuery {
-                    model: PhantomData,
-                    table_name: self.table_name,
-                    query,
-                    joins: self.joins,
-                    params: self.params,
+                    from: query,
+                    ..self
                 }
             },
             None => panic!("Cannot join a non-relation column"),
         }
     }
 
-    /// Select only the given columns (do not use this if you want to map to a model column which is not an `Option<T>`)
-    /// 
-    /// ## Arguments
-    /// * `columns` - The columns to select
+    /// Parse the results into another type instead of the model, eg. a struct deriving [FromRow](sequelite_macro::FromRow).
+    /// This is useful together with `columns` or `join_relation`, to select only some columns.
     /// 
     /// ## Example
     /// ```rs
-    /// let users = User::select()
+    /// #[derive(FromRow)]
+    /// struct UserName {
+    ///     id: i32,
+    ///     name: String,
+    /// }
+    /// 
+    /// let names: Vec<UserName> = User::select()
     ///     .columns(&[User::id, User::name])
+    ///     .select_as::<UserName>()
     ///     .exec(&conn).unwrap();
     /// ```
-    pub fn columns(self, columns: &[Column<'static>]) -> Self {
-        let columns = columns.iter().map(|c| c.name()).collect::<Vec<_>>().join(", ");
-        // Replace first SELECT * with the given columns
-        let query = self.query.replacen('*', &columns, 1);
+    pub fn select_as<T: FromRow>(self) -> ModelQuery<Projection<T>> {
         ModelQuery {
             model: PhantomData,
             table_name: self.table_name,
-            query,
+            columns: self.columns,
+            count: self.count,
+            distinct: self.distinct,
+            from: self.from,
             joins: self.joins,
-            params: self.params,
+            joined_tables: self.joined_tables,
+            filter: self.filter,
+            soft_delete: self.soft_delete,
+            group_by: self.group_by,
+            having: self.having,
+            clauses: self.clauses,
+            limit: self.limit,
+            offset: self.offset,
         }
     }
-}
 
-impl<M: Model> Queryable<Vec<M>> for ModelQuery<M> {
-    fn get_query(&mut self) -> crate::connection::RawQuery {
-        crate::connection::RawQuery::new(self.query.clone(), self.params.drain(..).collect())
+    /// Execute the query and parse each row into a tuple, by the position of the selected columns.
+    /// 
+    /// This is a shorthand for `query.select_as::<T>().exec(conn)`.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let names: Vec<(i32, String)> = User::select()
+    ///     .columns(&[User::id, User::name])
+    ///     .exec_tuples(&conn).unwrap();
+    /// ```
+    pub fn exec_tuples<T: FromRow>(self, conn: &crate::prelude::Connection) -> Result<Vec<T>, rusqlite::Error> {
+        self.select_as::<T>().exec(conn)
     }
 
-    fn parse_result(&mut self, rows: rusqlite::Rows) -> Vec<M> {
-        M::parse_rows(rows, 0, &self.joins)
+    /// Count the selected rows for every value of the column.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to group the rows by
+    /// * `conn` - The connection to execute the query on
+    /// 
+    /// ## Returns
+    /// A map from every value of the column to the number of rows with that value.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let adults_per_city: HashMap<String, i64> = User::select()
+    ///     .filter(User::age.ge(18))
+    ///     .group_count(User::city, &conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT users.city, COUNT(*) FROM users WHERE users.age >= ? GROUP BY users.city;
+    /// ```
+    /// 
+    /// ## Note
+    /// Rows where the column is NULL are counted as well, use `Option<K>` as the key type to get them.
+    pub fn group_count<K: FromSql + Eq + Hash>(self, column: Column<'static>, conn: &Connection) -> Result<HashMap<K, i64>, rusqlite::Error> {
+        let columns = vec![format!("{}.{}", column.table_name, column.name()), "COUNT(*)".to_string()];
+        ModelQuery { columns, count: false, distinct: false, ..self }
+            .group_by(column)
+            .exec_tuples::<(K, i64)>(conn)
+            .map(|groups| groups.into_iter().collect())
     }
-}
 
-impl<M: Model> Executable<Vec<M>> for ModelQuery<M> {
-    fn exec(self, conn: &crate::prelude::Connection) -> Result<Vec<M>, rusqlite::Error> {
-        conn.query(self)
+    /// Select only the given columns (do not use this if you want to map to a model column which is not an `Option<T>`, use [select_as](ModelQuery::select_as) instead)
+    /// 
+    /// ## Arguments
+    /// * `columns` - The columns to select
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let users = User::select()
+    ///     .columns(&[User::id, User::name])
+    ///     .exec(&conn).unwrap();
+    /// ```
+    pub fn columns(self, columns: &[Column<'static>]) -> Self {
+        ModelQuery {
+            columns: columns.iter().map(|c| c.name()).collect(),
+            ..self
+        }
     }
-}
 
-impl Queryable<usize> for ModelQuery<CountQuery> {
-    fn get_query(&mut self) -> crate::connection::RawQuery {
-        crate::connection::RawQuery::new(self.query.clone(), self.params.drain(..).collect())
+    /// Select only distinct rows (this should be called after `columns` if you use it)
+    ///
+    /// ## Example
+    /// ```rs
+    /// let cities = User::select()
+    ///     .columns(&[User::city])
+    ///     .distinct()
+    ///     .exec(&conn).unwrap();
+    /// ```
+    ///
+    /// ## Note
+    /// When used on a count query with a column specified, this will count distinct values instead:
+    /// ```rs
+    /// let cities = User::count()
+    ///     .columns(&[User::city])
+    ///     .distinct()
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// This will generate `SELECT COUNT(DISTINCT city) FROM users`.
+    pub fn distinct(self) -> Self {
+        ModelQuery {
+            distinct: true,
+            ..self
+        }
     }
+}
 
-    fn parse_result(&mut self, mut rows: rusqlite::Rows) -> usize {
-        rows.next().unwrap().unwrap().get(0).unwrap()
+impl<M> ModelQuery<M> {
+    /// Prepare the query for repeated execution with different parameters
+    /// 
+    /// ## Arguments
+    /// * `conn` - The connection to prepare the query on
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut query = User::select()
+    ///     .filter(User::name.eq(""))
+    ///     .prepare(&conn).unwrap();
+    /// 
+    /// let johns = query.exec(&[&"John"]).unwrap();
+    /// let janes = query.exec(&[&"Jane"]).unwrap();
+    /// ```
+    /// 
+    /// ## Note
+    /// Values passed to the query before preparing are just placeholders, all parameters (including limits and offsets) need to be passed on every execution.
+    pub fn prepare<T>(self, conn: &Connection) -> Result<PreparedQuery<'_, T, Self>, rusqlite::Error>
+    where
+        Self: Queryable<T>,
+    {
+        conn.prepare(self)
     }
-}
 
-impl Executable<usize> for ModelQuery<CountQuery> {
-    fn exec(self, conn: &crate::prelude::Connection) -> Result<usize, rusqlite::Error> {
-        conn.query(self)
+    /// Execute the query on an [AsyncConnection](crate::async_connection::AsyncConnection) and stream the results.
+    /// 
+    /// Rows are read on the connection thread and sent in batches, so large results are never buffered at once.
+    /// The query starts running when the stream is first polled. While other calls are waiting for the connection,
+    /// the remaining rows are read ahead, so an unfinished stream never blocks them.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let mut users = User::select().stream(&conn);
+    /// while let Some(user) = users.next().await {
+    ///     println!("{:?}", user?);
+    /// }
+    /// ```
+    /// 
+    /// ## Note
+    /// This requires the `async` feature to be enabled.
+    #[cfg(feature = "async")]
+    pub fn stream(mut self, conn: &crate::async_connection::AsyncConnection) -> impl futures::Stream<Item = Result<M, rusqlite::Error>>
+    where
+        M: Model + Send + 'static,
+    {
+        let query = self.build_query();
+        let params = query.owned_params();
+        let joins = std::mem::take(&mut self.joins);
+        let models = conn.stream(move |conn, send| {
+            let mut statement = conn.connection.prepare(&query.sql)?;
+            let mut rows = statement.query(rusqlite::params_from_iter(params?))?;
+            while let Some(row) = rows.next()? {
+                // Rows which can't be parsed are yielded as errors, without ending the stream
+                let model = M::parse_row(row, 0, &joins).and_then(|mut model| {
+                    model.load_collections(conn)?;
+                    Ok(model)
+                });
+                // Stop reading when the stream has been dropped
+                if !send(model) {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        futures::StreamExt::map(models, |model| model.and_then(|model| model))
     }
-}
+
+    /// Select one page of the results together with the total number of matching rows.
+    /// 
+    /// ## Arguments
+    /// * `page` - The number of the page, starting at 1
+    /// * `per_page` - The maximum number of rows on a page
+    /// * `conn` - The connection to execute the queries on
+    /// 
+    /// ## Returns
+    /// A [Page](Page) with the rows and the totals.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let page = User::select()
+    ///     .filter(User::age.ge(18))
+    ///     .order_by(User::name.asc())
+    ///     .paginate(2, 20, &conn).unwrap();
+    /// println!("Page {} of {} ({} users)", page.page, page.total_pages, page.total);
+    /// ```
+    /// This will generate the following SQL queries:
+    /// ```sql
+    /// SELECT COUNT(*) FROM (SELECT * FROM users WHERE users.age >= ? ORDER BY name ASC);
+    /// SELECT * FROM users WHERE users.age >= ? ORDER BY name ASC LIMIT ? OFFSET ?;
+    /// ```
+    /// 
+    /// ## Note
+    /// Both queries run in one transaction, so the total matches the rows. Limits and offsets set on the query are replaced.
+    /// 
+    /// ## Errors
+    /// [ToSqlConversionFailure](rusqlite::Error::ToSqlConversionFailure) if `page` or `per_page` is 0,
+    /// or if the offset of the page doesn't fit in a `u32`, so that unchecked input (eg. from a query string) can be passed as it is.
+    pub fn paginate(mut self, page: u32, per_page: u32, conn: &Connection) -> Result<Page<M>, rusqlite::Error>
+    where
+        M: Model,
+    {
+        let invalid = |message: String| rusqlite::Error::ToSqlConversionFailure(message.into());
+        if page == 0 {
+            return Err(invalid("Pages are numbered from 1".to_string()));
+        }
+        if per_page == 0 {
+            return Err(invalid("A page must have at least one row".to_string()));
+        }
+        let offset = (page - 1).checked_mul(per_page)
+            .ok_or_else(|| invalid(format!("Page {} with {} rows per page is out of range", page, per_page)))?;
+        self.limit = None;
+        self.offset = None;
+        let query = self.build_query();
+        let params = query.owned_params()?;
+
+        let transaction = if conn.connection.is_autocommit() {
+            Some(conn.connection.unchecked_transaction()?)
+        } else {
+            None
+        };
+
+        let count_sql = format!("SELECT COUNT(*) FROM ({})", query.sql);
+        debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&count_sql), count_sql);
+        let total: usize = conn.connection.prepare_cached(&count_sql)?
+            .query_row(rusqlite::params_from_iter(params.iter()), |row| row.get(0))?;
+
+        let limits = limit_offset(Some(per_page), Some(offset));
+        let sql = format!("{}{}", query.sql, limits.sql);
+        debug!(target: "query", "Executing query {:016x}: {:?}", query_id(&sql), sql);
+        let mut statement = conn.connection.prepare_cached(&sql)?;
+        let all_params = params.iter().map(|p| p as &dyn ToSql)
+            .chain(limits.params.iter().map(|p| p.as_ref()))
+            .collect::<Vec<_>>();
+        let mut items = M::parse_rows(statement.query(all_params.as_slice())?, 0, &self.joins)?;
+        M::load_all_collections(&mut items, conn)?;
+
+        if let Some(transaction) = transaction {
+            transaction.commit()?;
+        }
+        Ok(Page {
+            items,
+            total,
+            page,
+            total_pages: (total as u64).div_ceil(per_page as u64) as u32,
+        })
+    }
+}
+
+impl<M: Model> Queryable<Vec<M>> for ModelQuery<M> {
+    fn get_query(&mut self) -> crate::connection::RawQuery {
+        self.build_query()
+    }
+
+    fn parse_result(&mut self, rows: rusqlite::Rows) -> Result<Vec<M>, rusqlite::Error> {
+        M::parse_rows(rows, 0, &self.joins)
+    }
+
+    fn finish(&mut self, models: &mut Vec<M>, conn: &Connection) -> Result<(), rusqlite::Error> {
+        M::load_all_collections(models, conn)
+    }
+}
+
+impl<M: Model> Executable<Vec<M>> for ModelQuery<M> {
+    fn exec(self, conn: &crate::prelude::Connection) -> Result<Vec<M>, rusqlite::Error> {
+        conn.query(self)
+    }
+}
+
+impl<T: FromRow> Queryable<Vec<T>> for ModelQuery<Projection<T>> {
+    fn get_query(&mut self) -> crate::connection::RawQuery {
+        self.build_query()
+    }
+
+    fn parse_result(&mut self, mut rows: rusqlite::Rows) -> Result<Vec<T>, rusqlite::Error> {
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            results.push(T::from_row(row)?);
+        }
+        Ok(results)
+    }
+}
+
+impl<T: FromRow> Executable<Vec<T>> for ModelQuery<Projection<T>> {
+    fn exec(self, conn: &crate::prelude::Connection) -> Result<Vec<T>, rusqlite::Error> {
+        conn.query(self)
+    }
+}
+
+impl Queryable<usize> for ModelQuery<CountQuery> {
+    fn get_query(&mut self) -> crate::connection::RawQuery {
+        self.build_query()
+    }
+
+    fn parse_result(&mut self, mut rows: rusqlite::Rows) -> Result<usize, rusqlite::Error> {
+        match rows.next()? {
+            Some(row) => row.get(0),
+            None => Err(rusqlite::Error::QueryReturnedNoRows),
+        }
+    }
+}
+
+impl Executable<usize> for ModelQuery<CountQuery> {
+    fn exec(self, conn: &crate::prelude::Connection) -> Result<usize, rusqlite::Error> {
+        conn.query(self)
+    }
+}
+
+/// Join a new condition with an existing WHERE clause (if there is one) using AND
+pub(crate) fn combine_filters(existing: Option<RawQuery>, filter: RawQuery) -> RawQuery {
+    match existing {
+        Some(mut existing) => {
+            existing.params.extend(filter.params);
+            RawQuery::new(format!("({}) AND ({})", existing.sql, filter.sql), existing.params)
+        },
+        None => filter,
+    }
+}
+
+/// The condition selecting rows of the model which are not soft deleted (`table.deleted_at IS NULL`)
+pub(crate) fn soft_delete_condition<M: Model>() -> Option<String> {
+    M::soft_delete_column().map(|column| format!("{}.{} IS NULL", column.table_name, column.name()))
+}
+
+/// Build the WHERE clause (with a leading space) from the soft delete condition and the filter
+pub(crate) fn where_clause(soft_delete: Option<&str>, filter: Option<&RawQuery>) -> String {
+    match (soft_delete, filter) {
+        (Some(soft_delete), Some(filter)) => format!(" WHERE {} AND ({})", soft_delete, filter.sql),
+        (Some(soft_delete), None) => format!(" WHERE {}", soft_delete),
+        (None, Some(filter)) => format!(" WHERE {}", filter.sql),
+        (None, None) => String::new(),
+    }
+}
+
+/// Panic (in debug builds) if the filter uses columns of a table which is not part of the query.
+/// This catches filters on a different model early, instead of failing with an obscure "no such column" error.
+pub(crate) fn debug_assert_filter_tables(filter: &impl ModelQueryFilter, table: &str, joined_tables: &[String]) {
+    if cfg!(debug_assertions) {
+        for filter_table in filter.tables() {
+            if filter_table != table && !joined_tables.iter().any(|joined| joined == filter_table) {
+                panic!("Filter uses a column of table `{}`, but the query is on table `{}`", filter_table, table);
+            }
+        }
+    }
+}
+
+/// Build the LIMIT and OFFSET clauses (always in this order)
+///
+/// SQLite only accepts OFFSET after a LIMIT, so an offset without a limit is emitted as `LIMIT -1 OFFSET ?` (no limit).
+pub(crate) fn limit_offset(limit: Option<u32>, offset: Option<u32>) -> RawQuery {
+    let mut query = RawQuery::new(String::new(), Vec::new());
+    if let Some(limit) = limit {
+        query.sql.push_str(" LIMIT ?");
+        query.params.push(Box::new(limit));
+    } else if offset.is_some() {
+        query.sql.push_str(" LIMIT -1");
+    }
+    if let Some(offset) = offset {
+        query.sql.push_str(" OFFSET ?");
+        query.params.push(Box::new(offset));
+    }
+    query
+}
 
 pub trait ModelQueryFilter {
     fn get_query(&mut self) -> crate::connection::RawQuery;
+
+    /// Tables of the columns which are filtered (used to catch filters on columns of a different model)
+    fn tables(&self) -> Vec<&str> {
+        Vec::new()
+    }
 }
 
 pub struct InQueryFilter {
+    table: String,
     sql: RawQuery,
 }
 
@@ -272,9 +998,60 @@ impl ModelQueryFilter for InQueryFilter {
     fn get_query(&mut self) -> RawQuery {
         self.sql.move_clone()
     }
+
+    fn tables(&self) -> Vec<&str> {
+        vec![&self.table]
+    }
+}
+
+pub struct ExistsQueryFilter {
+    sql: RawQuery,
+}
+
+impl ModelQueryFilter for ExistsQueryFilter {
+    fn get_query(&mut self) -> RawQuery {
+        self.sql.move_clone()
+    }
+}
+
+/// Check if the subquery returns any rows
+/// 
+/// The subquery can reference columns of the outer query (correlated subquery).
+/// 
+/// ## Example
+/// ```rust
+/// // Users who have at least one post
+/// User::select().filter(exists(Post::select().filter(Post::author.eq_col(User::id)))).exec(conn);
+/// ```
+/// This will generate the following SQL query:
+/// ```sql
+/// SELECT * FROM users WHERE EXISTS (SELECT * FROM posts WHERE posts.author = users.id);
+/// ```
+pub fn exists<M: Model>(query: ModelQuery<M>) -> ExistsQueryFilter {
+    let q = query.to_query();
+    ExistsQueryFilter { sql: RawQuery::new(format!("EXISTS {}", q.sql), q.params) }
+}
+
+/// Check if the subquery does not return any rows
+/// 
+/// The subquery can reference columns of the outer query (correlated subquery).
+/// 
+/// ## Example
+/// ```rust
+/// // Users who have no posts
+/// User::select().filter(not_exists(Post::select().filter(Post::author.eq_col(User::id)))).exec(conn);
+/// ```
+/// This will generate the following SQL query:
+/// ```sql
+/// SELECT * FROM users WHERE NOT EXISTS (SELECT * FROM posts WHERE posts.author = users.id);
+/// ```
+pub fn not_exists<M: Model>(query: ModelQuery<M>) -> ExistsQueryFilter {
+    let q = query.to_query();
+    ExistsQueryFilter { sql: RawQuery::new(format!("NOT EXISTS {}", q.sql), q.params) }
 }
 
 pub struct ColumnQueryFilter {
+    table: String,
     column: String,
     value: Option<Box<dyn ToSql>>,
     op: &'static str,
@@ -286,9 +1063,157 @@ impl ModelQueryFilter for ColumnQueryFilter {
         let params = vec![self.value.take().unwrap()];
         RawQuery::new(sql, params)
     }
+
+    fn tables(&self) -> Vec<&str> {
+        vec![&self.table]
+    }
+}
+
+/// A filter on a column which is built from an arbitrary SQL expression (eg. `LIKE ? ESCAPE ?`)
+pub struct ColumnQueryFilterRaw {
+    table: String,
+    sql: RawQuery,
+}
+
+impl ModelQueryFilter for ColumnQueryFilterRaw {
+    fn get_query(&mut self) -> RawQuery {
+        self.sql.move_clone()
+    }
+
+    fn tables(&self) -> Vec<&str> {
+        // Expressions like COUNT(*) don't use any table
+        if self.table.is_empty() {
+            return Vec::new();
+        }
+        vec![&self.table]
+    }
+}
+
+/// An aggregate function (eg. `COUNT(*)` or `SUM(users.age)`) which can be compared in [having](ModelQuery::having).
+pub struct Aggregate {
+    table: String,
+    sql: String,
+}
+
+// Comparisons of an SQL expression with a value, for Aggregate and ColumnExpression
+macro_rules! impl_expression_comparisons {
+    ($name:literal) => {
+        fn compare<V: ToSql + 'static>(self, op: &str, value: V) -> ColumnQueryFilterRaw {
+            let sql = format!("{} {} ?", self.sql, op);
+            ColumnQueryFilterRaw { table: self.table, sql: RawQuery::new(sql, vec![Box::new(value)]) }
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is equal to the given value.")]
+        pub fn eq<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare("=", value)
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is not equal to the given value.")]
+        pub fn ne<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare("!=", value)
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is greater than the given value.")]
+        pub fn gt<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare(">", value)
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is less than the given value.")]
+        pub fn lt<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare("<", value)
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is greater than or equal to the given value.")]
+        pub fn ge<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare(">=", value)
+        }
+
+        #[doc = concat!("Checks if the ", $name, " is less than or equal to the given value.")]
+        pub fn le<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+            self.compare("<=", value)
+        }
+    };
+}
+
+impl Aggregate {
+    pub(crate) fn new(table: &str, sql: String) -> Self {
+        Aggregate { table: table.to_string(), sql }
+    }
+
+    impl_expression_comparisons!("aggregate");
+}
+
+/// An SQL function applied to a column (eg. `lower(users.name)`), which can be compared in filters.
+/// 
+/// ## Example
+/// ```rust
+/// User::select().filter(User::name.lower().eq("john")).exec(conn);
+/// User::select().filter(User::name.trim().length().gt(3)).exec(conn);
+/// ```
+/// This will generate the following SQL queries:
+/// ```sql
+/// SELECT * FROM users WHERE lower(users.name) = ?;
+/// SELECT * FROM users WHERE length(trim(users.name)) > ?;
+/// ```
+pub struct ColumnExpression {
+    table: String,
+    sql: String,
+}
+
+impl ColumnExpression {
+    pub(crate) fn new(table: &str, sql: String) -> Self {
+        ColumnExpression { table: table.to_string(), sql }
+    }
+
+    fn function(self, function: &str) -> Self {
+        ColumnExpression { sql: format!("{}({})", function, self.sql), ..self }
+    }
+
+    /// Convert the value to lower case (`lower(...)`, only ASCII characters are converted).
+    pub fn lower(self) -> Self {
+        self.function("lower")
+    }
+
+    /// Convert the value to upper case (`upper(...)`, only ASCII characters are converted).
+    pub fn upper(self) -> Self {
+        self.function("upper")
+    }
+
+    /// Number of characters of a text value, or bytes of a blob (`length(...)`).
+    pub fn length(self) -> Self {
+        self.function("length")
+    }
+
+    /// Absolute value of a number (`abs(...)`).
+    pub fn abs(self) -> Self {
+        self.function("abs")
+    }
+
+    /// Remove spaces from both ends of the value (`trim(...)`).
+    pub fn trim(self) -> Self {
+        self.function("trim")
+    }
+
+    impl_expression_comparisons!("expression");
+
+    /// Checks if the expression matches the given LIKE pattern.
+    pub fn like<V: ToSql + 'static>(self, pattern: V) -> ColumnQueryFilterRaw {
+        self.compare("LIKE", pattern)
+    }
+}
+
+/// Count the rows of a group, to be compared in [having](ModelQuery::having)
+/// 
+/// ## Example
+/// ```rust
+/// User::select().group_by(User::city).having(count_all().ge(10)).exec(conn);
+/// ```
+pub fn count_all() -> Aggregate {
+    Aggregate::new("", "COUNT(*)".to_string())
 }
 
 pub struct ColumnQueryFilterUnary {
+    table: String,
     column: String,
     op: &'static str,
 }
@@ -298,6 +1223,28 @@ impl ModelQueryFilter for ColumnQueryFilterUnary {
         let sql = format!("{} {}", self.column, self.op);
         RawQuery::new(sql, Vec::new())
     }
+
+    fn tables(&self) -> Vec<&str> {
+        vec![&self.table]
+    }
+}
+
+pub struct ColumnComparisonFilter {
+    table: String,
+    column: String,
+    other: String,
+    op: &'static str,
+}
+
+impl ModelQueryFilter for ColumnComparisonFilter {
+    fn get_query(&mut self) -> RawQuery {
+        let sql = format!("{} {} {}", self.column, self.op, self.other);
+        RawQuery::new(sql, Vec::new())
+    }
+
+    fn tables(&self) -> Vec<&str> {
+        vec![&self.table]
+    }
 }
 
 macro_rules! trait_column_filter {
@@ -311,6 +1258,7 @@ macro_rules! impl_column_filter {
         #[doc = $doc]
         fn $fn<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilter {
             ColumnQueryFilter {
+                table: self.table_name.to_string(),
                 column: format!("{}.{}", self.table_name, self.name()),
                 op: $op,
                 value: Some(Box::new(value)),
@@ -323,14 +1271,157 @@ macro_rules! impl_column_filter {
     };
 }
 
+macro_rules! trait_column_comparison {
+    ($fn:ident) => {
+        fn $fn(self, other: Column<'static>) -> ColumnComparisonFilter;
+    };
+}
+
+macro_rules! impl_column_comparison {
+    ($fn:ident, $op:literal, $doc:expr) => {
+        #[doc = $doc]
+        fn $fn(self, other: Column<'static>) -> ColumnComparisonFilter {
+            ColumnComparisonFilter {
+                table: self.table_name.to_string(),
+                column: format!("{}.{}", self.table_name, self.name()),
+                other: format!("{}.{}", other.table_name, other.name()),
+                op: $op,
+            }
+        }
+    };
+}
+
 pub struct ColumnQueryOrder {
+    table: String,
     column: String,
     order: ColumnQueryOrdering,
+    collation: Option<String>,
+}
+
+impl ColumnQueryOrder {
+    /// Compare values using the given collation (eg. `NOCASE` for case-insensitive sorting)
+    /// 
+    /// ## Arguments
+    /// * `collation` - The name of the collation, built-in ones are `BINARY`, `NOCASE` and `RTRIM`
+    /// 
+    /// ## Panics
+    /// If the collation name is not a valid identifier
+    /// 
+    /// ## Example
+    /// ```rust
+    /// User::select().order_by(User::name.asc().collate("NOCASE")).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM users ORDER BY name COLLATE NOCASE ASC;
+    /// ```
+    pub fn collate(self, collation: &str) -> Self {
+        if collation.is_empty() || !collation.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            panic!("Invalid collation name: {}", collation);
+        }
+        ColumnQueryOrder {
+            collation: Some(collation.to_string()),
+            ..self
+        }
+    }
+
+    fn collation_sql(&self) -> String {
+        match &self.collation {
+            Some(collation) => format!(" COLLATE {}", collation),
+            None => String::new(),
+        }
+    }
+
+    // The order with the column prefixed by its table
+    fn qualified_sql(&self) -> String {
+        format!("{}.{}{} {}", self.table, self.column, self.collation_sql(), self.order.into_sqlite())
+    }
 }
 
 impl IntoSqlite for ColumnQueryOrder {
     fn into_sqlite(&self) -> String {
-        format!("{} {}", self.column, self.order.into_sqlite())
+        format!("{}{} {}", self.column, self.collation_sql(), self.order.into_sqlite())
+    }
+}
+
+/// One or more orders that can be passed to `order_by`.
+/// 
+/// This is implemented for a single [ColumnQueryOrder], tuples of up to 4 orders, and arrays, vectors and slices of orders.
+/// 
+/// ## Example
+/// ```rs
+/// User::select().order_by((User::age.desc(), User::name.asc()));
+/// User::select().order_by(vec![User::age.desc(), User::name.asc()]);
+/// ```
+/// This will generate the following SQL query:
+/// ```sql
+/// SELECT * FROM users ORDER BY age DESC, name ASC;
+/// ```
+pub trait QueryOrder {
+    /// Get the SQL of the orders (without `ORDER BY`), separated with commas.
+    fn order_sql(&self) -> String;
+}
+
+impl QueryOrder for ColumnQueryOrder {
+    fn order_sql(&self) -> String {
+        self.into_sqlite()
+    }
+}
+
+impl<T: QueryOrder> QueryOrder for [T] {
+    fn order_sql(&self) -> String {
+        self.iter()
+            .map(|order| order.order_sql())
+            .filter(|sql| !sql.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl<T: QueryOrder, const N: usize> QueryOrder for [T; N] {
+    fn order_sql(&self) -> String {
+        self.as_slice().order_sql()
+    }
+}
+
+impl<T: QueryOrder> QueryOrder for Vec<T> {
+    fn order_sql(&self) -> String {
+        self.as_slice().order_sql()
+    }
+}
+
+impl<T: QueryOrder + ?Sized> QueryOrder for &T {
+    fn order_sql(&self) -> String {
+        (**self).order_sql()
+    }
+}
+
+macro_rules! impl_query_order_tuple {
+    ($($t:ident $idx:tt),+) => {
+        impl<$($t: QueryOrder),+> QueryOrder for ($($t,)+) {
+            fn order_sql(&self) -> String {
+                [$(self.$idx.order_sql()),+]
+                    .into_iter()
+                    .filter(|sql| !sql.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        }
+    };
+}
+
+impl_query_order_tuple!(A 0, B 1);
+impl_query_order_tuple!(A 0, B 1, C 2);
+impl_query_order_tuple!(A 0, B 1, C 2, D 3);
+
+/// Add orders to clauses which may already contain an ORDER BY (then they become tie-breakers)
+pub(crate) fn append_order(clauses: &str, order: &str) -> String {
+    if order.is_empty() {
+        clauses.to_string()
+    } else if clauses.contains("ORDER BY") {
+        format!("{}, {}", clauses, order)
+    } else {
+        format!("{} ORDER BY {}", clauses, order)
     }
 }
 
@@ -358,6 +1449,23 @@ pub trait ColumnQueryFilterImpl {
     
     trait_column_filter!(like);
     trait_column_filter!(not_like);
+    trait_column_filter!(glob);
+    trait_column_filter!(not_glob);
+    trait_column_filter!(is_distinct_from);
+    fn is<V: ToSql + 'static>(self, value: Option<V>) -> ColumnQueryFilter;
+
+    fn like_escape<V: ToSql + 'static>(self, pattern: V, escape: char) -> ColumnQueryFilterRaw;
+    fn ilike<V: ToSql + 'static>(self, pattern: V) -> ColumnQueryFilterRaw;
+    fn eq_nocase<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw;
+    fn between<V: ToSql + 'static>(self, low: V, high: V) -> ColumnQueryFilterRaw;
+    fn not_between<V: ToSql + 'static>(self, low: V, high: V) -> ColumnQueryFilterRaw;
+
+    trait_column_comparison!(eq_col);
+    trait_column_comparison!(ne_col);
+    trait_column_comparison!(gt_col);
+    trait_column_comparison!(lt_col);
+    trait_column_comparison!(ge_col);
+    trait_column_comparison!(le_col);
 
     fn is_null(self) -> ColumnQueryFilterUnary;
     fn is_not_null(self) -> ColumnQueryFilterUnary;
@@ -374,96 +1482,300 @@ impl ColumnQueryFilterImpl for Column<'_> {
         Checks if the column is equal to the given value.
         ## Example
         ```rust
-        User::select().filter(User::name.eq(\"John\")).exec(conn);
+        User::select().filter(User::name.eq(\"John\")).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.name = ?;
+        ```
+    ");
+    impl_column_filter!(ne, "!=", "
+        Checks if the column is not equal to the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::name.ne(\"John\")).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.name != ?;
+        ```
+    ");
+    impl_column_filter!(gt, ">", "
+        Checks if the column is greater than the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::age.gt(18)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.age > ?;
+        ```
+    ");
+    impl_column_filter!(lt, "<", "
+        Checks if the column is less than the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::age.lt(18)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.age < ?;
+        ```
+    ");
+    impl_column_filter!(ge, ">=", "
+        Checks if the column is greater than or equal to the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::age.ge(18)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.age >= ?;
+        ```
+    ");
+    impl_column_filter!(le, "<=", "
+        Checks if the column is less than or equal to the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::age.le(18)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.age <= ?;
+        ```
+    ");
+
+    impl_column_filter!(like, "LIKE", "
+        Checks if the column is like the given value.
+        `%` matches any sequence of characters and `_` any single character, use `like_escape` to match them literally.
+        SQLite's `LIKE` is case-insensitive for ASCII characters by default, use `glob` for case-sensitive matching.
+        ## Example
+        ```rust
+        User::select().filter(User::name.like(\"%John%\")).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        -- ? is a parameter
+        SELECT * FROM users WHERE users.name LIKE ?;
+        ```
+    ");
+    impl_column_filter!(not_like, "NOT LIKE", "
+        Checks if the column is not like the given value.
+        ## Example
+        ```rust
+        User::select().filter(User::name.not_like(\"%John%\")).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
-        -- ? is a parameter
-        SELECT * FROM users WHERE users.name = ?;
+        SELECT * FROM users WHERE users.name NOT LIKE ?;
         ```
     ");
-    impl_column_filter!(ne, "!=", "
-        Checks if the column is not equal to the given value.
+    impl_column_filter!(glob, "GLOB", "
+        Checks if the column matches the given glob pattern (case-sensitive).
+        `*` matches any sequence of characters, `?` any single character and `[...]` a set of characters.
         ## Example
         ```rust
-        User::select().filter(User::name.ne(\"John\")).exec(conn);
+        User::select().filter(User::name.glob(\"J*\")).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
         -- ? is a parameter
-        SELECT * FROM users WHERE users.name != ?;
+        SELECT * FROM users WHERE users.name GLOB ?;
         ```
     ");
-    impl_column_filter!(gt, ">", "
-        Checks if the column is greater than the given value.
+    impl_column_filter!(not_glob, "NOT GLOB", "
+        Checks if the column does not match the given glob pattern (case-sensitive).
         ## Example
         ```rust
-        User::select().filter(User::age.gt(18)).exec(conn);
+        User::select().filter(User::name.not_glob(\"J*\")).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
         -- ? is a parameter
-        SELECT * FROM users WHERE users.age > ?;
+        SELECT * FROM users WHERE users.name NOT GLOB ?;
         ```
     ");
-    impl_column_filter!(lt, "<", "
-        Checks if the column is less than the given value.
+    impl_column_filter!(is_distinct_from, "IS NOT", "
+        Checks if the column is not equal to the given value, treating NULL as a distinct value.
+        Unlike `ne`, this also matches rows where the column is NULL.
         ## Example
         ```rust
-        User::select().filter(User::age.lt(18)).exec(conn);
+        User::select().filter(User::nickname.is_distinct_from(\"Johnny\")).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
         -- ? is a parameter
-        SELECT * FROM users WHERE users.age < ?;
+        SELECT * FROM users WHERE users.nickname IS NOT ?;
         ```
     ");
-    impl_column_filter!(ge, ">=", "
-        Checks if the column is greater than or equal to the given value.
+
+    /// Checks if the column is equal to the given value, treating NULL as a regular value.
+    /// Unlike `eq`, passing `None` matches rows where the column is NULL.
+    /// ## Example
+    /// ```rust
+    /// User::select().filter(User::nickname.is(None::<String>)).exec(conn);
+    /// User::select().filter(User::nickname.is(Some("Johnny"))).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// -- ? is a parameter
+    /// SELECT * FROM users WHERE users.nickname IS ?;
+    /// ```
+    fn is<V: ToSql + 'static>(self, value: Option<V>) -> ColumnQueryFilter {
+        ColumnQueryFilter {
+            table: self.table_name.to_string(),
+            column: format!("{}.{}", self.table_name, self.name()),
+            op: "IS",
+            value: Some(Box::new(value)),
+        }
+    }
+
+    /// Checks if the column is like the given pattern, where the escape character can be used to match literal `%` and `_`.
+    /// ## Example
+    /// ```rust
+    /// User::select().filter(User::name.like_escape("%\\_%", '\\')).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// -- ? is a parameter
+    /// SELECT * FROM users WHERE users.name LIKE ? ESCAPE ?;
+    /// ```
+    fn like_escape<V: ToSql + 'static>(self, pattern: V, escape: char) -> ColumnQueryFilterRaw {
+        let sql = format!("{}.{} LIKE ? ESCAPE ?", self.table_name, self.name());
+        ColumnQueryFilterRaw { table: self.table_name.to_string(), sql: RawQuery::new(sql, vec![Box::new(pattern), Box::new(escape.to_string())]) }
+    }
+
+    /// Checks if the column is like the given pattern ignoring case.
+    /// 
+    /// SQLite's `LIKE` is already case-insensitive for ASCII characters by default,
+    /// but this will also work when `PRAGMA case_sensitive_like` is enabled.
+    /// ## Example
+    /// ```rust
+    /// User::select().filter(User::name.ilike("%john%")).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// -- ? is a parameter
+    /// SELECT * FROM users WHERE lower(users.name) LIKE lower(?);
+    /// ```
+    fn ilike<V: ToSql + 'static>(self, pattern: V) -> ColumnQueryFilterRaw {
+        let sql = format!("lower({}.{}) LIKE lower(?)", self.table_name, self.name());
+        ColumnQueryFilterRaw { table: self.table_name.to_string(), sql: RawQuery::new(sql, vec![Box::new(pattern)]) }
+    }
+
+    /// Checks if the column is equal to the given value ignoring case.
+    /// ## Example
+    /// ```rust
+    /// User::select().filter(User::name.eq_nocase("john")).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// -- ? is a parameter
+    /// SELECT * FROM users WHERE users.name = ? COLLATE NOCASE;
+    /// ```
+    fn eq_nocase<V: ToSql + 'static>(self, value: V) -> ColumnQueryFilterRaw {
+        let sql = format!("{}.{} = ? COLLATE NOCASE", self.table_name, self.name());
+        ColumnQueryFilterRaw { table: self.table_name.to_string(), sql: RawQuery::new(sql, vec![Box::new(value)]) }
+    }
+
+    /// Checks if the column is between the given values (inclusive).
+    /// ## Example
+    /// ```rust
+    /// Event::select().filter(Event::created_at.between(start, end)).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM events WHERE events.created_at BETWEEN ? AND ?;
+    /// ```
+    fn between<V: ToSql + 'static>(self, low: V, high: V) -> ColumnQueryFilterRaw {
+        let sql = format!("{}.{} BETWEEN ? AND ?", self.table_name, self.name());
+        ColumnQueryFilterRaw { table: self.table_name.to_string(), sql: RawQuery::new(sql, vec![Box::new(low), Box::new(high)]) }
+    }
+
+    /// Checks if the column is outside of the given range.
+    /// ## Example
+    /// ```rust
+    /// Event::select().filter(Event::created_at.not_between(start, end)).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM events WHERE events.created_at NOT BETWEEN ? AND ?;
+    /// ```
+    fn not_between<V: ToSql + 'static>(self, low: V, high: V) -> ColumnQueryFilterRaw {
+        let sql = format!("{}.{} NOT BETWEEN ? AND ?", self.table_name, self.name());
+        ColumnQueryFilterRaw { table: self.table_name.to_string(), sql: RawQuery::new(sql, vec![Box::new(low), Box::new(high)]) }
+    }
+
+    impl_column_comparison!(eq_col, "=", "
+        Checks if the column is equal to another column.
         ## Example
         ```rust
-        User::select().filter(User::age.ge(18)).exec(conn);
+        Order::select().filter(Order::shipped_at.eq_col(Order::created_at)).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
-        -- ? is a parameter
-        SELECT * FROM users WHERE users.age >= ?;
+        SELECT * FROM orders WHERE orders.shipped_at = orders.created_at;
         ```
     ");
-    impl_column_filter!(le, "<=", "
-        Checks if the column is less than or equal to the given value.
+    impl_column_comparison!(ne_col, "!=", "
+        Checks if the column is not equal to another column.
         ## Example
         ```rust
-        User::select().filter(User::age.le(18)).exec(conn);
+        Order::select().filter(Order::shipped_at.ne_col(Order::created_at)).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
-        -- ? is a parameter
-        SELECT * FROM users WHERE users.age <= ?;
+        SELECT * FROM orders WHERE orders.shipped_at != orders.created_at;
         ```
     ");
-
-    impl_column_filter!(like, "LIKE", "
-        Checks if the column is like the given value.
+    impl_column_comparison!(gt_col, ">", "
+        Checks if the column is greater than another column.
         ## Example
         ```rust
-        User::select().filter(User::name.like(\"%John%\")).exec(conn);
+        Order::select().filter(Order::shipped_at.gt_col(Order::created_at)).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
-        -- ? is a parameter
-        SELECT * FROM users WHERE users.name LIKE ?;
+        SELECT * FROM orders WHERE orders.shipped_at > orders.created_at;
         ```
     ");
-    impl_column_filter!(not_like, "NOT LIKE", "
-        Checks if the column is not like the given value.
+    impl_column_comparison!(lt_col, "<", "
+        Checks if the column is less than another column.
         ## Example
         ```rust
-        User::select().filter(User::name.not_like(\"%John%\")).exec(conn);
+        Order::select().filter(Order::shipped_at.lt_col(Order::created_at)).exec(conn);
         ```
         This will generate the following SQL query:
         ```sql
-        SELECT * FROM users WHERE users.name NOT LIKE ?;
+        SELECT * FROM orders WHERE orders.shipped_at < orders.created_at;
+        ```
+    ");
+    impl_column_comparison!(ge_col, ">=", "
+        Checks if the column is greater than or equal to another column.
+        ## Example
+        ```rust
+        Order::select().filter(Order::shipped_at.ge_col(Order::created_at)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        SELECT * FROM orders WHERE orders.shipped_at >= orders.created_at;
+        ```
+    ");
+    impl_column_comparison!(le_col, "<=", "
+        Checks if the column is less than or equal to another column.
+        ## Example
+        ```rust
+        Order::select().filter(Order::shipped_at.le_col(Order::created_at)).exec(conn);
+        ```
+        This will generate the following SQL query:
+        ```sql
+        SELECT * FROM orders WHERE orders.shipped_at <= orders.created_at;
         ```
     ");
 
@@ -478,6 +1790,7 @@ impl ColumnQueryFilterImpl for Column<'_> {
     /// ```
     fn is_null(self) -> ColumnQueryFilterUnary {
         ColumnQueryFilterUnary {
+            table: self.table_name.to_string(),
             column: format!("{}.{}", self.table_name, self.name()),
             op: "IS NULL",
         }
@@ -494,6 +1807,7 @@ impl ColumnQueryFilterImpl for Column<'_> {
     /// ```
     fn is_not_null(self) -> ColumnQueryFilterUnary {
         ColumnQueryFilterUnary {
+            table: self.table_name.to_string(),
             column: format!("{}.{}", self.table_name, self.name()),
             op: "IS NOT NULL",
         }
@@ -513,7 +1827,7 @@ impl ColumnQueryFilterImpl for Column<'_> {
         let q = values.to_query();
         let sql = format!("{}.{} IN {}", self.table_name, self.name(), q.sql);
 
-        InQueryFilter { sql: RawQuery::new(sql, q.params) }
+        InQueryFilter { table: self.table_name.to_string(), sql: RawQuery::new(sql, q.params) }
     }
 
     /// Check if the column is not in the list of values
@@ -530,7 +1844,7 @@ impl ColumnQueryFilterImpl for Column<'_> {
         let q = values.to_query();
         let sql = format!("{}.{} NOT IN {}", self.table_name, self.name(), q.sql);
 
-        InQueryFilter { sql: RawQuery::new(sql, q.params) }
+        InQueryFilter { table: self.table_name.to_string(), sql: RawQuery::new(sql, q.params) }
     }
 
     /// Order the query by the column in ascending order
@@ -544,8 +1858,10 @@ impl ColumnQueryFilterImpl for Column<'_> {
     /// ```
     fn asc(self) -> ColumnQueryOrder {
         ColumnQueryOrder {
+            table: self.table_name.to_string(),
             column: self.name(),
             order: ColumnQueryOrdering::Ascending,
+            collation: None,
         }
     }
 
@@ -560,12 +1876,18 @@ impl ColumnQueryFilterImpl for Column<'_> {
     /// ```
     fn desc(self) -> ColumnQueryOrder {
         ColumnQueryOrder {
+            table: self.table_name.to_string(),
             column: self.name(),
             order: ColumnQueryOrdering::Descending,
+            collation: None,
         }
     }
 }
 
+/// Values (or a subquery) for the right-hand side of `IN`.
+/// 
+/// An empty list produces `IN ()`, which SQLite accepts: `IN ()` matches no rows and `NOT IN ()` matches every row (including NULLs).
+/// Rewriting it as `IN (NULL)` would be wrong for `NOT IN`, which would then match nothing.
 pub trait ColumnInQuery {
     fn to_query(self) -> RawQuery;
 }
@@ -579,62 +1901,41 @@ impl<M: Model> ColumnInQuery for ModelQuery<M> {
     }
 }
 
-impl<T: ToSql + 'static> ColumnInQuery for Vec<T> {
-    fn to_query(self) -> RawQuery {
-        let mut params = Vec::new();
-        let mut sql = String::from("(");
-
-        for (i, v) in self.into_iter().enumerate() {
-            if i > 0 {
-                sql.push_str(", ");
-            }
+// Placeholders for a list of values, eg. `(?, ?, ?)`
+fn in_list<T: ToSql + 'static>(values: impl Iterator<Item = T>) -> RawQuery {
+    let mut params = Vec::new();
+    let mut sql = String::from("(");
 
-            sql.push('?');
-            params.push(Box::new(v) as Box<dyn ToSql + 'static>);
+    for (i, v) in values.enumerate() {
+        if i > 0 {
+            sql.push_str(", ");
         }
 
-        sql.push(')');
-
-        RawQuery::new(sql, params)
+        sql.push('?');
+        params.push(Box::new(v) as Box<dyn ToSql + 'static>);
     }
-}
-
-impl<T: ToSql + 'static> ColumnInQuery for &'static [T] {
-    fn to_query(self) -> RawQuery {
-        let mut params = Vec::new();
-        let mut sql = String::from("(");
-
-        for (i, v) in self.iter().enumerate() {
-            if i > 0 {
-                sql.push_str(", ");
-            }
 
-            sql.push('?');
-            params.push(Box::new(v) as Box<dyn ToSql + 'static>);
-        }
+    sql.push(')');
 
-        sql.push(')');
+    RawQuery::new(sql, params)
+}
 
-        RawQuery::new(sql, params)
+impl<T: ToSql + 'static> ColumnInQuery for Vec<T> {
+    fn to_query(self) -> RawQuery {
+        in_list(self.into_iter())
     }
 }
-impl<T: ToSql + 'static, const N: usize> ColumnInQuery for &'static [T; N] {
-    fn to_query(self) -> RawQuery {
-        let mut params = Vec::new();
-        let mut sql = String::from("(");
-
-        for (i, v) in self.iter().enumerate() {
-            if i > 0 {
-                sql.push_str(", ");
-            }
-
-            sql.push('?');
-            params.push(Box::new(v) as Box<dyn ToSql + 'static>);
-        }
 
-        sql.push(')');
+/// Borrowed values are cloned into the query, so the slice doesn't have to outlive it.
+impl<T: ToSql + Clone + 'static> ColumnInQuery for &[T] {
+    fn to_query(self) -> RawQuery {
+        in_list(self.iter().cloned())
+    }
+}
 
-        RawQuery::new(sql, params)
+impl<T: ToSql + Clone + 'static, const N: usize> ColumnInQuery for &[T; N] {
+    fn to_query(self) -> RawQuery {
+        in_list(self.iter().cloned())
     }
 }
 
@@ -646,6 +1947,10 @@ pub trait ModelQueryFilterExt: ModelQueryFilter {
     fn or<F: ModelQueryFilter>(self, filter: F) -> ModelQueryFilterOr<Self, F>
     where
         Self: Sized;
+
+    fn not(self) -> ModelQueryFilterNot<Self>
+    where
+        Self: Sized;
 }
 
 impl<F: ModelQueryFilter> ModelQueryFilterExt for F {
@@ -656,7 +1961,7 @@ impl<F: ModelQueryFilter> ModelQueryFilterExt for F {
     /// ```
     /// This will generate the following SQL query:
     /// ```sql
-    /// SELECT * FROM users WHERE users.name = ? AND users.age > ?;
+    /// SELECT * FROM users WHERE (users.name = ?) AND (users.age > ?);
     /// ```
     /// 
     /// ## Note
@@ -681,7 +1986,7 @@ impl<F: ModelQueryFilter> ModelQueryFilterExt for F {
     /// ```
     /// This will generate the following SQL query:
     /// ```sql
-    /// SELECT * FROM users WHERE users.name = ? OR users.age > ?;
+    /// SELECT * FROM users WHERE (users.name = ?) OR (users.age > ?);
     /// ```
     /// 
     /// ## Note
@@ -698,6 +2003,80 @@ impl<F: ModelQueryFilter> ModelQueryFilterExt for F {
             filter1: filter,
         }
     }
+
+    /// Negate the filter with a NOT operator
+    /// ## Example
+    /// ```rust
+    /// User::select().filter((User::name.eq("John") | User::age.gt(18)).not()).exec(conn);
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// SELECT * FROM users WHERE NOT ((users.name = ?) OR (users.age > ?));
+    /// ```
+    /// 
+    /// ## Note
+    /// You can also use '!' instead:
+    /// ```rust
+    /// User::select().filter(!(User::name.eq("John") | User::age.gt(18))).exec(conn);
+    /// ```
+    fn not(self) -> ModelQueryFilterNot<Self>
+    where
+        Self: Sized,
+    {
+        ModelQueryFilterNot {
+            filter: self,
+        }
+    }
+}
+
+/// A filter that can be cloned and applied to multiple queries.
+/// Parameters are converted to owned SQLite values when the filter is created.
+/// 
+/// ## Example
+/// ```rs
+/// let inactive = SharedFilter::new(User::last_login.lt(cutoff)).unwrap();
+/// 
+/// // Preview what would be deleted
+/// let count = User::count().apply_filter(&inactive).exec(&conn).unwrap();
+/// // Then delete it
+/// User::delete().apply_filter(&inactive).exec(&conn).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SharedFilter {
+    sql: String,
+    params: Vec<Value>,
+    tables: Vec<String>,
+}
+
+impl SharedFilter {
+    /// Create a reusable filter from any filter.
+    /// 
+    /// ## Returns
+    /// An error if any of the filter parameters fails to convert to a SQLite value.
+    pub fn new(mut filter: impl ModelQueryFilter) -> Result<Self, rusqlite::Error> {
+        let tables = filter.tables().into_iter().map(|table| table.to_string()).collect();
+        let query = filter.get_query();
+        let params = query.owned_params()?;
+
+        Ok(SharedFilter {
+            sql: query.sql,
+            params,
+            tables,
+        })
+    }
+}
+
+impl ModelQueryFilter for SharedFilter {
+    fn get_query(&mut self) -> RawQuery {
+        let params = self.params.iter()
+            .map(|param| Box::new(param.clone()) as Box<dyn ToSql>)
+            .collect();
+        RawQuery::new(self.sql.clone(), params)
+    }
+
+    fn tables(&self) -> Vec<&str> {
+        self.tables.iter().map(|table| table.as_str()).collect()
+    }
 }
 
 pub struct ModelQueryFilterAnd<F0: ModelQueryFilter, F1: ModelQueryFilter> {
@@ -710,29 +2089,79 @@ pub struct ModelQueryFilterOr<F0: ModelQueryFilter, F1: ModelQueryFilter> {
     filter1: F1,
 }
 
+/// Negate the filter with a NOT operator
+/// 
+/// This is the same as calling [ModelQueryFilterExt::not] or using '!' on the filter.
+/// 
+/// ## Example
+/// ```rust
+/// User::select().filter(not(User::name.eq("John") & User::age.lt(18))).exec(conn);
+/// ```
+/// This will generate the following SQL query:
+/// ```sql
+/// SELECT * FROM users WHERE NOT ((users.name = ?) AND (users.age < ?));
+/// ```
+pub fn not<F: ModelQueryFilter>(filter: F) -> ModelQueryFilterNot<F> {
+    ModelQueryFilterNot {
+        filter,
+    }
+}
+
+pub struct ModelQueryFilterNot<F: ModelQueryFilter> {
+    filter: F,
+}
+
+impl<F: ModelQueryFilter> ModelQueryFilter for ModelQueryFilterNot<F> {
+    fn get_query(&mut self) -> crate::connection::RawQuery {
+        let mut query = self.filter.get_query();
+        query.sql = format!("NOT ({})", query.sql);
+        query
+    }
+
+    fn tables(&self) -> Vec<&str> {
+        self.filter.tables()
+    }
+}
+
 impl<F0: ModelQueryFilter, F1: ModelQueryFilter> ModelQueryFilter for ModelQueryFilterAnd<F0, F1> {
     fn get_query(&mut self) -> crate::connection::RawQuery {
         let mut query = self.filter0.get_query();
         let mut query1 = self.filter1.get_query();
-        query.sql = format!("{} AND {}", query.sql, query1.sql);
+        query.sql = format!("({}) AND ({})", query.sql, query1.sql);
         query.params.append(&mut query1.params);
         query
     }
+
+    fn tables(&self) -> Vec<&str> {
+        let mut tables = self.filter0.tables();
+        tables.extend(self.filter1.tables());
+        tables
+    }
 }
 
 impl<F0: ModelQueryFilter, F1: ModelQueryFilter> ModelQueryFilter for ModelQueryFilterOr<F0, F1> {
     fn get_query(&mut self) -> crate::connection::RawQuery {
         let mut query = self.filter0.get_query();
         let mut query1 = self.filter1.get_query();
-        query.sql = format!("{} OR {}", query.sql, query1.sql);
+        query.sql = format!("({}) OR ({})", query.sql, query1.sql);
         query.params.append(&mut query1.params);
         query
     }
+
+    fn tables(&self) -> Vec<&str> {
+        let mut tables = self.filter0.tables();
+        tables.extend(self.filter1.tables());
+        tables
+    }
 }
 
 macro_rules! impl_op {
     ($op:ident ($fn:ident), $target:ident => $result:ident, $doc:expr) => {
-        impl<T: ModelQueryFilter> $op<T> for $target {
+        impl_op!($op ($fn), [] $target => $result, $doc);
+    };
+
+    ($op:ident ($fn:ident), [$($gen:tt)*] $target:ty => $result:ident, $doc:expr) => {
+        impl<$($gen)* T: ModelQueryFilter> $op<T> for $target {
             type Output = $result<Self, T>;
 
             #[doc = $doc]
@@ -746,6 +2175,25 @@ macro_rules! impl_op {
     };
 }
 
+macro_rules! impl_not {
+    ($target:ident) => {
+        impl_not!([] $target);
+    };
+
+    ([$($gen:tt)*] $target:ty) => {
+        impl<$($gen)*> Not for $target {
+            type Output = ModelQueryFilterNot<Self>;
+
+            /// Alternative to [ModelQueryFilterExt::not]
+            fn not(self) -> Self::Output {
+                ModelQueryFilterNot {
+                    filter: self,
+                }
+            }
+        }
+    };
+}
+
 impl_op!(BitAnd (bitand), ColumnQueryFilter => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
 impl_op!(BitOr (bitor), ColumnQueryFilter => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
 
@@ -755,16 +2203,107 @@ impl_op!(BitOr (bitor), InQueryFilter => ModelQueryFilterOr, "Alternative to [Mo
 impl_op!(BitAnd (bitand), ColumnQueryFilterUnary => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
 impl_op!(BitOr (bitor), ColumnQueryFilterUnary => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
 
+impl_op!(BitAnd (bitand), ExistsQueryFilter => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), ExistsQueryFilter => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), ColumnComparisonFilter => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), ColumnComparisonFilter => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), [F: ModelQueryFilter,] ModelQueryFilterNot<F> => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), [F: ModelQueryFilter,] ModelQueryFilterNot<F> => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), [F0: ModelQueryFilter, F1: ModelQueryFilter,] ModelQueryFilterAnd<F0, F1> => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), [F0: ModelQueryFilter, F1: ModelQueryFilter,] ModelQueryFilterAnd<F0, F1> => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), [F0: ModelQueryFilter, F1: ModelQueryFilter,] ModelQueryFilterOr<F0, F1> => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), [F0: ModelQueryFilter, F1: ModelQueryFilter,] ModelQueryFilterOr<F0, F1> => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), ColumnQueryFilterRaw => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), ColumnQueryFilterRaw => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_op!(BitAnd (bitand), SharedFilter => ModelQueryFilterAnd, "Alternative to [ModelQueryFilterExt::and]");
+impl_op!(BitOr (bitor), SharedFilter => ModelQueryFilterOr, "Alternative to [ModelQueryFilterExt::or]");
+
+impl_not!(ColumnQueryFilter);
+impl_not!(ColumnQueryFilterRaw);
+impl_not!(InQueryFilter);
+impl_not!(ColumnQueryFilterUnary);
+impl_not!(ExistsQueryFilter);
+impl_not!(ColumnComparisonFilter);
+impl_not!(SharedFilter);
+impl_not!([F: ModelQueryFilter] ModelQueryFilterNot<F>);
+impl_not!([F0: ModelQueryFilter, F1: ModelQueryFilter] ModelQueryFilterAnd<F0, F1>);
+impl_not!([F0: ModelQueryFilter, F1: ModelQueryFilter] ModelQueryFilterOr<F0, F1>);
+
 
 pub struct ModelInsertQuery<M: Model> {
     model: PhantomData<M>,
     columns: Vec<String>,
     values: Vec<Vec<Box<dyn ToSql>>>,
+    // Values of the `Vec<T>` fields of every row (empty if the model has none)
+    collections: Vec<Vec<CollectionValues>>,
+    on_conflict: Option<String>,
+}
+
+impl<M: Model> ModelInsertQuery<M> {
+    /// Skip rows which would violate the uniqueness of the given column, other constraint violations still return an error.
+    /// 
+    /// Unlike `INSERT OR IGNORE`, this only ignores conflicts on the given column,
+    /// which has to be the primary key or have a `UNIQUE` constraint.
+    /// 
+    /// ## Arguments
+    /// * `target` - The column that may conflict
+    /// 
+    /// ## Returns
+    /// A [ModelInsertOnConflict], which returns the id of the last inserted row, or `None` if every row was skipped.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// match user.into_insertable().on_conflict_do_nothing(User::email).exec(&conn).unwrap() {
+    ///     Some(id) => println!("Inserted user {}", id),
+    ///     None => println!("The email is already taken"),
+    /// }
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// INSERT INTO users (name, email) VALUES (?, ?) ON CONFLICT(email) DO NOTHING RETURNING rowid;
+    /// ```
+    pub fn on_conflict_do_nothing(mut self, target: Column<'static>) -> ModelInsertOnConflict<M> {
+        debug_assert_eq!(target.table_name, M::table_name(), "Conflict target {} is not a column of {}", target.name(), M::table_name());
+        self.on_conflict = Some(format!("ON CONFLICT({}) DO NOTHING", target.name()));
+        ModelInsertOnConflict { query: self }
+    }
+}
+
+/// An insert which skips conflicting rows, created with [on_conflict_do_nothing](ModelInsertQuery::on_conflict_do_nothing).
+pub struct ModelInsertOnConflict<M: Model> {
+    query: ModelInsertQuery<M>,
+}
+
+impl<M: Model> Insertable for ModelInsertOnConflict<M> {
+    fn get_query(&mut self) -> RawQuery {
+        self.query.get_query()
+    }
+
+    fn get_queries(&mut self, max_params: usize) -> Vec<RawQuery> {
+        self.query.get_queries(max_params)
+    }
+
+    fn take_collections(&mut self) -> Vec<Vec<CollectionValues>> {
+        self.query.take_collections()
+    }
+}
+
+impl<M: Model> Executable<Option<i64>> for ModelInsertOnConflict<M> {
+    /// Insert the rows, returning the id of the last inserted row, or `None` if every row was skipped.
+    fn exec(mut self, conn: &crate::prelude::Connection) -> Result<Option<i64>, rusqlite::Error> {
+        conn.insert_returning_ids(&mut self).map(|ids| ids.last().copied())
+    }
 }
 
 impl<M: Model> Insertable for ModelInsertQuery<M> {
     fn get_query(&mut self) -> RawQuery {
-        let mut sql = format!("INSERT INTO {} (", M::table_name());
+        let mut sql = format!("INSERT INTO {} (", qualified_table_name(M::table_name()));
         for column in &self.columns {
             sql.push_str(column);
             sql.push_str(", ");
@@ -787,20 +2326,57 @@ impl<M: Model> Insertable for ModelInsertQuery<M> {
         }
         sql.pop();
 
+        if let Some(on_conflict) = &self.on_conflict {
+            sql.push(' ');
+            sql.push_str(on_conflict);
+        }
+
         RawQuery {
             sql,
             params,
         }
     }
+
+    fn get_queries(&mut self, max_params: usize) -> Vec<RawQuery> {
+        let rows_per_query = (max_params / self.columns.len().max(1)).max(1);
+        if self.values.len() <= rows_per_query {
+            return vec![self.get_query()];
+        }
+
+        let mut queries = Vec::new();
+        let mut rest = std::mem::take(&mut self.values);
+        while !rest.is_empty() {
+            let remaining = rest.split_off(rows_per_query.min(rest.len()));
+            self.values = rest;
+            queries.push(self.get_query());
+            rest = remaining;
+        }
+        queries
+    }
+
+    fn take_collections(&mut self) -> Vec<Vec<CollectionValues>> {
+        std::mem::take(&mut self.collections)
+    }
+}
+
+impl<M: Model> Executable<i64> for ModelInsertQuery<M> {
+    /// Insert the rows, returning the id of the last inserted row (like [Connection::insert](crate::connection::Connection::insert)).
+    fn exec(mut self, conn: &crate::prelude::Connection) -> Result<i64, rusqlite::Error> {
+        conn.insert_queries(&mut self)
+    }
 }
 
-impl<M: Model> IntoInsertable for M {
+impl<M: Model + WritableModel> IntoInsertable for M {
     type Insertable = ModelInsertQuery<M>;
 
     fn into_insertable(&self) -> ModelInsertQuery<M> {
         let mut columns = Vec::new();
         let mut values = Vec::new();
         for column in M::columns() {
+            // Generated columns are computed by the database, and timestamps default to the current time
+            if column.is_generated() || column.is_auto_now_add() {
+                continue;
+            }
             let cv = self.column_value(column);
 
             if !column.can_insert_null() && cv.is_none() {
@@ -813,10 +2389,18 @@ impl<M: Model> IntoInsertable for M {
             }
         }
 
+        let collections = if M::collections().is_empty() {
+            Vec::new()
+        } else {
+            vec![self.collection_values()]
+        };
+
         ModelInsertQuery {
             model: PhantomData,
             columns,
             values: vec![values], // Only one row
+            collections,
+            on_conflict: None,
         }
     }
 }
@@ -827,6 +2411,7 @@ impl<M: Model, const N: usize, I: IntoInsertable<Insertable = ModelInsertQuery<M
     fn into_insertable(&self) -> Self::Insertable {
         let mut columns = Vec::new();
         let mut values = Vec::new();
+        let mut collections = Vec::new();
         
         for v in self.iter() {
             let mut insertable = v.into_insertable();
@@ -839,12 +2424,15 @@ impl<M: Model, const N: usize, I: IntoInsertable<Insertable = ModelInsertQuery<M
 
             let v = insertable.values.pop().unwrap();
             values.push(v);
+            collections.append(&mut insertable.collections);
         }
 
         ModelInsertQuery {
             model: PhantomData,
             columns,
             values,
+            collections,
+            on_conflict: None,
         }
     }
 }
@@ -855,6 +2443,7 @@ impl<M: Model, I: IntoInsertable<Insertable = ModelInsertQuery<M>>> IntoInsertab
     fn into_insertable(&self) -> Self::Insertable {
         let mut columns = Vec::new();
         let mut values = Vec::new();
+        let mut collections = Vec::new();
         
         for v in self.iter() {
             let mut insertable = v.into_insertable();
@@ -867,12 +2456,15 @@ impl<M: Model, I: IntoInsertable<Insertable = ModelInsertQuery<M>>> IntoInsertab
 
             let v = insertable.values.pop().unwrap();
             values.push(v);
+            collections.append(&mut insertable.collections);
         }
 
         ModelInsertQuery {
             model: PhantomData,
             columns,
             values,
+            collections,
+            on_conflict: None,
         }
     }
 }
\ No newline at end of file
@@ -1,8 +1,8 @@
 use std::fmt::Debug;
 
-use rusqlite::{types::FromSql, ToSql};
+use rusqlite::{types::{FromSql, Value, ValueRef}, ToSql};
 
-use crate::{IntoSqlite, prelude::{Executable, Connection, ColumnQueryFilterImpl}};
+use crate::{IntoSqlite, prelude::{Executable, Connection, ColumnQueryFilterImpl}, sql_types::{SqliteType, SqliteTyped}};
 
 use super::{Model, query::ModelQuery, Column};
 
@@ -30,7 +30,7 @@ use super::{Model, query::ModelQuery, Column};
 /// let mut conn = Connection::new_memory().unwrap();
 /// conn.register::<User>().unwrap();
 /// conn.register::<Post>().unwrap();
-/// conn.migrate();
+/// conn.migrate().unwrap();
 /// 
 /// let user_id = User {
 ///     id: None,
@@ -48,23 +48,41 @@ use super::{Model, query::ModelQuery, Column};
 /// assert_eq!(post_id, post.author.get_id());
 /// ```
 pub struct Relation<M> where M: Model {
-    related_key: Option<i64>,
-    related: Option<M>,
+    // The value of the referenced column, usually the id of the related row
+    related_key: Option<Value>,
+    // Boxed, so that a model can have a relation to itself (eg. `manager: Option<Relation<Self>>`)
+    related: Option<Box<M>>,
+    // The referenced column, if it is not the id (see `#[references = "column"]`)
+    key_column: Option<Column<'static>>,
 
     marker: std::marker::PhantomData<M>
 }
 
+/// The type of a relation column referencing the id of the related model.
+pub(crate) const RELATION_KEY_TYPE: SqliteType = SqliteType::Integer;
+
+/// Relations referencing the id are stored as integers.
+/// The `Model` derive gives relations marked with `#[references = "column"]` the type of the referenced column instead.
+impl<M: Model> SqliteTyped for Relation<M> {
+    const SQLITE_TYPE: SqliteType = RELATION_KEY_TYPE;
+}
+
 impl<M: Model> ToSql for Relation<M> {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
-        Ok(rusqlite::types::ToSqlOutput::Owned(rusqlite::types::Value::Integer(self.related_key.unwrap_or(0))))
+        // A relation without an id is stored as NULL, not as a reference to row 0
+        Ok(match &self.related_key {
+            Some(key) => rusqlite::types::ToSqlOutput::Borrowed(key.into()),
+            None => rusqlite::types::ToSqlOutput::Owned(Value::Null),
+        })
     }
 }
 
 impl<M: Model> Clone for Relation<M> {
     fn clone(&self) -> Self {
         Relation {
-            related_key: self.related_key,
+            related_key: self.related_key.clone(),
             related: None,
+            key_column: self.key_column.clone(),
 
             marker: Default::default()
         }
@@ -74,8 +92,12 @@ impl<M: Model> Clone for Relation<M> {
 impl<M: Model> FromSql for Relation<M> {
     fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
         Ok(Relation {
-            related_key: value.as_i64().ok(),
+            related_key: match Value::column_result(value)? {
+                Value::Null => None,
+                key => Some(key),
+            },
             related: None,
+            key_column: None,
 
             marker: Default::default()
         })
@@ -87,6 +109,7 @@ impl<M> Default for Relation<M> where M: Model {
         Self {
             related_key: None,
             related: None,
+            key_column: None,
 
             marker: Default::default()
         }
@@ -95,16 +118,15 @@ impl<M> Default for Relation<M> where M: Model {
 
 impl<M: Model + Debug> Debug for Relation<M> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.related.is_none() {
-            f.debug_struct("UnfetchedRelation")
+        match &self.related {
+            None => f.debug_struct("UnfetchedRelation")
                 .field("table", &M::table_name())
-                .field("id", &self.related_key.unwrap())
-                .finish()
-        } else {
-            f.debug_struct("Relation")
+                .field("key", &self.related_key)
+                .finish(),
+            Some(related) => f.debug_struct("Relation")
                 .field("table", &M::table_name())
-                .field("id", &self.related_key.unwrap())
-                .field("model", &self.related.as_ref().unwrap())
+                .field("key", &self.related_key)
+                .field("model", related)
                 .finish()
         }
     }
@@ -112,58 +134,124 @@ impl<M: Model + Debug> Debug for Relation<M> {
 
 impl<M: Model> Relation<M> {
     /// Create a new relation from an id in the related table.
+    /// 
+    /// Relations referencing another column (`#[references = "column"]`) are created with [by_key](Relation::by_key).
     pub fn id(id: impl Into<i64>) -> Self {
         Relation {
-            related_key: Some(id.into()),
+            related_key: Some(Value::Integer(id.into())),
             ..Default::default()
         }
     }
 
-    /// Create a new relation from a model that is already in the database.
-    pub fn model(model: &M) -> Self {
+    /// Create a new relation from the value of a column of the related model,
+    /// for relations referencing that column (`#[references = "column"]`) instead of the id.
+    /// 
+    /// ## Arguments
+    /// * `column` - The referenced column of the related model
+    /// * `key` - The value of that column in the related row
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let login = Login { id: None, user: Relation::by_key(User::email, "john@example.com".to_string()) };
+    /// ```
+    pub fn by_key(column: Column<'static>, key: impl Into<Value>) -> Self {
+        let key_column = if column.name_const() == M::id_column().name_const() {
+            None
+        } else {
+            Some(column)
+        };
+
         Relation {
-            related_key: Some(model.get_id()),
+            related_key: Some(key.into()),
+            key_column,
             ..Default::default()
         }
     }
 
+    /// Create a new relation from a model that is already in the database.
+    /// 
+    /// This uses the id of the model, relations referencing another column are created with [by_key](Relation::by_key).
+    pub fn model(model: &M) -> Self {
+        Self::id(model.get_id())
+    }
+
     /// This function is used to fetch the related model from the database.
     /// It should not be called manually unless you know what you're doing.
-    pub fn parse_from_row(row: &rusqlite::Row, offset: usize, idx: usize, counter: &mut usize, is_joined: bool) -> Self {
-        if !is_joined {
-            let related_key = row.get::<_, i64>(offset + idx);
+    pub fn parse_from_row(row: &rusqlite::Row, offset: usize, idx: usize, counter: &mut usize, is_joined: bool, key_column: &'static Column<'static>) -> rusqlite::Result<Self> {
+        // The foreign key holds the value of the referenced column, which is not always the id
+        let related_key = row.get::<_, Value>(offset + idx)?;
+        let key_column = if key_column.name_const() == M::id_column().name_const() {
+            None
+        } else {
+            Some(key_column.clone())
+        };
 
-            return Relation {
-                related_key: Some(related_key.unwrap()),
+        if !is_joined {
+            return Ok(Relation {
+                related_key: Some(related_key),
+                key_column,
                 ..Default::default()
-            }
+            })
         }
 
-
-        let related = M::parse_row(row, offset + *counter, &Vec::new());
+        let related = M::parse_row(row, offset + *counter, &Vec::new())?;
         *counter += M::count_columns();
         
-        Relation {
-            related_key: Some(related.get_id()),
-            related: Some(related),
+        Ok(Relation {
+            related_key: Some(related_key),
+            related: Some(Box::new(related)),
+            key_column,
 
             marker: Default::default()
+        })
+    }
+
+    /// Same as [parse_from_row](Relation::parse_from_row), but for `Option<Relation<M>>` fields.
+    /// Returns `None` if the foreign key is NULL.
+    pub fn parse_optional_from_row(row: &rusqlite::Row, offset: usize, idx: usize, counter: &mut usize, is_joined: bool, key_column: &'static Column<'static>) -> rusqlite::Result<Option<Self>> {
+        if row.get_ref(offset + idx)? == ValueRef::Null {
+            // The joined columns are still there (as NULLs)
+            if is_joined {
+                *counter += M::count_columns();
+            }
+            return Ok(None);
         }
+
+        Self::parse_from_row(row, offset, idx, counter, is_joined, key_column).map(Some)
     }
 
-    /// Get the id of the related model
+    /// Get the id of the related model.
+    /// 
+    /// Returns `None` for relations referencing another column, their key is returned by [get_key](Relation::get_key).
     pub fn get_id(&self) -> Option<i64> {
-        self.related_key
+        match (&self.key_column, &self.related_key) {
+            (None, Some(Value::Integer(id))) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Get the value of the referenced column, which is the id of the related model
+    /// unless the relation references another column (`#[references = "column"]`).
+    pub fn get_key(&self) -> Option<&Value> {
+        self.related_key.as_ref()
+    }
+
+    // The column of the related model the key refers to
+    fn key_column(&self) -> Column<'static> {
+        match &self.key_column {
+            Some(column) => column.clone(),
+            None => M::id_column(),
+        }
     }
 
     /// Try to get the related model if it's already loaded
     pub fn try_get(&self) -> Option<&M> {
-        self.related.as_ref()
+        self.related.as_deref()
     }
 
     /// Try to get the related model taking it out if it's already loaded
     pub fn try_take(&mut self) -> Option<M> {
-        self.related.take()
+        self.related.take().map(|related| *related)
     }
 
     /// Get the related model if it's already loaded, otherwise fetch it from the database
@@ -172,7 +260,7 @@ impl<M: Model> Relation<M> {
             self.fetch(conn)?;
         }
 
-        Ok(self.related.as_ref().unwrap())
+        Ok(self.related.as_deref().unwrap())
     }
 
     /// Get the related model taking it out if it's already loaded, otherwise fetch it from the database
@@ -181,26 +269,26 @@ impl<M: Model> Relation<M> {
             self.fetch(conn)?;
         }
 
-        Ok(self.related.take().unwrap())
+        Ok(*self.related.take().unwrap())
     }
 
     /// Fetch the related model from the database
     pub fn fetch(&mut self, conn: &Connection) -> rusqlite::Result<&M> {
         let select_query = ModelQuery::<M>::select()
-            .filter(M::id_column().eq(self.get_id()))
+            .filter(self.key_column().eq(self.related_key.clone()))
             .limit(1);
 
         if self.related.is_none() {
-            self.related = Some(select_query.exec(conn).unwrap().into_iter().next().unwrap());
+            self.related = Some(Box::new(select_query.exec(conn).unwrap().into_iter().next().unwrap()));
         }
 
-        Ok(self.related.as_ref().unwrap())
+        Ok(self.related.as_deref().unwrap())
     }
 
     /// Fetch the related model from the database and take it out
     pub fn fetch_once(&self, conn: &Connection) -> rusqlite::Result<M> {
         let select_query = ModelQuery::<M>::select()
-            .filter(M::id_column().eq(self.get_id()))
+            .filter(self.key_column().eq(self.related_key.clone()))
             .limit(1);
 
         Ok(select_query.exec(conn).unwrap().into_iter().next().unwrap())
@@ -1,14 +1,19 @@
 use rusqlite::ToSql;
 
-use crate::{connection::{RawQuery, Queryable, Executable}, IntoSqlite};
+use crate::connection::{RawQuery, Queryable, Executable, qualified_table_name, substitute_params};
 
-use super::{Model, Column, query::{ModelQueryFilter, ColumnQueryOrder}};
+use super::{Model, WritableModel, Column, query::{ColumnQueryFilterImpl, ModelQueryFilter, SharedFilter, QueryOrder, append_order, combine_filters, debug_assert_filter_tables, limit_offset}};
 
 /// Query that updates rows in a table.
 pub struct ModelUpdateQuery<T: Model> {
+    /// Everything that goes after the WHERE clause (ORDER BY, ...)
     pub query: RawQuery,
+    pub filter: Option<RawQuery>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
     pub columns: Vec<Column<'static>>,
-    pub values: Vec<Box<dyn rusqlite::types::ToSql>>,
+    /// Expressions assigned to the columns (`?` for plain values)
+    pub values: Vec<RawQuery>,
     marker: std::marker::PhantomData<T>
 }
 
@@ -22,6 +27,9 @@ impl<T: Model> ModelUpdateQuery<T> {
     pub fn new() -> Self {
         ModelUpdateQuery {
             query: RawQuery::new("".to_string(), Vec::new()),
+            filter: None,
+            limit: None,
+            offset: None,
             columns: Vec::new(),
             values: Vec::new(),
             marker: Default::default()
@@ -33,9 +41,7 @@ impl<T: Model> ModelUpdateQuery<T> {
         params_old.extend(other.params);
         ModelUpdateQuery { 
             query: RawQuery::new(format!("{} {}", self.query.sql, other.sql), params_old),
-            columns: self.columns,
-            values: self.values,
-            marker: Default::default()
+            ..self
         }
     }
 
@@ -55,10 +61,30 @@ impl<T: Model> ModelUpdateQuery<T> {
     ///     .filter(User::id.eq(1))
     ///     .exec(&conn).unwrap();
     /// ```
+    /// 
+    /// ## Note
+    /// Calling this multiple times will combine the filters with AND.
     pub fn filter(self, mut filter: impl ModelQueryFilter) -> Self {
-        let mut filter_query = filter.get_query();
-        filter_query.sql = format!("WHERE {}", filter_query.sql);
-        ModelUpdateQuery::combine(self, filter_query)
+        debug_assert_filter_tables(&filter, T::table_name(), &[]);
+        let filter_query = filter.get_query();
+        ModelUpdateQuery {
+            filter: Some(combine_filters(self.filter, filter_query)),
+            ..self
+        }
+    }
+
+    /// Filter the query with a reusable filter
+    /// 
+    /// ## Arguments
+    /// * `filter` - The shared filter to apply, it is cloned so it can be applied again
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let inactive = SharedFilter::new(User::last_login.lt(cutoff)).unwrap();
+    /// User::update().set(User::active, false).apply_filter(&inactive).exec(&conn).unwrap();
+    /// ```
+    pub fn apply_filter(self, filter: &SharedFilter) -> Self {
+        self.filter(filter.clone())
     }
 
     // Limit and offset
@@ -80,7 +106,10 @@ impl<T: Model> ModelUpdateQuery<T> {
     ///     .exec(&conn).unwrap();
     /// ```
     pub fn limit(self, limit: u32) -> Self {
-        self.combine(RawQuery::new("LIMIT ?".to_string(), vec![Box::new(limit)]))
+        ModelUpdateQuery {
+            limit: Some(limit),
+            ..self
+        }
     }
 
     /// Offset the number of rows returned by the query.
@@ -101,7 +130,10 @@ impl<T: Model> ModelUpdateQuery<T> {
     ///     .exec(&conn).unwrap();
     /// ```
     pub fn offset(self, offset: u32) -> Self {
-        self.combine(RawQuery::new("OFFSET ?".to_string(), vec![Box::new(offset)]))
+        ModelUpdateQuery {
+            offset: Some(offset),
+            ..self
+        }
     }
 
     // Order
@@ -123,8 +155,12 @@ impl<T: Model> ModelUpdateQuery<T> {
     ///     .order_by(User::id.desc())
     ///     .exec(&conn).unwrap();
     /// ```
-    pub fn order_by(self, order: ColumnQueryOrder) -> Self {
-        self.combine(RawQuery::new(format!("ORDER BY {}", order.into_sqlite()), Vec::new()))
+    pub fn order_by(self, order: impl QueryOrder) -> Self {
+        let sql = append_order(&self.query.sql, &order.order_sql());
+        ModelUpdateQuery {
+            query: RawQuery::new(sql, self.query.params),
+            ..self
+        }
     }
 
     // Update value for a column
@@ -144,47 +180,210 @@ impl<T: Model> ModelUpdateQuery<T> {
     ///     .exec(&conn).unwrap();
     /// ```
     pub fn set<V: ToSql + 'static>(self, column: Column<'static>, value: V) -> Self {
+        self.set_expression(column, RawQuery::new("?".to_string(), vec![Box::new(value)]))
+    }
+
+    /// Increment the value of a column in the rows updated by the query.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to increment
+    /// * `amount` - The amount to add to the column
+    /// 
+    /// ## Returns
+    /// A new query with the increment applied.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::update()
+    ///     .increment(User::visits, 1)
+    ///     .filter(User::id.eq(1))
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// UPDATE users SET visits=visits + ? WHERE users.id = ?;
+    /// ```
+    pub fn increment<V: ToSql + 'static>(self, column: Column<'static>, amount: V) -> Self {
+        let sql = format!("{} + ?", column.name());
+        self.set_expression(column, RawQuery::new(sql, vec![Box::new(amount)]))
+    }
+
+    /// Decrement the value of a column in the rows updated by the query.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to decrement
+    /// * `amount` - The amount to subtract from the column
+    /// 
+    /// ## Returns
+    /// A new query with the decrement applied.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::update()
+    ///     .decrement(User::credits, 5)
+    ///     .filter(User::id.eq(1))
+    ///     .exec(&conn).unwrap();
+    /// ```
+    pub fn decrement<V: ToSql + 'static>(self, column: Column<'static>, amount: V) -> Self {
+        let sql = format!("{} - ?", column.name());
+        self.set_expression(column, RawQuery::new(sql, vec![Box::new(amount)]))
+    }
+
+    /// Set the value of a column separately for each row, identified by its id.
+    /// 
+    /// ## Arguments
+    /// * `column` - The column to set the value of
+    /// * `values` - Pairs of row id and the value to set the column to for that row
+    /// 
+    /// ## Returns
+    /// A new query with the values set, filtered to the listed rows.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// User::update()
+    ///     .set_each(User::name, &[(1, "John"), (2, "Jane")])
+    ///     .exec(&conn).unwrap();
+    /// ```
+    /// This will generate the following SQL query:
+    /// ```sql
+    /// UPDATE users SET name=CASE id WHEN ? THEN ? WHEN ? THEN ? ELSE name END WHERE users.id IN (?, ?);
+    /// ```
+    /// 
+    /// ## Note
+    /// Passing an empty slice does nothing.
+    /// The filter on ids is combined with other filters using AND, so calling this multiple times only updates rows listed in every call.
+    pub fn set_each<V: ToSql + Clone + 'static>(self, column: Column<'static>, values: &[(i64, V)]) -> Self {
+        if values.is_empty() {
+            return self;
+        }
+
+        let mut sql = format!("CASE {}", T::id_column().name());
+        let mut params: Vec<Box<dyn ToSql>> = Vec::new();
+        for (id, value) in values {
+            sql.push_str(" WHEN ? THEN ?");
+            params.push(Box::new(*id));
+            params.push(Box::new(value.clone()));
+        }
+        sql = format!("{} ELSE {} END", sql, column.name());
+
+        let ids = values.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        self.set_expression(column, RawQuery::new(sql, params))
+            .filter(T::id_column().in_(ids))
+    }
+
+    fn set_expression(self, column: Column<'static>, expression: RawQuery) -> Self {
         let mut columns = self.columns;
         let mut values = self.values;
 
         columns.push(column);
-        values.push(Box::new(value));
+        values.push(expression);
         ModelUpdateQuery {
-            query: self.query,
             columns,
             values,
-            marker: Default::default()
+            ..self
         }
     }
 
     // TODO: Add support for multiple values in one function
 }
 
-impl<M: Model> Queryable<()> for ModelUpdateQuery<M> {
-    fn get_query(&mut self) -> RawQuery {
-        let mut sql = format!("UPDATE {} SET ", M::table_name());
+impl<M: Model + WritableModel> ModelUpdateQuery<M> {
+    /// Build the SQL of the query (with `?` in place of parameters)
+    fn sql(&self) -> String {
+        let mut sql = format!("UPDATE {} SET ", qualified_table_name(M::table_name()));
 
         // Set columns
-        for (i, column) in self.columns.iter().enumerate() {
-            sql = format!("{}{}=?", sql, column.name());
-            if i != self.columns.len() - 1 {
-                sql = format!("{}, ", sql);
+        let mut assignments = self.columns.iter().zip(self.values.iter())
+            .map(|(column, value)| format!("{}={}", column.name(), value.sql))
+            .collect::<Vec<_>>();
+        // Bump #[auto_now] columns, unless they are set explicitly
+        for column in M::columns().iter().filter(|column| column.is_auto_now()) {
+            if !self.columns.iter().any(|set| set.name() == column.name()) {
+                assignments.push(format!("{}=CURRENT_TIMESTAMP", column.name()));
             }
         }
+        sql.push_str(&assignments.join(", "));
 
-        // Combine params
-        let mut params = Vec::new();
-        for value in self.values.drain(..) {
-            params.push(value);
+        // Filter
+        if let Some(filter) = &self.filter {
+            sql = format!("{} WHERE {}", sql, filter.sql);
         }
 
+        format!("{}{}{}", sql, self.query.sql, limit_offset(self.limit, self.offset).sql)
+    }
+
+    /// Get the SQL of the query, with `?` in place of parameters.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let query = User::update().set(User::name, "John").filter(User::id.eq(1));
+    /// assert_eq!(query.to_sql(), "UPDATE users SET name=? WHERE users.id = ?");
+    /// assert_eq!(query.parameter_count(), 2);
+    /// ```
+    pub fn to_sql(&self) -> String {
+        self.sql()
+    }
+
+    /// Get the SQL of the query together with its bound parameters.
+    /// 
+    /// This consumes the query, as its parameters are moved into the returned [RawQuery](crate::connection::RawQuery).
+    /// Use [to_sql](Self::to_sql) to only inspect the SQL of a query that is executed later.
+    /// 
+    /// ## Example
+    /// ```rs
+    /// let raw = User::update().set(User::age, 18).filter(User::id.eq(1)).to_raw();
+    /// assert_eq!(raw.sql, "UPDATE users SET age=? WHERE users.id = ?");
+    /// assert_eq!(raw.params.len(), 2);
+    /// ```
+    pub fn to_raw(mut self) -> RawQuery {
+        self.get_query()
+    }
+
+    /// Get the number of parameters bound to the query (the number of `?` in [to_sql](Self::to_sql)).
+    pub fn parameter_count(&self) -> usize {
+        let limits = limit_offset(self.limit, self.offset);
+        self.values.iter()
+            .chain(self.filter.iter())
+            .chain([&self.query, &limits])
+            .map(|query| query.params.len())
+            .sum()
+    }
+
+    /// Get the SQL of the query with parameters substituted, eg. for logging.
+    /// 
+    /// Text is quoted and escaped, and blobs are hex encoded, so the result can be copied and executed as it is.
+    /// This is only meant for debugging, always use parameters when executing queries.
+    pub fn debug_sql(&self) -> String {
+        let limits = limit_offset(self.limit, self.offset);
+        let params = self.values.iter()
+            .chain(self.filter.iter())
+            .chain([&self.query, &limits])
+            .flat_map(|query| query.params.iter().map(|p| p.as_ref()))
+            .collect::<Vec<&dyn ToSql>>();
+        substitute_params(&self.sql(), &params)
+    }
+}
+
+impl<M: Model + WritableModel> Queryable<()> for ModelUpdateQuery<M> {
+    fn get_query(&mut self) -> RawQuery {
+        let sql = self.sql();
+
+        let mut params = Vec::new();
+        for value in self.values.iter_mut() {
+            params.append(&mut value.params);
+        }
+        if let Some(filter) = &mut self.filter {
+            params.append(&mut filter.params);
+        }
         params.append(&mut self.query.params);
+        params.append(&mut limit_offset(self.limit, self.offset).params);
 
-        RawQuery::new(format!("{}{}", sql, self.query.sql), params)
+        RawQuery::new(sql, params)
     }
 
-    fn parse_result(&mut self, _rows: rusqlite::Rows) {
+    fn parse_result(&mut self, _rows: rusqlite::Rows) -> Result<(), rusqlite::Error> {
         // Nothing to parse
+        Ok(())
     }
 
     fn should_execute(&self) -> bool {
@@ -192,8 +391,13 @@ impl<M: Model> Queryable<()> for ModelUpdateQuery<M> {
     }
 }
 
-impl<M: Model> Executable<usize> for ModelUpdateQuery<M> {
+impl<M: Model + WritableModel> Executable<usize> for ModelUpdateQuery<M> {
     fn exec(self, conn: &crate::connection::Connection) -> Result<usize, rusqlite::Error> {
+        // Nothing to update (eg. set_each with empty values)
+        if self.columns.is_empty() {
+            return Ok(0);
+        }
+
         conn.exec(self)
     }
 }
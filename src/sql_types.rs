@@ -12,6 +12,96 @@ pub enum SqliteType {
     DateTime,
 }
 
+impl SqliteType {
+    /// Get the SQLite type that the `Model` derive uses for a Rust type.
+    /// 
+    /// The types are looked up in the implementations of [SqliteTyped], which the derive uses as well.
+    /// 
+    /// ## Arguments
+    /// * `name` - The name of the Rust type as written in a model, eg. `i32`, `Option<String>` or `Vec<u8>`
+    /// 
+    /// ## Returns
+    /// The SQLite type, or `None` if the type is not known (enums deriving `SqliteEnum` decide their type themselves)
+    /// 
+    /// ## Example
+    /// ```rs
+    /// assert_eq!(SqliteType::for_rust_type("Option<String>"), Some(SqliteType::Text));
+    /// assert_eq!(SqliteType::for_rust_type("Vec<u8>"), Some(SqliteType::Blob));
+    /// ```
+    /// 
+    /// ## Note
+    /// `Relation<M>` is reported as `Integer`, the type of the id it references.
+    /// A relation marked with `#[references = "column"]` has the type of the referenced column instead, which is not known from the name.
+    pub fn for_rust_type(name: &str) -> Option<SqliteType> {
+        let name: String = name.chars().filter(|c| !c.is_whitespace()).collect();
+        if let Some((outer, inner)) = name.strip_suffix('>').and_then(|name| name.split_once('<')) {
+            match outer {
+                "Option" => return SqliteType::for_rust_type(inner),
+                "Relation" => return Some(crate::model::relation::RELATION_KEY_TYPE),
+                #[cfg(feature = "serde")]
+                "Json" => return Some(<crate::model::json::Json<()> as SqliteTyped>::SQLITE_TYPE),
+                _ => {}
+            }
+        }
+
+        #[cfg(feature = "decimal")]
+        if name == "Decimal" {
+            return Some(<crate::model::decimal::Decimal as SqliteTyped>::SQLITE_TYPE);
+        }
+
+        // Paths like `chrono::NaiveDateTime` can be written without the module as well
+        RUST_TYPES.iter()
+            .find(|(rust_type, _)| {
+                let rust_type: String = rust_type.chars().filter(|c| !c.is_whitespace()).collect();
+                rust_type == name || rust_type.rsplit("::").next() == Some(name.as_str())
+            })
+            .map(|(_, sqlite_type)| *sqlite_type)
+    }
+}
+
+/// A Rust type which can be stored in a column, and the type of that column.
+/// 
+/// This decides the column types of the `Model` derive (except for relations marked with `#[references = "column"]`,
+/// which have the type of the referenced column), and [SqliteType::for_rust_type] is built on the same implementations.
+/// Enums deriving `SqliteEnum` implement it as well.
+#[diagnostic::on_unimplemented(
+    message = "Unsupported type: `{Self}` can't be used as a model column",
+    label = "unsupported column type",
+    note = "fieldless enums can be used as columns by deriving `SqliteEnum`"
+)]
+pub trait SqliteTyped {
+    /// The type of the column the value is stored in.
+    const SQLITE_TYPE: SqliteType;
+}
+
+impl<T: SqliteTyped> SqliteTyped for Option<T> {
+    const SQLITE_TYPE: SqliteType = T::SQLITE_TYPE;
+}
+
+macro_rules! impl_sqlite_typed {
+    ($($sqlite_type:ident: $($ty:ty),*;)*) => {
+        $($(
+            impl SqliteTyped for $ty {
+                const SQLITE_TYPE: SqliteType = SqliteType::$sqlite_type;
+            }
+        )*)*
+
+        // The names of the types above, as looked up by `SqliteType::for_rust_type`
+        const RUST_TYPES: &[(&str, SqliteType)] = &[$($(
+            (stringify!($ty), <$ty as SqliteTyped>::SQLITE_TYPE),
+        )*)*];
+    };
+}
+
+// Values of u64 above i64::MAX cannot be stored and will fail on insert
+impl_sqlite_typed! {
+    Text: String;
+    Integer: i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, bool;
+    Real: f32, f64;
+    Blob: Vec<u8>;
+    DateTime: chrono::NaiveDateTime;
+}
+
 impl IntoSqlite for i32 {
     fn into_sqlite(&self) -> String {
         self.to_string()
@@ -24,6 +114,20 @@ impl IntoSqlite for i64 {
     }
 }
 
+macro_rules! impl_into_sqlite_int {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoSqlite for $ty {
+                fn into_sqlite(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_into_sqlite_int!(i8, i16, isize, u8, u16, u32, u64, usize);
+
 impl IntoSqlite for f32 {
     fn into_sqlite(&self) -> String {
         self.to_string()
@@ -97,6 +201,15 @@ impl SqliteType {
     }
 }
 
+/// A fieldless enum that can be used as a column type.
+/// 
+/// This should be implemented using `#[derive(SqliteEnum)]`, which stores variants by their discriminant
+/// or by their name when the enum is marked with `#[store_as_text]`.
+pub trait SqliteEnum: rusqlite::ToSql + rusqlite::types::FromSql {
+    /// The type of the column the enum is stored in.
+    const SQLITE_TYPE: SqliteType;
+}
+
 /// A flag for a column in a SQLite database.
 /// 
 /// This is used to determine the flags of a column when creating a table.